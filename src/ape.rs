@@ -0,0 +1,352 @@
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+use cue::Cue;
+use errors::CueError;
+use parser::parse;
+use writer;
+
+/// Key of the APEv2 item holding a CUE sheet
+pub const CUESHEET_KEY: &str = "Cuesheet";
+
+const PREAMBLE: &[u8] = b"APETAGEX";
+const FLAG_HAS_HEADER: u32 = 1 << 31;
+const FLAG_IS_HEADER: u32 = 1 << 29;
+
+/// An item of an APEv2 tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApeItem {
+    /// Item key, compared case-insensitively
+    pub key: String,
+    /// Item flags; bits 1-2 give the value type (0 is UTF-8 text)
+    pub flags: u32,
+    /// Raw item value
+    pub value: Vec<u8>,
+}
+
+/// An APEv2 tag, as used by Monkey's Audio and WavPack files.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ApeTag {
+    /// Items in tag order
+    pub items: Vec<ApeItem>,
+}
+
+/// Position of an APEv2 tag in a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApeTagLocation {
+    /// Offset of the first byte of the tag (its header, if present)
+    pub start: u64,
+    /// Offset of the byte after the tag footer
+    pub end: u64,
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid(reason: &str) -> CueError {
+    CueError::Parse(format!("invalid APEv2 tag: {}", reason))
+}
+
+impl ApeTag {
+    /// Locates and reads the APEv2 tag at the end of a file, before any ID3v1 tag.
+    ///
+    /// Returns `None` if the file has no APEv2 tag.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the tag is truncated or on IO errors.
+    pub fn read<R: Read + Seek>(
+        reader: &mut R,
+    ) -> Result<Option<(Self, ApeTagLocation)>, CueError> {
+        let footer_end = match locate_footer(reader)? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+
+        reader.seek(SeekFrom::Start(footer_end - 32))?;
+        let mut footer = [0u8; 32];
+        reader.read_exact(&mut footer)?;
+        let size = u64::from(le32(&footer[12..16]));
+        let count = le32(&footer[16..20]);
+        let flags = le32(&footer[20..24]);
+
+        if size < 32 || size > footer_end {
+            return Err(invalid("bad tag size"));
+        }
+        let items_start = footer_end - size;
+        let start = if flags & FLAG_HAS_HEADER != 0 {
+            items_start.saturating_sub(32)
+        } else {
+            items_start
+        };
+
+        reader.seek(SeekFrom::Start(items_start))?;
+        let mut data = vec![0u8; (size - 32) as usize];
+        reader.read_exact(&mut data)?;
+
+        let mut items = Vec::new();
+        let mut pos = 0;
+        for _ in 0..count {
+            let head = data
+                .get(pos..pos + 8)
+                .ok_or_else(|| invalid("item truncated"))?;
+            let len = le32(&head[0..4]) as usize;
+            let item_flags = le32(&head[4..8]);
+            let key_len = data[pos + 8..]
+                .iter()
+                .position(|b| *b == 0)
+                .ok_or_else(|| invalid("unterminated item key"))?;
+            let key = String::from_utf8_lossy(&data[pos + 8..pos + 8 + key_len]).into_owned();
+            let value_start = pos + 8 + key_len + 1;
+            let value = data
+                .get(value_start..value_start + len)
+                .ok_or_else(|| invalid("item value truncated"))?;
+            items.push(ApeItem {
+                key,
+                flags: item_flags,
+                value: value.to_vec(),
+            });
+            pos = value_start + len;
+        }
+
+        Ok(Some((
+            ApeTag { items },
+            ApeTagLocation {
+                start,
+                end: footer_end,
+            },
+        )))
+    }
+
+    /// Serializes the tag with both a header and a footer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut items = Vec::new();
+        for item in &self.items {
+            items.extend_from_slice(&(item.value.len() as u32).to_le_bytes());
+            items.extend_from_slice(&item.flags.to_le_bytes());
+            items.extend_from_slice(item.key.as_bytes());
+            items.push(0);
+            items.extend_from_slice(&item.value);
+        }
+
+        let frame = |flags: u32| {
+            let mut bytes = PREAMBLE.to_vec();
+            bytes.extend_from_slice(&2000u32.to_le_bytes());
+            bytes.extend_from_slice(&(items.len() as u32 + 32).to_le_bytes());
+            bytes.extend_from_slice(&(self.items.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&flags.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 8]);
+            bytes
+        };
+
+        let mut bytes = frame(FLAG_HAS_HEADER | FLAG_IS_HEADER);
+        bytes.extend_from_slice(&items);
+        bytes.extend(frame(FLAG_HAS_HEADER));
+        bytes
+    }
+
+    /// Returns the text of the item with `key` (compared case-insensitively), if present.
+    pub fn text(&self, key: &str) -> Option<String> {
+        self.items
+            .iter()
+            .find(|item| item.key.eq_ignore_ascii_case(key))
+            .map(|item| String::from_utf8_lossy(&item.value).into_owned())
+    }
+
+    /// Replaces or adds a UTF-8 text item with `key` (compared case-insensitively).
+    pub fn set_text(&mut self, key: &str, value: &str) {
+        let item = ApeItem {
+            key: key.to_string(),
+            flags: 0,
+            value: value.as_bytes().to_vec(),
+        };
+        match self
+            .items
+            .iter()
+            .position(|item| item.key.eq_ignore_ascii_case(key))
+        {
+            Some(i) => self.items[i] = item,
+            None => self.items.push(item),
+        }
+    }
+}
+
+// Returns the offset of the end of the APEv2 footer, skipping a trailing ID3v1 tag.
+fn locate_footer<R: Read + Seek>(reader: &mut R) -> Result<Option<u64>, CueError> {
+    let len = reader.seek(SeekFrom::End(0))?;
+
+    let mut end = len;
+    if len >= 128 {
+        reader.seek(SeekFrom::Start(len - 128))?;
+        let mut id3 = [0u8; 3];
+        reader.read_exact(&mut id3)?;
+        if &id3 == b"TAG" {
+            end = len - 128;
+        }
+    }
+
+    if end < 32 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(end - 32))?;
+    let mut preamble = [0u8; 8];
+    reader.read_exact(&mut preamble)?;
+
+    Ok(if preamble == PREAMBLE {
+        Some(end)
+    } else {
+        None
+    })
+}
+
+/// Reads the CUE sheet in the `Cuesheet` item of a file's APEv2 tag.
+///
+/// The sheet is parsed leniently. If it has a single FILE naming something other than
+/// `host_file`, it is [rebound](../cue/struct.Cue.html#method.rebind) to `host_file`.
+///
+/// Returns `None` if the file has no APEv2 tag or no `Cuesheet` item.
+///
+/// # Failures
+///
+/// Fails if the tag is invalid or on IO errors.
+pub fn read_cue<R: Read + Seek>(reader: &mut R, host_file: &str) -> Result<Option<Cue>, CueError> {
+    let sheet = match ApeTag::read(reader)? {
+        Some((tag, _)) => tag.text(CUESHEET_KEY),
+        None => None,
+    };
+
+    match sheet {
+        Some(sheet) => {
+            let mut cue = parse(&mut BufReader::new(sheet.as_bytes()), false)?;
+            cue.rebind(host_file);
+            Ok(Some(cue))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Copies a file from `input` to `output`, storing `cue` in the `Cuesheet` item of its APEv2
+/// tag. Other items are kept; a tag is added if the file has none. Any ID3v1 tag is kept after
+/// the APEv2 tag.
+///
+/// # Failures
+///
+/// Fails if an existing tag is invalid or on IO errors.
+pub fn write_cue<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    cue: &Cue,
+) -> Result<(), CueError> {
+    let len = input.seek(SeekFrom::End(0))?;
+    let (mut tag, audio_end, trailer_start) = match ApeTag::read(input)? {
+        Some((tag, location)) => (tag, location.start, location.end),
+        None => {
+            let end = locate_id3v1(input, len)?;
+            (ApeTag::default(), end, end)
+        }
+    };
+    tag.set_text(CUESHEET_KEY, &writer::to_string(cue));
+
+    input.seek(SeekFrom::Start(0))?;
+    io::copy(&mut input.by_ref().take(audio_end), output)?;
+    output.write_all(&tag.to_bytes())?;
+    input.seek(SeekFrom::Start(trailer_start))?;
+    io::copy(input, output)?;
+    Ok(())
+}
+
+fn locate_id3v1<R: Read + Seek>(reader: &mut R, len: u64) -> Result<u64, CueError> {
+    if len < 128 {
+        return Ok(len);
+    }
+    reader.seek(SeekFrom::Start(len - 128))?;
+    let mut id3 = [0u8; 3];
+    reader.read_exact(&mut id3)?;
+    Ok(if &id3 == b"TAG" { len - 128 } else { len })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+    use std::io::Cursor;
+
+    const AUDIO: &[u8] = b"MAC \x96\x0f\0\0audio";
+
+    fn id3v1() -> Vec<u8> {
+        let mut tag = b"TAG".to_vec();
+        tag.resize(128, b'x');
+        tag
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let mut output = Vec::new();
+        write_cue(&mut Cursor::new(AUDIO.to_vec()), &mut output, &cue).unwrap();
+        assert!(output.starts_with(AUDIO));
+
+        let read = read_cue(
+            &mut Cursor::new(output),
+            "My Bloody Valentine - Loveless.wav",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(read, cue);
+    }
+
+    #[test]
+    fn test_rebind_to_host_file() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let mut output = Vec::new();
+        write_cue(&mut Cursor::new(AUDIO.to_vec()), &mut output, &cue).unwrap();
+
+        let read = read_cue(&mut Cursor::new(output), "Loveless.ape")
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.files[0].file, "Loveless.ape");
+    }
+
+    #[test]
+    fn test_replace_keeps_items_and_id3v1() {
+        let mut tag = ApeTag::default();
+        tag.set_text("Artist", "My Bloody Valentine");
+        tag.set_text("CUESHEET", "old");
+        let mut input = AUDIO.to_vec();
+        input.extend(tag.to_bytes());
+        input.extend(id3v1());
+
+        let cue = parse_from_file("test/fixtures/catalog.cue", true).unwrap();
+        let mut output = Vec::new();
+        write_cue(&mut Cursor::new(input), &mut output, &cue).unwrap();
+        assert!(output.starts_with(AUDIO));
+        assert!(output.ends_with(&id3v1()));
+
+        let (tag, location) = ApeTag::read(&mut Cursor::new(&output)).unwrap().unwrap();
+        assert_eq!(location.start, AUDIO.len() as u64);
+        assert_eq!(tag.items.len(), 2);
+        assert_eq!(tag.text("artist"), Some("My Bloody Valentine".to_string()));
+        assert_eq!(tag.text(CUESHEET_KEY), Some(writer::to_string(&cue)));
+    }
+
+    #[test]
+    fn test_no_tag() {
+        assert!(ApeTag::read(&mut Cursor::new(AUDIO.to_vec()))
+            .unwrap()
+            .is_none());
+        assert!(read_cue(&mut Cursor::new(Vec::new()), "a.ape")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_truncated_tag() {
+        let mut tag = ApeTag::default();
+        tag.set_text(CUESHEET_KEY, "REM A b");
+        let mut bytes = tag.to_bytes();
+        // Claim more items than the tag holds
+        let count = bytes.len() - 16;
+        bytes[count] = 2;
+        assert!(ApeTag::read(&mut Cursor::new(bytes)).is_err());
+    }
+}
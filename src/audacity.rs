@@ -0,0 +1,171 @@
+use chapters::{
+    chapters, nanos_to_seconds, seconds_to_nanos, to_cue, ChapterMark, ExportOptions, ImportOptions,
+};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use util::frames_to_nanos;
+
+/// Exports a single-file `cue` as an Audacity label track, for an audio file `length` CD frames
+/// long.
+///
+/// Each track becomes a region label, `start<TAB>end<TAB>label`, with times in seconds to six
+/// decimals (see [`chapters`](../chapters/fn.chapters.html) for the spans). Labels are built
+/// from TITLE and PERFORMER according to the
+/// [`labels`](../chapters/struct.ExportOptions.html#structfield.labels) option.
+///
+/// # Example
+///
+/// ```
+/// use rcue::audacity::to_audacity_labels;
+/// use rcue::chapters::ExportOptions;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let labels = to_audacity_labels(&cue, 30000, &ExportOptions::default()).unwrap();
+/// assert_eq!(labels, "0.000000\t257.693333\tOnly Shallow\n257.693333\t400.000000\tLoomer\n");
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE.
+pub fn to_audacity_labels(
+    cue: &Cue,
+    length: u64,
+    options: &ExportOptions,
+) -> Result<String, CueError> {
+    let seconds = |frames: u64| nanos_to_seconds(frames_to_nanos(frames), 6, options.rounding);
+
+    let mut out = String::new();
+    for chapter in chapters(cue, length)? {
+        let label = options
+            .labels
+            .join(chapter.title.as_deref(), chapter.performer.as_deref())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{}\t{}\t{}\n",
+            seconds(chapter.start),
+            seconds(chapter.end),
+            label.replace(['\t', '\r', '\n'], " ")
+        ));
+    }
+    Ok(out)
+}
+
+/// Imports an Audacity label track as a single-file sheet for the audio file `file`.
+///
+/// Each label starts a track at its start time, rounded to CD frames as described in
+/// [`chapters::to_cue`](../chapters/fn.to_cue.html); label ends are ignored, as tracks run until
+/// the next one. Label text maps to TITLE and PERFORMER according to the
+/// [`labels`](../chapters/struct.ImportOptions.html#structfield.labels) option. Spectral
+/// selection lines (starting with `\`) and blank lines are skipped.
+///
+/// # Example
+///
+/// ```
+/// use rcue::audacity::from_audacity_labels;
+/// use rcue::chapters::ImportOptions;
+///
+/// let labels = "0.000000\t257.693333\tOnly Shallow\n257.693333\t400.000000\tLoomer\n";
+/// let (cue, diagnostics) =
+///     from_audacity_labels(labels, "Loveless.wav", &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.files[0].tracks[1].title, Some("Loomer".to_string()));
+/// assert!(diagnostics.is_empty());
+/// ```
+///
+/// # Failures
+///
+/// Fails if a label has an invalid start time, or there are more than 99 labels.
+pub fn from_audacity_labels(
+    text: &str,
+    file: &str,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let mut marks = Vec::new();
+
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, '\t');
+        let start = fields.next().unwrap_or("");
+        let start = seconds_to_nanos(start)
+            .ok_or_else(|| CueError::Parse(format!("line {}: bad label start {}", i + 1, start)))?;
+        fields.next();
+        let label = fields.next().unwrap_or("");
+
+        marks.push(ChapterMark {
+            start,
+            title: if label.is_empty() {
+                None
+            } else {
+                Some(label.to_string())
+            },
+            performer: None,
+        });
+    }
+
+    to_cue(&marks, file, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chapters::{LabelMapping, Rounding};
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_round_trip() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let options = ExportOptions {
+            labels: LabelMapping::PerformerTitle,
+            ..ExportOptions::default()
+        };
+        let labels = to_audacity_labels(&cue, 30000, &options).unwrap();
+        assert!(labels.ends_with("\tMy Bloody Valentine - Loomer\n"));
+
+        let options = ImportOptions {
+            labels: LabelMapping::PerformerTitle,
+            ..ImportOptions::default()
+        };
+        let (imported, diagnostics) =
+            from_audacity_labels(&labels, "Loveless.wav", &options).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            chapters(&imported, 30000).unwrap(),
+            chapters(&cue, 30000).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        let labels = "1.006\t1.006\tPoint\n\\\t100.0\t2000.0\n\n0.52\t1.0\t\n";
+        let options = ImportOptions {
+            rounding: Rounding::Down,
+            ..ImportOptions::default()
+        };
+        let (cue, diagnostics) = from_audacity_labels(labels, "a.wav", &options).unwrap();
+        let starts: Vec<u64> = chapters(&cue, 1000)
+            .unwrap()
+            .iter()
+            .map(|chapter| chapter.start)
+            .collect();
+        assert_eq!(starts, vec![39, 75]);
+        assert_eq!(cue.files[0].tracks[0].title, None);
+        assert_eq!(cue.files[0].tracks[1].title, Some("Point".to_string()));
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                Some(2),
+                "start rounded by 6000000ns to frame 75"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_bad_start() {
+        let options = ImportOptions::default();
+        assert!(from_audacity_labels("abc\t1\tx\n", "a.wav", &options).is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! `rcue` command-line tool, built with the `cli` feature.
+//!
+//! ```text
+//! rcue info    [--json] [SHEET]
+//! rcue lint    [--json] [--disable RULE,...] [--fix [-i | -o OUT]] [SHEET]
+//! rcue fmt     [--indent 2|4|tab] [--quote standard|always|needed] [--normalize] [--sort-rem]
+//!              [--crlf] [--bom] [-i | -o OUT] [SHEET]
+//! rcue json    [SHEET]
+//! rcue convert [--from FORMAT] --to FORMAT [--file NAME] [--length MM:SS:FF] [--fps N]
+//!              [-o OUT] [INPUT]
+//! ```
+//!
+//! Input is read from standard input when no file (or `-`) is given, and output is written to
+//! standard output unless `-o` or `-i` (in place) is given. `--strict` parses sheets in strict
+//! mode. Exit status is 0 on success, 1 when `lint` has findings, and 2 on errors.
+
+extern crate rcue;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+use std::time::Duration;
+
+use rcue::cue::Cue;
+use rcue::errors::{CueError, Diagnostic};
+use rcue::lint::{apply_fixes, lint_with, LintFinding, LintOptions, Rule, Source};
+use rcue::parser::parse;
+use rcue::playlist::PlaylistOptions;
+use rcue::toc;
+use rcue::util::{duration_to_frames, frames_to_timestamp, timestamp_to_duration};
+use rcue::writer::{self, FormatOptions, Indent, LineEnding, Quoting};
+use rcue::{audacity, chapters, edl, ffmetadata, image, json, matroska, ogm, playlist, reaper};
+
+const USAGE: &str = "\
+usage: rcue <command> [options] [SHEET]
+
+commands:
+  info      print the disc and its tracks
+  lint      check a sheet for common mistakes, exiting with 1 on findings
+  fmt       rewrite a sheet canonically (parsed in strict mode)
+  json      print the sheet model as JSON
+  convert   convert between CUE sheets and other formats
+
+options:
+  --json              machine-readable output (info, lint)
+  --strict            parse sheets in strict mode
+  --disable RULE,...  rules to skip (lint)
+  --fix               print the sheet with suggested fixes applied (lint)
+  -i, --in-place      overwrite the input file (fmt, lint --fix)
+  -o, --output PATH   write to PATH instead of standard output
+  --from FORMAT       input format of convert (default cue)
+  --to FORMAT         output format of convert
+  --file NAME         FILE of sheets imported by convert (default audio.wav)
+  --length MM:SS:FF   length of the audio file, for chapter exports
+  --fps N             EDL frame rate (default 25)
+  --indent N|tab      indentation per level of fmt (default 2)
+  --quote STYLE       quoting of fmt: standard, always or needed (default standard)
+  --normalize         uppercase REM names, FILE types, TRACK modes and FLAGS, and pad TRACK
+                      and INDEX numbers (fmt)
+  --sort-rem          sort REM comments by name (fmt)
+  --crlf              write CRLF line endings (fmt)
+  --bom               start with a UTF-8 byte order mark (fmt)
+
+formats: cue, toc, ccd, ffmetadata, matroska, ogm, edl, audacity, reaper (to and from);
+         m3u8, xspf, pls, json (to)
+";
+
+#[derive(Default)]
+struct Args {
+    command: String,
+    input: Option<String>,
+    output: Option<String>,
+    in_place: bool,
+    json: bool,
+    strict: bool,
+    fix: bool,
+    disabled: Vec<Rule>,
+    from: Option<String>,
+    to: Option<String>,
+    file: Option<String>,
+    length: Option<u64>,
+    fps: Option<u32>,
+    format: FormatOptions,
+}
+
+fn usage_error(message: &str) -> CueError {
+    CueError::Parse(format!("{}\n\n{}", message, USAGE.trim_end()))
+}
+
+fn parse_args(args: &[String]) -> Result<Args, CueError> {
+    let mut iter = args.iter();
+    let mut parsed = Args {
+        command: iter
+            .next()
+            .ok_or_else(|| usage_error("missing command"))?
+            .clone(),
+        ..Args::default()
+    };
+
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| usage_error(&format!("missing value for {}", name)))
+        };
+        match arg.as_ref() {
+            "--json" => parsed.json = true,
+            "--strict" => parsed.strict = true,
+            "--fix" => parsed.fix = true,
+            "-i" | "--in-place" => parsed.in_place = true,
+            "-o" | "--output" => parsed.output = Some(value(arg)?),
+            "--from" => parsed.from = Some(value(arg)?),
+            "--to" => parsed.to = Some(value(arg)?),
+            "--file" => parsed.file = Some(value(arg)?),
+            "--disable" => {
+                for id in value(arg)?.split(',').filter(|id| !id.is_empty()) {
+                    let rule = Rule::from_id(id)
+                        .ok_or_else(|| usage_error(&format!("unknown lint rule {}", id)))?;
+                    parsed.disabled.push(rule);
+                }
+            }
+            "--length" => {
+                let length = timestamp_to_duration(&value(arg)?)?;
+                parsed.length = Some(duration_to_frames(&length));
+            }
+            "--fps" => {
+                let fps = value(arg)?;
+                parsed.fps = Some(
+                    fps.parse()
+                        .map_err(|_| usage_error(&format!("bad frame rate {}", fps)))?,
+                );
+            }
+            "--indent" => {
+                let indent = value(arg)?;
+                parsed.format.indent = match indent.as_ref() {
+                    "tab" => Indent::Tab,
+                    n => Indent::Spaces(
+                        n.parse()
+                            .map_err(|_| usage_error(&format!("bad indentation {}", n)))?,
+                    ),
+                };
+            }
+            "--quote" => {
+                let quoting = value(arg)?;
+                parsed.format.quoting = match quoting.as_ref() {
+                    "standard" => Quoting::Standard,
+                    "always" => Quoting::Always,
+                    "needed" => Quoting::WhenNeeded,
+                    _ => return Err(usage_error(&format!("unknown quoting {}", quoting))),
+                };
+            }
+            "--normalize" => parsed.format.normalize_keywords = true,
+            "--sort-rem" => parsed.format.sort_rems = true,
+            "--crlf" => parsed.format.line_ending = LineEnding::CrLf,
+            "--bom" => parsed.format.bom = true,
+            "-" => parsed.input = Some(arg.clone()),
+            _ if arg.starts_with('-') => {
+                return Err(usage_error(&format!("unknown option {}", arg)))
+            }
+            _ if parsed.input.is_none() => parsed.input = Some(arg.clone()),
+            _ => return Err(usage_error(&format!("unexpected argument {}", arg))),
+        }
+    }
+
+    if parsed.in_place && parsed.input.as_ref().is_none_or(|input| input == "-") {
+        return Err(usage_error("--in-place needs an input file"));
+    }
+    Ok(parsed)
+}
+
+// Input and output of a command
+struct Io<'a> {
+    stdin: &'a mut dyn Read,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+}
+
+impl<'a> Io<'a> {
+    fn read_input(&mut self, args: &Args) -> Result<Vec<u8>, CueError> {
+        let mut bytes = Vec::new();
+        match args.input.as_deref() {
+            None | Some("-") => {
+                self.stdin.read_to_end(&mut bytes)?;
+            }
+            Some(path) => bytes = fs::read(path)?,
+        }
+        Ok(bytes)
+    }
+
+    fn write_output(&mut self, args: &Args, text: &str) -> Result<(), CueError> {
+        let path = if args.in_place {
+            args.input.as_ref()
+        } else {
+            args.output.as_ref()
+        };
+        match path {
+            Some(path) if path != "-" => fs::write(path, text)?,
+            _ => self.stdout.write_all(text.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    fn warn(&mut self, diagnostics: &[Diagnostic]) -> Result<(), CueError> {
+        for diagnostic in diagnostics {
+            writeln!(self.stderr, "warning: {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+// Input must be UTF-8: replacing other encodings' bytes would lose them for good when the
+// output overwrites the input.
+fn decode(bytes: &[u8]) -> Result<String, CueError> {
+    let text = str::from_utf8(bytes).map_err(|err| {
+        CueError::Parse(format!(
+            "input is not valid UTF-8 (at byte {}), convert it first",
+            err.valid_up_to()
+        ))
+    })?;
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+// Directory that FILE paths of the input sheet are relative to
+fn input_dir(args: &Args) -> PathBuf {
+    match args.input.as_ref() {
+        Some(path) if path != "-" => Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        _ => PathBuf::new(),
+    }
+}
+
+fn parse_sheet(text: &str, strict: bool) -> Result<Cue, CueError> {
+    parse(&mut io::Cursor::new(text), strict)
+}
+
+fn timestamp(duration: Option<Duration>) -> String {
+    duration.map_or("--:--:--".to_string(), |d| {
+        frames_to_timestamp(duration_to_frames(&d))
+    })
+}
+
+fn info(cue: &Cue) -> String {
+    let mut out = String::new();
+    let mut field = |name: &str, value: Option<&String>| {
+        if let Some(value) = value {
+            out.push_str(&format!("{:<12}{}\n", format!("{}:", name), value));
+        }
+    };
+    field("Title", cue.title.as_ref());
+    field("Performer", cue.performer.as_ref());
+    field("Songwriter", cue.songwriter.as_ref());
+    field("Catalog", cue.catalog.as_ref());
+    field("Media type", cue.original_media_type.as_ref());
+    for (key, value) in &cue.comments {
+        out.push_str(&format!("{:<12}{}\n", format!("{}:", key), value));
+    }
+    if !cue.sessions.is_empty() {
+        out.push_str(&format!("{:<12}{}\n", "Sessions:", cue.sessions.len()));
+    }
+
+    for file in &cue.files {
+        out.push_str(&format!("\n{} ({})\n", file.file, file.format));
+        for track in &file.tracks {
+            let mut line = format!(
+                "  {:>2}  {:<10}  {}",
+                track.no,
+                track.format,
+                timestamp(track.start())
+            );
+            match (&track.performer, &track.title) {
+                (Some(performer), Some(title)) => {
+                    line.push_str(&format!("  {} - {}", performer, title))
+                }
+                (None, Some(title)) => line.push_str(&format!("  {}", title)),
+                _ => {}
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn finding_json(finding: &LintFinding) -> String {
+    let fix = match finding.fix {
+        Some(ref fix) => format!(
+            "{{\"line\": {}, \"replacement\": {}}}",
+            fix.line,
+            fix.replacement
+                .as_ref()
+                .map_or("null".to_string(), |r| json::escape(r))
+        ),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"rule\": {}, \"line\": {}, \"track\": {}, \"message\": {}, \"fix\": {}}}",
+        json::escape(finding.rule.id()),
+        finding.line.map_or("null".to_string(), |l| l.to_string()),
+        finding.track.map_or("null".to_string(), |t| t.to_string()),
+        json::escape(&finding.message),
+        fix
+    )
+}
+
+fn run_lint(args: &Args, io: &mut Io, text: &str) -> Result<i32, CueError> {
+    let options = LintOptions {
+        disabled: args.disabled.clone(),
+    };
+    let cue = parse_sheet(text, args.strict)?;
+    let findings = lint_with(&cue, &Source::new(text), &options);
+
+    if args.fix {
+        let fixed = apply_fixes(text, &findings);
+        io.write_output(args, &fixed)?;
+        let remaining = lint_with(
+            &parse_sheet(&fixed, args.strict)?,
+            &Source::new(&fixed),
+            &options,
+        );
+        for finding in &remaining {
+            writeln!(io.stderr, "{}", finding)?;
+        }
+        return Ok(if remaining.is_empty() { 0 } else { 1 });
+    }
+
+    if args.json {
+        let items: Vec<String> = findings
+            .iter()
+            .map(|f| format!("  {}", finding_json(f)))
+            .collect();
+        let out = if items.is_empty() {
+            "[]\n".to_string()
+        } else {
+            format!("[\n{}\n]\n", items.join(",\n"))
+        };
+        io.write_output(args, &out)?;
+    } else {
+        let name = args.input.as_deref().unwrap_or("-");
+        let out: String = findings
+            .iter()
+            .map(|finding| match finding.line {
+                Some(line) => format!(
+                    "{}:{}: {} [{}]\n",
+                    name,
+                    line,
+                    finding.message,
+                    finding.rule.id()
+                ),
+                None => format!("{}: {}\n", name, finding),
+            })
+            .collect();
+        io.write_output(args, &out)?;
+    }
+    Ok(if findings.is_empty() { 0 } else { 1 })
+}
+
+fn length(args: &Args, format: &str) -> Result<u64, CueError> {
+    args.length.ok_or_else(|| {
+        usage_error(&format!(
+            "{} export needs --length, the length of the audio file",
+            format
+        ))
+    })
+}
+
+fn import(args: &Args, bytes: &[u8]) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let from = args.from.as_deref().unwrap_or("cue");
+    let file = args.file.as_deref().unwrap_or("audio.wav");
+    let text = decode(bytes)?;
+    let options = chapters::ImportOptions::default();
+    match from {
+        "cue" => Ok((parse_sheet(&text, args.strict)?, vec![])),
+        "toc" => toc::from_toc(&text),
+        "ccd" => image::from_ccd(&text, file),
+        "ffmetadata" => ffmetadata::from_ffmetadata(&text, file, &options),
+        "matroska" => matroska::from_matroska_xml(&text, file, None, &options),
+        "ogm" => ogm::from_ogm(&text, file, &options),
+        "edl" => edl::from_edl(&text, file, args.fps.unwrap_or(25), &options),
+        "audacity" => audacity::from_audacity_labels(&text, file, &options),
+        "reaper" => reaper::from_reaper_csv(&text, file, &options),
+        _ => Err(usage_error(&format!("unknown input format {}", from))),
+    }
+}
+
+fn export(args: &Args, cue: &Cue) -> Result<(String, Vec<Diagnostic>), CueError> {
+    let to = args
+        .to
+        .as_ref()
+        .ok_or_else(|| usage_error("convert needs --to"))?;
+    let options = chapters::ExportOptions::default();
+    let file_lengths: Vec<u64> = args.length.into_iter().collect();
+    let playlist_options = || PlaylistOptions {
+        cue_dir: input_dir(args),
+        playlist_dir: args
+            .output
+            .as_ref()
+            .and_then(|output| Path::new(output).parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+    let text = match to.as_ref() {
+        "cue" => writer::to_string(cue),
+        "toc" => return Ok(toc::to_toc(cue)),
+        "json" => json::to_json(cue),
+        "ffmetadata" => ffmetadata::to_ffmetadata(cue, length(args, to)?)?,
+        "matroska" => matroska::to_matroska_xml(cue, length(args, to)?, "eng")?,
+        "ogm" => ogm::to_ogm(cue)?,
+        "edl" => edl::to_edl(cue, length(args, to)?, args.fps.unwrap_or(25), &options)?,
+        "audacity" => audacity::to_audacity_labels(cue, length(args, to)?, &options)?,
+        "reaper" => reaper::to_reaper_csv(cue, length(args, to)?, &options)?,
+        "m3u8" => playlist::to_m3u8(cue, &file_lengths, &playlist_options()),
+        "xspf" => playlist::to_xspf(cue, &file_lengths, &playlist_options()),
+        "pls" => playlist::to_pls(cue, &file_lengths, &playlist_options()),
+        _ => return Err(usage_error(&format!("unknown output format {}", to))),
+    };
+    Ok((text, vec![]))
+}
+
+/// Runs the tool with `args` (without the program name), returning the exit status.
+fn run(args: &[String], io: &mut Io) -> Result<i32, CueError> {
+    let args = parse_args(args)?;
+    match args.command.as_ref() {
+        "fmt" => {
+            let out = writer::format(&decode(&io.read_input(&args)?)?, &args.format)?;
+            io.write_output(&args, &out)?;
+            Ok(0)
+        }
+        "info" | "json" => {
+            let cue = parse_sheet(&decode(&io.read_input(&args)?)?, args.strict)?;
+            let out = match args.command.as_ref() {
+                "info" if !args.json => info(&cue),
+                _ => json::to_json(&cue),
+            };
+            io.write_output(&args, &out)?;
+            Ok(0)
+        }
+        "lint" => {
+            let text = decode(&io.read_input(&args)?)?;
+            run_lint(&args, io, &text)
+        }
+        "convert" => {
+            let (cue, diagnostics) = import(&args, &io.read_input(&args)?)?;
+            io.warn(&diagnostics)?;
+            let (out, diagnostics) = export(&args, &cue)?;
+            io.warn(&diagnostics)?;
+            io.write_output(&args, &out)?;
+            Ok(0)
+        }
+        "help" | "-h" | "--help" => {
+            io.stdout.write_all(USAGE.as_bytes())?;
+            Ok(0)
+        }
+        command => Err(usage_error(&format!("unknown command {}", command))),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut io = Io {
+        stdin: &mut stdin.lock(),
+        stdout: &mut stdout.lock(),
+        stderr: &mut io::stderr(),
+    };
+    let status = match run(&args, &mut io) {
+        Ok(status) => status,
+        Err(err) => {
+            let _ = writeln!(io.stderr, "rcue: {}", err);
+            2
+        }
+    };
+    let _ = io.stdout.flush();
+    process::exit(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(args: &[&str], stdin: &str) -> (Result<i32, CueError>, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let status = run(
+            &args,
+            &mut Io {
+                stdin: &mut stdin.as_bytes(),
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+            },
+        );
+        (
+            status,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_info() {
+        let (status, out, _) = run_with(&["info", "test/fixtures/good.cue"], "");
+        assert_eq!(status.unwrap(), 0);
+        assert!(out.starts_with("Title:      Loveless\n"));
+        assert!(out.contains("  02  AUDIO       04:17:52  My Bloody Valentine - Loomer\n"));
+
+        let (_, out, _) = run_with(&["info", "--json"], "TITLE \"A\"\n");
+        assert!(out.contains("\"title\": \"A\""));
+    }
+
+    #[test]
+    fn test_lint() {
+        let (status, out, _) = run_with(&["lint", "test/fixtures/duplicate_track.cue"], "");
+        assert_eq!(status.unwrap(), 1);
+        assert_eq!(
+            out,
+            "test/fixtures/duplicate_track.cue:13: TRACK 01 already given [duplicate-track]\n"
+        );
+
+        let (status, out, _) = run_with(
+            &[
+                "lint",
+                "--disable",
+                "duplicate-track",
+                "test/fixtures/duplicate_track.cue",
+            ],
+            "",
+        );
+        assert_eq!((status.unwrap(), out.as_ref()), (0, ""));
+
+        let (_, out, _) = run_with(&["lint", "--json", "-"], "TITLE \"\"\n");
+        assert_eq!(
+            out,
+            "[\n  {\"rule\": \"empty-string\", \"line\": 1, \"track\": null, \
+             \"message\": \"empty TITLE\", \"fix\": {\"line\": 1, \"replacement\": null}}\n]\n"
+        );
+
+        let (status, out, _) = run_with(&["lint", "--fix"], "TITLE \"\"\nTITLE \"A\"\n");
+        assert_eq!((status.unwrap(), out.as_ref()), (0, "TITLE \"A\"\n"));
+
+        assert!(run_with(&["lint", "--disable", "nope"], "").0.is_err());
+    }
+
+    #[test]
+    fn test_fmt_and_json() {
+        let sheet = "title \"A\"\nfile \"a.wav\" WAVE\ntrack 1 audio\nindex 1 00:00:00\n";
+        let (_, out, _) = run_with(&["fmt"], sheet);
+        assert_eq!(
+            out,
+            "TITLE \"A\"\nFILE \"a.wav\" WAVE\n  TRACK 1 audio\n    INDEX 1 00:00:00\n"
+        );
+        let (_, out, _) = run_with(&["fmt", "--indent", "tab", "--normalize", "--crlf"], sheet);
+        assert_eq!(
+            out,
+            "TITLE \"A\"\r\nFILE \"a.wav\" WAVE\r\n\tTRACK 01 AUDIO\r\n\t\tINDEX 01 00:00:00\r\n"
+        );
+        assert!(run_with(&["fmt", "--quote", "never"], sheet).0.is_err());
+        assert!(run_with(&["fmt"], "TRACK 01 AUDIO\n").0.is_err());
+
+        let (_, out, _) = run_with(&["json", "test/fixtures/good.cue"], "");
+        assert!(out.contains("\"file\": \"My Bloody Valentine - Loveless.wav\""));
+    }
+
+    #[test]
+    fn test_convert() {
+        let (status, out, _) = run_with(
+            &[
+                "convert",
+                "--to",
+                "ffmetadata",
+                "--length",
+                "10:00:00",
+                "test/fixtures/good.cue",
+            ],
+            "",
+        );
+        assert_eq!(status.unwrap(), 0);
+        assert!(out.starts_with(";FFMETADATA1\n"));
+
+        let (_, out, _) = run_with(
+            &[
+                "convert", "--from", "ogm", "--to", "cue", "--file", "b.flac",
+            ],
+            "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Intro\n",
+        );
+        assert!(out.contains("FILE \"b.flac\" WAVE\n"));
+        assert!(out.contains("TITLE \"Intro\"\n"));
+
+        let missing = run_with(&["convert", "--to", "reaper", "test/fixtures/good.cue"], "");
+        assert!(missing.0.unwrap_err().to_string().contains("--length"));
+        assert!(run_with(&["convert", "--to", "mp3"], "").0.is_err());
+    }
+
+    #[test]
+    fn test_non_utf8_input() {
+        let path = std::env::temp_dir().join(format!("rcue_cli_latin1_{}.cue", process::id()));
+        let sheet = b"TITLE \"Caf\xe9\"\nFILE \"a.wav\" WAVE\n";
+        fs::write(&path, &sheet[..]).unwrap();
+        let path_arg = path.to_string_lossy().into_owned();
+
+        let commands: [&[&str]; 3] = [&["fmt", "-i"], &["lint", "--fix", "-i"], &["info"]];
+        for command in &commands {
+            let mut args = command.to_vec();
+            args.push(&path_arg);
+            let (status, _, _) = run_with(&args, "");
+            assert!(status.unwrap_err().to_string().contains("not valid UTF-8"));
+        }
+        assert_eq!(fs::read(&path).unwrap(), &sheet[..]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_args() {
+        assert!(run_with(&[], "").0.is_err());
+        assert!(run_with(&["frobnicate"], "").0.is_err());
+        assert!(run_with(&["fmt", "-i"], "").0.is_err());
+        assert!(run_with(&["info", "a.cue", "b.cue"], "").0.is_err());
+    }
+}
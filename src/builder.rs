@@ -0,0 +1,488 @@
+use std::time::Duration;
+
+use cue::{Cue, CueFile, Track};
+use errors::{CueError, Diagnostic};
+use util::{duration_to_frames, frames_to_duration};
+
+// Parses a strict `mm:ss:ff` timestamp: seconds below 60 and frames below 75.
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let parts: Vec<&str> = timestamp.split(':').collect();
+    if parts.len() != 3
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    let minutes = parts[0].parse::<u64>().ok()?;
+    let seconds = parts[1].parse::<u64>().ok()?;
+    let frames = parts[2].parse::<u64>().ok()?;
+    if seconds >= 60 || frames >= 75 {
+        return None;
+    }
+    Some(frames_to_duration((minutes * 60 + seconds) * 75 + frames))
+}
+
+/// Builds a [`Cue`](../cue/struct.Cue.html) programmatically, checking at
+/// [`build`](#method.build) that the result is a valid sheet.
+///
+/// # Example
+///
+/// ```
+/// use rcue::builder::CueBuilder;
+///
+/// let cue = CueBuilder::new()
+///     .title("Loveless")
+///     .performer("My Bloody Valentine")
+///     .file("a.wav", "WAVE", |f| {
+///         f.track(1, "AUDIO", |t| t.title("Only Shallow").index(1, "00:00:00"))
+///             .track(2, "AUDIO", |t| t.index(0, "04:15:00").index(1, "04:17:52"))
+///     })
+///     .build()
+///     .unwrap();
+/// assert_eq!(cue.files[0].tracks[1].no, "02");
+/// assert_eq!(cue.files[0].tracks[0].title, Some("Only Shallow".to_string()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CueBuilder {
+    cue: Cue,
+    errors: Vec<Diagnostic>,
+}
+
+impl Default for CueBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CueBuilder {
+    /// Starts an empty sheet.
+    pub fn new() -> Self {
+        Self {
+            cue: Cue::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Sets the TITLE of the disc.
+    pub fn title(mut self, title: &str) -> Self {
+        self.cue.title = Some(title.to_string());
+        self
+    }
+
+    /// Sets the PERFORMER of the disc.
+    pub fn performer(mut self, performer: &str) -> Self {
+        self.cue.performer = Some(performer.to_string());
+        self
+    }
+
+    /// Sets the SONGWRITER of the disc.
+    pub fn songwriter(mut self, songwriter: &str) -> Self {
+        self.cue.songwriter = Some(songwriter.to_string());
+        self
+    }
+
+    /// Sets the CATALOG (UPC/EAN) of the disc.
+    pub fn catalog(mut self, catalog: &str) -> Self {
+        self.cue.catalog = Some(catalog.to_string());
+        self
+    }
+
+    /// Sets the CDTEXTFILE of the disc.
+    pub fn cd_text_file(mut self, cd_text_file: &str) -> Self {
+        self.cue.cd_text_file = Some(cd_text_file.to_string());
+        self
+    }
+
+    /// Adds a disc-level `REM key value`.
+    pub fn rem(mut self, key: &str, value: &str) -> Self {
+        self.cue.comments.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds a FILE, whose tracks are added by `build_file`.
+    pub fn file<F>(mut self, file: &str, format: &str, build_file: F) -> Self
+    where
+        F: FnOnce(FileBuilder) -> FileBuilder,
+    {
+        let builder = build_file(FileBuilder {
+            file: CueFile::new(file, format),
+            errors: Vec::new(),
+        });
+        self.cue.files.push(builder.file);
+        self.errors.extend(builder.errors);
+        self
+    }
+
+    /// Checks the sheet and returns it.
+    ///
+    /// # Failures
+    ///
+    /// Fails, listing every problem found, if a timestamp is not a valid `mm:ss:ff`, if the sheet
+    /// or one of its FILEs has no tracks, if track numbers are not between 1 and 99 or not
+    /// consecutive, if a track has no INDEX 01 or its index numbers are not increasing, or if the
+    /// indices of a FILE are not in increasing time order. As the CUE format allows, the first
+    /// track may be numbered above 1.
+    pub fn build(self) -> Result<Cue, CueError> {
+        let mut errors = self.errors;
+        check(&self.cue, &mut errors);
+        if errors.is_empty() {
+            return Ok(self.cue);
+        }
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        Err(CueError::Parse(messages.join("; ")))
+    }
+}
+
+fn check(cue: &Cue, errors: &mut Vec<Diagnostic>) {
+    if cue.files.iter().all(|file| file.tracks.is_empty()) {
+        errors.push(Diagnostic::new(None, "sheet has no tracks"));
+    }
+
+    let mut previous: Option<u32> = None;
+    for file in &cue.files {
+        if file.tracks.is_empty() {
+            errors.push(Diagnostic::new(
+                None,
+                &format!("FILE \"{}\" has no tracks", file.file),
+            ));
+        }
+
+        // Position and track of the last index in this FILE
+        let mut last: Option<(u64, u32)> = None;
+        for track in &file.tracks {
+            let no = track.no.parse::<u32>().unwrap_or(0);
+            if !(1..=99).contains(&no) {
+                errors.push(Diagnostic::new(
+                    Some(no),
+                    "track numbers must be between 1 and 99",
+                ));
+            } else if let Some(previous) = previous {
+                if no != previous + 1 {
+                    errors.push(Diagnostic::new(
+                        Some(no),
+                        &format!("follows track {:02}", previous),
+                    ));
+                }
+            }
+            previous = Some(no);
+
+            if track.index(1).is_none() {
+                errors.push(Diagnostic::new(Some(no), "no INDEX 01"));
+            }
+            let mut previous_index: Option<u32> = None;
+            for (index, time) in &track.indices {
+                let index = index.parse::<u32>().unwrap_or(0);
+                if index > 99 {
+                    errors.push(Diagnostic::new(
+                        Some(no),
+                        &format!("INDEX {} is above 99", index),
+                    ));
+                }
+                if let Some(previous_index) = previous_index {
+                    if index <= previous_index {
+                        errors.push(Diagnostic::new(
+                            Some(no),
+                            &format!("INDEX {:02} after INDEX {:02}", index, previous_index),
+                        ));
+                    }
+                }
+                previous_index = Some(index);
+
+                let frames = duration_to_frames(time);
+                if let Some((last_frames, last_track)) = last {
+                    if frames <= last_frames {
+                        let message = if last_track == no {
+                            format!("INDEX {:02} is not after the previous index", index)
+                        } else {
+                            format!(
+                                "INDEX {:02} is not after the indices of track {:02}",
+                                index, last_track
+                            )
+                        };
+                        errors.push(Diagnostic::new(Some(no), &message));
+                    }
+                }
+                last = Some((frames, no));
+            }
+        }
+    }
+}
+
+/// Adds tracks to a FILE for [`CueBuilder::file`](struct.CueBuilder.html#method.file).
+#[derive(Clone, Debug)]
+pub struct FileBuilder {
+    file: CueFile,
+    errors: Vec<Diagnostic>,
+}
+
+impl FileBuilder {
+    /// Adds a `REM key value` to the FILE.
+    pub fn rem(mut self, key: &str, value: &str) -> Self {
+        self.file
+            .comments
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds TRACK `no` of type `format` (eg. AUDIO), whose fields are set by `build_track`.
+    pub fn track<F>(mut self, no: u32, format: &str, build_track: F) -> Self
+    where
+        F: FnOnce(TrackBuilder) -> TrackBuilder,
+    {
+        let builder = build_track(TrackBuilder {
+            no,
+            track: Track::new(&format!("{:02}", no), format),
+            errors: Vec::new(),
+        });
+        self.file.tracks.push(builder.track);
+        self.errors.extend(builder.errors);
+        self
+    }
+}
+
+/// Sets the fields of a TRACK for [`FileBuilder::track`](struct.FileBuilder.html#method.track).
+///
+/// Timestamps are `mm:ss:ff` strings; invalid ones are reported by
+/// [`CueBuilder::build`](struct.CueBuilder.html#method.build).
+#[derive(Clone, Debug)]
+pub struct TrackBuilder {
+    no: u32,
+    track: Track,
+    errors: Vec<Diagnostic>,
+}
+
+impl TrackBuilder {
+    fn timestamp(&mut self, field: &str, timestamp: &str) -> Option<Duration> {
+        let time = parse_timestamp(timestamp);
+        if time.is_none() {
+            self.errors.push(Diagnostic::new(
+                Some(self.no),
+                &format!("bad {} timestamp \"{}\"", field, timestamp),
+            ));
+        }
+        time
+    }
+
+    /// Sets the TITLE of the track.
+    pub fn title(mut self, title: &str) -> Self {
+        self.track.title = Some(title.to_string());
+        self
+    }
+
+    /// Sets the PERFORMER of the track.
+    pub fn performer(mut self, performer: &str) -> Self {
+        self.track.performer = Some(performer.to_string());
+        self
+    }
+
+    /// Sets the SONGWRITER of the track.
+    pub fn songwriter(mut self, songwriter: &str) -> Self {
+        self.track.songwriter = Some(songwriter.to_string());
+        self
+    }
+
+    /// Sets the ISRC of the track.
+    pub fn isrc(mut self, isrc: &str) -> Self {
+        self.track.isrc = Some(isrc.to_string());
+        self
+    }
+
+    /// Adds a sub-code flag (DCP, 4CH, PRE, SCMS) to the track.
+    pub fn flag(mut self, flag: &str) -> Self {
+        self.track.flags.push(flag.to_string());
+        self
+    }
+
+    /// Adds a `REM key value` to the track.
+    pub fn rem(mut self, key: &str, value: &str) -> Self {
+        self.track
+            .comments
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the PREGAP of the track, as an `mm:ss:ff` timestamp.
+    pub fn pregap(mut self, pregap: &str) -> Self {
+        self.track.pregap = self.timestamp("PREGAP", pregap);
+        self
+    }
+
+    /// Sets the POSTGAP of the track, as an `mm:ss:ff` timestamp.
+    pub fn postgap(mut self, postgap: &str) -> Self {
+        self.track.postgap = self.timestamp("POSTGAP", postgap);
+        self
+    }
+
+    /// Adds INDEX `no` at `timestamp`, an `mm:ss:ff` position in the FILE.
+    pub fn index(mut self, no: u32, timestamp: &str) -> Self {
+        if let Some(time) = self.timestamp(&format!("INDEX {:02}", no), timestamp) {
+            self.track.indices.push((format!("{:02}", no), time));
+        }
+        self
+    }
+
+    /// Adds INDEX `no` at `frames` CD frames into the FILE, as read from a drive's TOC.
+    pub fn index_frames(mut self, no: u32, frames: u64) -> Self {
+        self.track
+            .indices
+            .push((format!("{:02}", no), frames_to_duration(frames)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+    use writer::to_string;
+
+    #[test]
+    fn test_build_matches_parsed() {
+        let parsed = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let mut builder = CueBuilder::new();
+        if let Some(ref title) = parsed.title {
+            builder = builder.title(title);
+        }
+        if let Some(ref performer) = parsed.performer {
+            builder = builder.performer(performer);
+        }
+        if let Some(ref songwriter) = parsed.songwriter {
+            builder = builder.songwriter(songwriter);
+        }
+        if let Some(ref cd_text_file) = parsed.cd_text_file {
+            builder = builder.cd_text_file(cd_text_file);
+        }
+        for (key, value) in &parsed.comments {
+            builder = builder.rem(key, value);
+        }
+        for file in &parsed.files {
+            builder = builder.file(&file.file, &file.format, |mut f| {
+                for track in &file.tracks {
+                    let no = track.no.parse().unwrap();
+                    f = f.track(no, &track.format, |mut t| {
+                        if let Some(ref title) = track.title {
+                            t = t.title(title);
+                        }
+                        if let Some(ref performer) = track.performer {
+                            t = t.performer(performer);
+                        }
+                        if let Some(ref songwriter) = track.songwriter {
+                            t = t.songwriter(songwriter);
+                        }
+                        if let Some(ref isrc) = track.isrc {
+                            t = t.isrc(isrc);
+                        }
+                        for flag in &track.flags {
+                            t = t.flag(flag);
+                        }
+                        for (key, value) in &track.comments {
+                            t = t.rem(key, value);
+                        }
+                        for (index, time) in &track.indices {
+                            t = t.index_frames(index.parse().unwrap(), duration_to_frames(time));
+                        }
+                        t
+                    });
+                }
+                f
+            });
+        }
+        let built = builder.build().unwrap();
+        assert_eq!(to_string(&built), to_string(&parsed));
+    }
+
+    #[test]
+    fn test_build_fields() {
+        let cue = CueBuilder::new()
+            .catalog("0724384260624")
+            .cd_text_file("disc.cdt")
+            .file("a.bin", "BINARY", |f| {
+                f.rem("CRC", "1234").track(1, "MODE1/2352", |t| {
+                    t.index(1, "00:00:00")
+                        .flag("DCP")
+                        .isrc("USRC17607839")
+                        .pregap("00:02:00")
+                        .postgap("00:01:74")
+                })
+            })
+            .build()
+            .unwrap();
+        let track = &cue.files[0].tracks[0];
+        assert_eq!(cue.catalog, Some("0724384260624".to_string()));
+        assert_eq!(cue.files[0].comments[0].0, "CRC");
+        assert_eq!(track.flags, vec!["DCP"]);
+        assert_eq!(track.pregap, Some(Duration::new(2, 0)));
+        assert_eq!(duration_to_frames(&track.postgap.unwrap()), 149);
+    }
+
+    fn message(builder: CueBuilder) -> String {
+        match builder.build() {
+            Err(CueError::Parse(message)) => message,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert_eq!(message(CueBuilder::new()), "sheet has no tracks");
+
+        let err = message(
+            CueBuilder::new()
+                .file("a.wav", "WAVE", |f| {
+                    f.track(1, "AUDIO", |t| t.index(1, "00:00:00"))
+                        .track(3, "AUDIO", |t| t.index(0, "01:00:00"))
+                })
+                .file("b.wav", "WAVE", |f| f),
+        );
+        assert_eq!(
+            err,
+            "track 03: follows track 01; track 03: no INDEX 01; FILE \"b.wav\" has no tracks"
+        );
+
+        let err = message(CueBuilder::new().file("a.wav", "WAVE", |f| {
+            f.track(1, "AUDIO", |t| t.index(1, "00:10:00"))
+                .track(2, "AUDIO", |t| {
+                    t.index(1, "00:05:00")
+                        .index(0, "00:20:00")
+                        .index(2, "00:60:00")
+                })
+        }));
+        assert_eq!(
+            err,
+            "track 02: bad INDEX 02 timestamp \"00:60:00\"; \
+             track 02: INDEX 01 is not after the indices of track 01; \
+             track 02: INDEX 00 after INDEX 01"
+        );
+
+        let err = message(CueBuilder::new().file("a.wav", "WAVE", |f| {
+            f.track(0, "AUDIO", |t| t.index(1, "00:00:00").index(1, "00:00:00"))
+        }));
+        assert_eq!(
+            err,
+            "track 00: track numbers must be between 1 and 99; \
+             track 00: INDEX 01 after INDEX 01; \
+             track 00: INDEX 01 is not after the previous index"
+        );
+
+        let cue = CueBuilder::new()
+            .file("a.wav", "WAVE", |f| {
+                f.track(5, "AUDIO", |t| t.index(1, "00:00:00"))
+                    .track(6, "AUDIO", |t| t.index(1, "01:00:00"))
+            })
+            .build();
+        assert!(cue.is_ok());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("01:02:03"), Some(frames_to_duration(4653)));
+        assert_eq!(
+            parse_timestamp("99:59:74").map(|d| duration_to_frames(&d)),
+            Some(449999)
+        );
+        assert_eq!(parse_timestamp("1:2"), None);
+        assert_eq!(parse_timestamp("00:00:75"), None);
+        assert_eq!(parse_timestamp("00:-1:00"), None);
+    }
+}
@@ -0,0 +1,546 @@
+use std::time::Duration;
+
+use cue::{Cue, CueFile, PregapMode, Track};
+use errors::{CueError, Diagnostic};
+use util::{frames_to_duration, frames_to_nanos, FRAMES_PER_SECOND};
+
+/// A track of a single-file sheet as a chapter of its audio file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    /// First frame of the chapter (75 frames = 1s)
+    pub start: u64,
+    /// Frame after the last frame of the chapter
+    pub end: u64,
+    /// Track title
+    pub title: Option<String>,
+    /// Track performer
+    pub performer: Option<String>,
+}
+
+/// Returns the tracks of a single-file `cue` as chapters of a file `length` CD frames long.
+///
+/// Each chapter runs from a track's INDEX 01 to the next track's INDEX 01, so pregaps belong to
+/// the previous chapter. The last chapter ends at `length`. Tracks without an INDEX are skipped.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::chapters;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let chapters = chapters(&cue, 30000).unwrap();
+/// assert_eq!((chapters[1].start, chapters[1].end), (19327, 30000));
+/// assert_eq!(chapters[1].title, Some("Loomer".to_string()));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE; multi-file sheets can be converted with
+/// [`layout::convert`](../layout/fn.convert.html) first.
+pub fn chapters(cue: &Cue, length: u64) -> Result<Vec<Chapter>, CueError> {
+    if cue.files.len() != 1 {
+        return Err(CueError::Parse(format!(
+            "chapters need a single-file sheet, got {} FILEs",
+            cue.files.len()
+        )));
+    }
+
+    let file = &cue.files[0];
+    Ok(file
+        .tracks
+        .iter()
+        .zip(file.track_spans(PregapMode::Append))
+        .filter_map(|(track, span)| {
+            span.map(|span| Chapter {
+                start: span.start.min(length),
+                end: span.end.unwrap_or(length).min(length),
+                title: track.title.clone(),
+                performer: track.performer.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Returns the value of the first disc-level `REM` comment named `key`, ignoring case.
+pub fn comment<'a>(cue: &'a Cue, key: &str) -> Option<&'a str> {
+    cue.comments
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_str())
+}
+
+/// A chapter read from a chaptered media file, before it is placed on CD frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChapterMark {
+    /// Start of the chapter in nanoseconds
+    pub start: u64,
+    /// Chapter name
+    pub title: Option<String>,
+    /// Chapter artist
+    pub performer: Option<String>,
+}
+
+/// How a time is rounded to a coarser unit, such as CD frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to the nearest unit, halves up
+    Nearest,
+    /// Round towards zero
+    Down,
+    /// Round away from zero
+    Up,
+}
+
+impl Rounding {
+    /// Divides `n` by `d`, rounding the quotient.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rcue::chapters::Rounding;
+    ///
+    /// assert_eq!(Rounding::Nearest.div(7, 2), 4);
+    /// assert_eq!(Rounding::Down.div(7, 2), 3);
+    /// assert_eq!(Rounding::Up.div(6, 2), 3);
+    /// ```
+    pub fn div(self, n: u128, d: u128) -> u128 {
+        match self {
+            Rounding::Nearest => (n * 2 + d) / (d * 2),
+            Rounding::Down => n / d,
+            Rounding::Up => n.div_ceil(d),
+        }
+    }
+}
+
+/// How a marker label or chapter name maps to a track's TITLE and PERFORMER.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelMapping {
+    /// The whole label is the TITLE
+    Title,
+    /// `Performer - Title` labels are split on the first ` - `; other labels are the TITLE
+    PerformerTitle,
+    /// Labels are not used
+    Ignore,
+}
+
+impl LabelMapping {
+    /// Splits a label into a title and a performer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rcue::chapters::LabelMapping;
+    ///
+    /// let (title, performer) = LabelMapping::PerformerTitle.split("My Bloody Valentine - Loomer");
+    /// assert_eq!(title.as_deref(), Some("Loomer"));
+    /// assert_eq!(performer.as_deref(), Some("My Bloody Valentine"));
+    /// ```
+    pub fn split(self, label: &str) -> (Option<String>, Option<String>) {
+        match self {
+            LabelMapping::Title => (Some(label.to_string()), None),
+            LabelMapping::PerformerTitle => match label.find(" - ") {
+                Some(i) => (
+                    Some(label[i + 3..].to_string()),
+                    Some(label[..i].to_string()),
+                ),
+                None => (Some(label.to_string()), None),
+            },
+            LabelMapping::Ignore => (None, None),
+        }
+    }
+
+    /// Builds a label from a title and a performer. Returns `None` if there is no title, or for
+    /// [`Ignore`](#variant.Ignore).
+    pub fn join(self, title: Option<&str>, performer: Option<&str>) -> Option<String> {
+        match (self, title, performer) {
+            (LabelMapping::Ignore, _, _) | (_, None, _) => None,
+            (LabelMapping::PerformerTitle, Some(title), Some(performer)) => {
+                Some(format!("{} - {}", performer, title))
+            }
+            (_, Some(title), _) => Some(title.to_string()),
+        }
+    }
+}
+
+/// Options for importing chapters and markers into a [`Cue`](../cue/struct.Cue.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImportOptions {
+    /// Largest rounding of a chapter start to a CD frame that is not reported (default 1ms)
+    pub tolerance: Duration,
+    /// How chapter starts are rounded to CD frames (default `Nearest`)
+    pub rounding: Rounding,
+    /// How chapter names without an artist map to TITLE and PERFORMER (default `Title`)
+    pub labels: LabelMapping,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            tolerance: Duration::from_millis(1),
+            rounding: Rounding::Nearest,
+            labels: LabelMapping::Title,
+        }
+    }
+}
+
+/// Options for exporting a [`Cue`](../cue/struct.Cue.html) as markers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExportOptions {
+    /// How CD frames are rounded to the target format's time unit (default `Nearest`)
+    pub rounding: Rounding,
+    /// How TITLE and PERFORMER map to labels (default `Title`)
+    pub labels: LabelMapping,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            rounding: Rounding::Nearest,
+            labels: LabelMapping::Title,
+        }
+    }
+}
+
+/// Parses decimal seconds, such as `257.693333`, into nanoseconds. Digits after the ninth
+/// decimal are ignored.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::seconds_to_nanos;
+///
+/// assert_eq!(seconds_to_nanos("257.693333"), Some(257_693_333_000));
+/// assert_eq!(seconds_to_nanos("12"), Some(12_000_000_000));
+/// assert_eq!(seconds_to_nanos("-1"), None);
+/// ```
+pub fn seconds_to_nanos(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (seconds, fraction) = match s.find('.') {
+        Some(dot) => (&s[..dot], &s[dot + 1..]),
+        None => (s, ""),
+    };
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if (seconds.is_empty() && fraction.is_empty()) || !all_digits(seconds) || !all_digits(fraction)
+    {
+        return None;
+    }
+
+    let seconds: u64 = if seconds.is_empty() {
+        0
+    } else {
+        seconds.parse().ok()?
+    };
+    let fraction = &fraction[..fraction.len().min(9)];
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u64>().ok()? * 10u64.pow(9 - fraction.len() as u32)
+    };
+    seconds.checked_mul(1_000_000_000)?.checked_add(nanos)
+}
+
+/// Formats nanoseconds as decimal seconds with `decimals` digits after the point.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::{nanos_to_seconds, Rounding};
+///
+/// assert_eq!(nanos_to_seconds(257_693_333_333, 6, Rounding::Nearest), "257.693333");
+/// assert_eq!(nanos_to_seconds(257_693_333_333, 3, Rounding::Up), "257.694");
+/// assert_eq!(nanos_to_seconds(2_000_000_000, 0, Rounding::Nearest), "2");
+/// ```
+pub fn nanos_to_seconds(nanos: u64, decimals: u32, rounding: Rounding) -> String {
+    let decimals = decimals.min(9);
+    let unit = 10u128.pow(decimals);
+    let units = rounding.div(u128::from(nanos) * unit, 1_000_000_000);
+    if decimals == 0 {
+        format!("{}", units)
+    } else {
+        format!(
+            "{}.{:0width$}",
+            units / unit,
+            units % unit,
+            width = decimals as usize
+        )
+    }
+}
+
+/// Parses a chapter clock time, `HH:MM:SS` with an optional fraction of up to nine digits, into
+/// nanoseconds. Hours may have any number of digits; times too long to count in nanoseconds are
+/// rejected.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::clock_to_nanos;
+///
+/// assert_eq!(clock_to_nanos("00:04:17.693333333"), Some(257_693_333_333));
+/// assert_eq!(clock_to_nanos("01:00:00.5"), Some(3_600_500_000_000));
+/// assert_eq!(clock_to_nanos("4:17"), None);
+/// assert_eq!(clock_to_nanos("99999999999:00:00"), None);
+/// ```
+pub fn clock_to_nanos(s: &str) -> Option<u64> {
+    fn digits(s: &str) -> Option<u64> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    let mut parts = s.trim().split(':');
+    let hours = digits(parts.next()?)?;
+    let minutes = digits(parts.next()?)?;
+    let seconds = parts.next()?;
+    if parts.next().is_some() || minutes >= 60 {
+        return None;
+    }
+
+    let (seconds, fraction) = match seconds.find('.') {
+        Some(dot) => (&seconds[..dot], &seconds[dot + 1..]),
+        None => (seconds, "0"),
+    };
+    let seconds = digits(seconds)?;
+    if seconds >= 60 || fraction.len() > 9 {
+        return None;
+    }
+    let nanos = digits(fraction)? * 10u64.pow(9 - fraction.len() as u32);
+
+    hours
+        .checked_mul(60)?
+        .checked_add(minutes)?
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1_000_000_000)?
+        .checked_add(nanos)
+}
+
+fn file_format(file: &str) -> &'static str {
+    let extension = file.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "mp3" => "MP3",
+        "aif" | "aiff" => "AIFF",
+        _ => "WAVE",
+    }
+}
+
+/// Builds a single-file [`Cue`](../cue/struct.Cue.html) for the media file `file` with one AUDIO
+/// track per chapter, in order of start time.
+///
+/// Chapter starts are rounded to CD frames and become each track's INDEX 01. Artists become
+/// PERFORMER, and names become TITLE, or TITLE and PERFORMER for chapters without an artist,
+/// according to the [`labels`](struct.ImportOptions.html#structfield.labels) option. A
+/// diagnostic is returned for every start rounded by more than the tolerance, and for chapters
+/// that would start on or before the previous one, which are moved one frame after it. The FILE
+/// type is MP3 or AIFF for those extensions and WAVE otherwise.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::{to_cue, ChapterMark, ImportOptions};
+///
+/// let marks = vec![
+///     ChapterMark { start: 0, title: Some("Intro".to_string()), performer: None },
+///     ChapterMark { start: 90_005_000_000, title: None, performer: None },
+/// ];
+/// let (cue, diagnostics) = to_cue(&marks, "book.m4b", &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.files[0].file, "book.m4b");
+/// assert_eq!(cue.files[0].tracks[1].no, "02");
+/// assert_eq!(diagnostics.len(), 1);
+/// ```
+///
+/// # Failures
+///
+/// Fails if there are more than 99 chapters.
+pub fn to_cue(
+    marks: &[ChapterMark],
+    file: &str,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    if marks.len() > 99 {
+        return Err(CueError::Parse(format!(
+            "a CUE sheet holds at most 99 tracks, got {} chapters",
+            marks.len()
+        )));
+    }
+
+    let mut marks = marks.to_vec();
+    marks.sort_by_key(|mark| mark.start);
+
+    let tolerance = options.tolerance.as_nanos();
+    let mut diagnostics = Vec::new();
+    let mut cue_file = CueFile::new(file, file_format(file));
+    let mut previous: Option<u64> = None;
+
+    for (i, mark) in marks.into_iter().enumerate() {
+        let no = i as u32 + 1;
+        let nanos = u128::from(mark.start);
+        let mut frame = options
+            .rounding
+            .div(nanos * u128::from(FRAMES_PER_SECOND), 1_000_000_000)
+            as u64;
+
+        let error = u128::from(frames_to_nanos(frame)).abs_diff(nanos);
+        if error > tolerance {
+            diagnostics.push(Diagnostic::new(
+                Some(no),
+                &format!("start rounded by {}ns to frame {}", error, frame),
+            ));
+        }
+        if let Some(previous) = previous {
+            if frame <= previous {
+                frame = previous + 1;
+                diagnostics.push(Diagnostic::new(
+                    Some(no),
+                    &format!(
+                        "start is not after the previous chapter, moved to frame {}",
+                        frame
+                    ),
+                ));
+            }
+        }
+        previous = Some(frame);
+
+        let mut track = Track::new(&format!("{:02}", no), "AUDIO");
+        let (title, performer) = match (mark.title, mark.performer) {
+            (Some(title), None) => options.labels.split(&title),
+            (title, performer) => match options.labels {
+                LabelMapping::Ignore => (None, performer),
+                _ => (title, performer),
+            },
+        };
+        track.title = title;
+        track.performer = performer;
+        track
+            .indices
+            .push(("01".to_string(), frames_to_duration(frame)));
+        cue_file.tracks.push(track);
+    }
+
+    let mut cue = Cue::new();
+    cue.files.push(cue_file);
+    Ok((cue, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_chapters_clamped_to_length() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let chapters = chapters(&cue, 100).unwrap();
+        assert_eq!(chapters[0].start, 0);
+        assert_eq!(chapters[0].end, 100);
+        assert_eq!(chapters[1].start, 100);
+    }
+
+    #[test]
+    fn test_chapters_multi_file() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert!(chapters(&cue, 100).is_err());
+    }
+
+    fn mark(start: u64) -> ChapterMark {
+        ChapterMark {
+            start,
+            title: None,
+            performer: None,
+        }
+    }
+
+    #[test]
+    fn test_to_cue_round_trips_chapters() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let marks: Vec<ChapterMark> = chapters(&cue, 30000)
+            .unwrap()
+            .into_iter()
+            .map(|chapter| ChapterMark {
+                start: frames_to_nanos(chapter.start),
+                title: chapter.title,
+                performer: chapter.performer,
+            })
+            .collect();
+
+        let (imported, diagnostics) =
+            to_cue(&marks, "Loveless.mka", &ImportOptions::default()).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(imported.files[0].format, "WAVE");
+        assert_eq!(
+            chapters(&imported, 30000).unwrap(),
+            chapters(&cue, 30000).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_cue_sorts_and_separates_starts() {
+        let marks = vec![mark(2_000_000_000), mark(0), mark(2_004_000_000)];
+        let (cue, diagnostics) = to_cue(&marks, "a.mp3", &ImportOptions::default()).unwrap();
+        let starts: Vec<Option<u64>> = cue.files[0]
+            .track_spans(PregapMode::Append)
+            .into_iter()
+            .map(|span| span.map(|span| span.start))
+            .collect();
+        assert_eq!(starts, vec![Some(0), Some(150), Some(151)]);
+        assert_eq!(cue.files[0].format, "MP3");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].track, Some(3));
+    }
+
+    #[test]
+    fn test_to_cue_rounding_and_labels() {
+        let marks = vec![
+            ChapterMark {
+                start: 0,
+                title: Some("My Bloody Valentine - Only Shallow".to_string()),
+                performer: None,
+            },
+            ChapterMark {
+                start: 2_004_000_000,
+                title: Some("A - B".to_string()),
+                performer: Some("C".to_string()),
+            },
+        ];
+        let options = ImportOptions {
+            rounding: Rounding::Up,
+            labels: LabelMapping::PerformerTitle,
+            ..ImportOptions::default()
+        };
+        let (cue, _) = to_cue(&marks, "a.wav", &options).unwrap();
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].title.as_deref(), Some("Only Shallow"));
+        assert_eq!(tracks[0].performer.as_deref(), Some("My Bloody Valentine"));
+        assert_eq!(tracks[1].title.as_deref(), Some("A - B"));
+        assert_eq!(tracks[1].performer.as_deref(), Some("C"));
+        assert_eq!(chapters(&cue, 1000).unwrap()[1].start, 151);
+
+        let options = ImportOptions {
+            labels: LabelMapping::Ignore,
+            ..ImportOptions::default()
+        };
+        let (cue, _) = to_cue(&marks, "a.wav", &options).unwrap();
+        assert_eq!(cue.files[0].tracks[0].title, None);
+    }
+
+    #[test]
+    fn test_label_join() {
+        assert_eq!(
+            LabelMapping::PerformerTitle.join(Some("T"), Some("P")),
+            Some("P - T".to_string())
+        );
+        assert_eq!(
+            LabelMapping::Title.join(Some("T"), Some("P")),
+            Some("T".to_string())
+        );
+        assert_eq!(LabelMapping::PerformerTitle.join(None, Some("P")), None);
+        assert_eq!(LabelMapping::Ignore.join(Some("T"), None), None);
+    }
+
+    #[test]
+    fn test_to_cue_too_many_chapters() {
+        let marks: Vec<ChapterMark> = (0..100).map(|i| mark(i * 1_000_000_000)).collect();
+        assert!(to_cue(&marks, "a.wav", &ImportOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use cue::{Cue, CueFile, PregapMode, TrackMode};
+use errors::CueError;
+use hash::Crc32;
+use track_reader::{TrackReader, TrackReaderOptions};
+use wav::{PcmFormat, PcmSource};
+
+/// Samples (of 4 bytes) per CD frame
+const FRAME_SAMPLES: u64 = 588;
+
+/// Options for [`checksums`](fn.checksums.html).
+#[derive(Clone, Copy, Debug)]
+pub struct ChecksumOptions {
+    /// Which track the audio between INDEX 00 and INDEX 01 (the pregap) belongs to. EAC and
+    /// AccurateRip append pregaps to the previous track.
+    pub pregap: PregapMode,
+}
+
+impl Default for ChecksumOptions {
+    fn default() -> Self {
+        Self {
+            pregap: PregapMode::Append,
+        }
+    }
+}
+
+/// Checksums of the audio of one track.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrackChecksums {
+    /// Number of stereo samples in the track
+    pub samples: u64,
+    /// CRC-32 of the track's PCM data, as EAC's "Copy CRC"
+    pub crc32: u32,
+    /// CRC-32 of the track's PCM data without its zero 16-bit samples, as EAC's
+    /// "CRC w/o null samples"
+    pub crc32_without_null: u32,
+    /// AccurateRip v1 checksum
+    pub accuraterip_v1: u32,
+    /// AccurateRip v2 checksum
+    pub accuraterip_v2: u32,
+}
+
+// Checksums of a track's little-endian 16-bit stereo samples, skipping the first 5 frames of
+// the disc's first track and the last 5 frames of its last track for AccurateRip.
+fn track_checksums<R: Read>(
+    reader: &mut R,
+    len: u64,
+    big_endian: bool,
+    first: bool,
+    last: bool,
+) -> Result<TrackChecksums, CueError> {
+    let samples = len / 4;
+    let check_from = if first { FRAME_SAMPLES * 5 } else { 1 };
+    let check_to = if last {
+        samples.saturating_sub(FRAME_SAMPLES * 5)
+    } else {
+        samples
+    };
+
+    let mut crc = Crc32::new();
+    let mut crc_without_null = Crc32::new();
+    let mut v1: u32 = 0;
+    let mut v2: u32 = 0;
+    let mut position: u64 = 1;
+    let mut buffer = vec![0u8; 4 * FRAME_SAMPLES as usize * 16];
+    let mut pending = 0;
+
+    loop {
+        let read = reader.read(&mut buffer[pending..])?;
+        if read == 0 {
+            break;
+        }
+        let available = pending + read;
+        let whole = available - available % 4;
+        if big_endian {
+            for pair in buffer[..whole].chunks_mut(2) {
+                pair.swap(0, 1);
+            }
+        }
+
+        crc.update(&buffer[..whole]);
+        for sample in buffer[..whole].chunks(4) {
+            for word in sample.chunks(2) {
+                if word != [0, 0] {
+                    crc_without_null.update(word);
+                }
+            }
+
+            if position >= check_from && position <= check_to {
+                let value = u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                let product = u64::from(value) * (position & 0xFFFF_FFFF);
+                v1 = v1.wrapping_add(product as u32);
+                v2 = v2
+                    .wrapping_add((product >> 32) as u32)
+                    .wrapping_add(product as u32);
+            }
+            position += 1;
+        }
+
+        buffer.copy_within(whole..available, 0);
+        pending = available - whole;
+    }
+
+    Ok(TrackChecksums {
+        samples,
+        crc32: crc.finish(),
+        crc32_without_null: crc_without_null.finish(),
+        accuraterip_v1: v1,
+        accuraterip_v2: v2,
+    })
+}
+
+/// Computes the checksums of each AUDIO track of `cue`, keyed by track number. Each FILE is
+/// resolved relative to `cue_dir`.
+///
+/// See [`checksums_with`](fn.checksums_with.html).
+///
+/// # Failures
+///
+/// Fails if a FILE can not be read, is not 16-bit stereo 44.1KHz PCM, or has an unsupported
+/// type.
+pub fn checksums(
+    cue: &Cue,
+    cue_dir: &Path,
+    options: &ChecksumOptions,
+) -> Result<BTreeMap<u32, TrackChecksums>, CueError> {
+    checksums_with(
+        cue,
+        |file| Ok(File::open(cue_dir.join(file.file.replace('\\', "/")))?),
+        options,
+    )
+}
+
+/// Computes the checksums of each AUDIO track of `cue`, keyed by track number, reading each
+/// FILE from the reader returned by `open`.
+///
+/// Tracks cover the exact sample range of their INDEXes in their FILE, with the pregap assigned
+/// according to `options`. WAVE, BINARY and MOTOROLA files are supported. Data tracks are
+/// skipped. For the AccurateRip checksums, the first audio track leaves out its first 5 frames
+/// (minus one sample) and the last audio track its last 5 frames.
+///
+/// # Example
+///
+/// ```
+/// use rcue::checksum::{checksums_with, ChecksumOptions};
+/// use rcue::parser::parse;
+/// use std::io::Cursor;
+///
+/// let sheet = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+/// let cue = parse(&mut Cursor::new(sheet), true).unwrap();
+/// let sums = checksums_with(&cue, |_| Ok(Cursor::new(vec![0u8; 2352])), &ChecksumOptions::default()).unwrap();
+/// assert_eq!(sums[&1].samples, 588);
+/// assert_eq!(sums[&1].crc32_without_null, 0);
+/// ```
+///
+/// # Failures
+///
+/// Fails if `open` fails, or a FILE is not 16-bit stereo 44.1KHz PCM or has an unsupported type.
+pub fn checksums_with<R, F>(
+    cue: &Cue,
+    mut open: F,
+    options: &ChecksumOptions,
+) -> Result<BTreeMap<u32, TrackChecksums>, CueError>
+where
+    R: Read + Seek,
+    F: FnMut(&CueFile) -> Result<R, CueError>,
+{
+    let is_audio = |file: &CueFile, position: usize| {
+        file.tracks[position].mode().unwrap_or(TrackMode::Audio) == TrackMode::Audio
+    };
+    let audio: Vec<(usize, usize)> = cue
+        .files
+        .iter()
+        .enumerate()
+        .flat_map(|(f, file)| (0..file.tracks.len()).map(move |t| (f, t)))
+        .filter(|&(f, t)| is_audio(&cue.files[f], t))
+        .collect();
+
+    let mut sums = BTreeMap::new();
+    for (f, file) in cue.files.iter().enumerate() {
+        if !audio.iter().any(|&(audio_file, _)| audio_file == f) {
+            continue;
+        }
+        let mut source = open(file)?;
+        let pcm = PcmSource::open(&mut source, &file.format)?;
+        let cd = PcmFormat {
+            big_endian: pcm.format.big_endian,
+            ..PcmFormat::cd()
+        };
+        if pcm.format != cd {
+            return Err(CueError::Parse(format!(
+                "{} is not 16-bit stereo 44.1KHz PCM",
+                file.file
+            )));
+        }
+
+        for position in 0..file.tracks.len() {
+            if !is_audio(file, position) {
+                continue;
+            }
+            let no: u32 = file.tracks[position].no.parse().map_err(|_| {
+                CueError::Parse(format!("bad track number {}", file.tracks[position].no))
+            })?;
+            let first = audio.first() == Some(&(f, position));
+            let last = audio.last() == Some(&(f, position));
+
+            let mut reader = TrackReader::new(
+                source,
+                file,
+                cue.files.get(f + 1),
+                position,
+                TrackReaderOptions {
+                    pregap: options.pregap,
+                    wav_header: false,
+                },
+            )?;
+            let len = reader.len();
+            sums.insert(
+                no,
+                track_checksums(&mut reader, len, pcm.format.big_endian, first, last)?,
+            );
+            source = reader.into_inner();
+        }
+    }
+
+    Ok(sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::hash_reader;
+    use parser::parse;
+    use std::io::Cursor;
+    use wav::header;
+
+    fn sheet(text: &str) -> Cue {
+        parse(&mut Cursor::new(text), true).unwrap()
+    }
+
+    // Reference AccurateRip implementation over a whole track's samples.
+    fn accuraterip(samples: &[u32], first: bool, last: bool) -> (u32, u32) {
+        let from = if first { 2940 } else { 1 };
+        let to = if last {
+            samples.len() as u64 - 2940
+        } else {
+            samples.len() as u64
+        };
+        let (mut v1, mut v2) = (0u32, 0u32);
+        for (i, sample) in samples.iter().enumerate() {
+            let multiplier = i as u64 + 1;
+            if multiplier >= from && multiplier <= to {
+                v1 = v1.wrapping_add(sample.wrapping_mul(multiplier as u32));
+                let product = u64::from(*sample) * multiplier;
+                v2 = v2
+                    .wrapping_add((product >> 32) as u32)
+                    .wrapping_add(product as u32);
+            }
+        }
+        (v1, v2)
+    }
+
+    fn image(frames: usize) -> Vec<u8> {
+        (0..frames * 2352)
+            .map(|i| if i % 7 == 0 { 0 } else { (i * 31 % 251) as u8 })
+            .collect()
+    }
+
+    #[test]
+    fn test_checksums() {
+        let cue = sheet(
+            "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:00:10\n    INDEX 01 00:00:12\n  TRACK 03 AUDIO\n    INDEX 01 00:00:20\n",
+        );
+        let data = image(30);
+        let sums = checksums_with(
+            &cue,
+            |_| Ok(Cursor::new(data.clone())),
+            &ChecksumOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(sums.keys().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let ranges = [(0, 12), (12, 20), (20, 30)];
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            let bytes = &data[start * 2352..end * 2352];
+            let sum = sums[&(i as u32 + 1)];
+            assert_eq!(sum.samples, (end - start) as u64 * 588);
+
+            let expected = hash_reader(&mut &bytes[..]).unwrap().crc32;
+            assert_eq!(format!("{:08x}", sum.crc32), expected);
+
+            let mut non_null = Crc32::new();
+            for word in bytes.chunks(2).filter(|w| *w != [0, 0]) {
+                non_null.update(word);
+            }
+            assert_eq!(sum.crc32_without_null, non_null.finish());
+
+            let samples: Vec<u32> = bytes
+                .chunks(4)
+                .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+                .collect();
+            let (v1, v2) = accuraterip(&samples, i == 0, i == 2);
+            assert_eq!((sum.accuraterip_v1, sum.accuraterip_v2), (v1, v2));
+        }
+    }
+
+    #[test]
+    fn test_accuraterip_known_answer() {
+        // A 20-frame disc of one track in which every sample has the same value. AccurateRip
+        // sums multipliers 2940 to 11760 - 2940 = 8820: 5881 samples whose multipliers add up to
+        // (2940 + 8820) * 5881 / 2 = 34580280.
+        let cue = sheet("FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n");
+        let sums = |sample: [u8; 4]| {
+            let data: Vec<u8> = sample.iter().cloned().cycle().take(20 * 2352).collect();
+            let sums = checksums_with(
+                &cue,
+                |_| Ok(Cursor::new(data.clone())),
+                &ChecksumOptions::default(),
+            )
+            .unwrap();
+            (sums[&1].accuraterip_v1, sums[&1].accuraterip_v2)
+        };
+
+        assert_eq!(sums([1, 0, 0, 0]), (34_580_280, 34_580_280));
+        // Each product k * (2^32 - 1) has k - 1 in its high word and 2^32 - k in its low word, so
+        // v1 is -34580280 and v2 is -5881, modulo 2^32
+        assert_eq!(sums([0xFF; 4]), (4_260_387_016, 4_294_961_415));
+    }
+
+    #[test]
+    fn test_prepend_and_motorola() {
+        let text = "FILE \"a.bin\" MOTOROLA\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:00:08\n    INDEX 01 00:00:10\n";
+        let cue = sheet(text);
+        let data = image(20);
+        let swapped: Vec<u8> = data.chunks(2).flat_map(|w| vec![w[1], w[0]]).collect();
+        let options = ChecksumOptions {
+            pregap: PregapMode::Prepend,
+        };
+
+        let big = checksums_with(&cue, |_| Ok(Cursor::new(swapped.clone())), &options).unwrap();
+        let little = checksums_with(
+            &sheet(&text.replace("MOTOROLA", "BINARY")),
+            |_| Ok(Cursor::new(data.clone())),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(big, little);
+        assert_eq!(big[&2].samples, 12 * 588);
+    }
+
+    #[test]
+    fn test_data_tracks_skipped() {
+        let cue = sheet(
+            "FILE \"a.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:10\n",
+        );
+        let data = image(20);
+        let sums = checksums_with(
+            &cue,
+            |_| Ok(Cursor::new(data.clone())),
+            &ChecksumOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(sums.keys().cloned().collect::<Vec<_>>(), vec![2]);
+
+        // Track 02 is both the first and the last audio track
+        let samples: Vec<u32> = data[10 * 2352..]
+            .chunks(4)
+            .map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect();
+        let (v1, _) = accuraterip(&samples, true, true);
+        assert_eq!(sums[&2].accuraterip_v1, v1);
+    }
+
+    #[test]
+    fn test_not_cd_audio() {
+        let cue = sheet("FILE \"a.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n");
+        let format = PcmFormat {
+            sample_rate: 48000,
+            ..PcmFormat::cd()
+        };
+        let mut wav = header(&format, 4);
+        wav.extend(&[0u8; 4]);
+        assert!(checksums_with(
+            &cue,
+            |_| Ok(Cursor::new(wav.clone())),
+            &ChecksumOptions::default()
+        )
+        .is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use std::convert::TryFrom;
 use std::time::Duration;
 
+use errors::CueError;
+use util::{duration_to_frames, frames_to_duration, frames_to_timestamp, timestamp_to_duration};
+
 /// Represents a CUE command in a CUE sheet.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Command {
@@ -40,6 +44,80 @@ pub enum Command {
     None,
 }
 
+/// Data mode of a TRACK, which decides the size of its sectors in a BINARY image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackMode {
+    /// Audio, 2352 bytes per sector
+    Audio,
+    /// Karaoke CD+G, 2448 bytes per sector
+    Cdg,
+    /// CD-ROM Mode 1 data (cooked), 2048 bytes per sector
+    Mode1_2048,
+    /// CD-ROM Mode 1 data (raw), 2352 bytes per sector
+    Mode1_2352,
+    /// CD-ROM XA Mode 2 Form 1 data (cooked), 2048 bytes per sector
+    Mode2_2048,
+    /// CD-ROM XA Mode 2 Form 2 data, 2324 bytes per sector
+    Mode2_2324,
+    /// CD-ROM Mode 2 data, 2336 bytes per sector
+    Mode2_2336,
+    /// CD-ROM Mode 2 data (raw), 2352 bytes per sector
+    Mode2_2352,
+    /// CD-I Mode 2 data, 2336 bytes per sector
+    Cdi2336,
+    /// CD-I Mode 2 data (raw), 2352 bytes per sector
+    Cdi2352,
+}
+
+impl TrackMode {
+    /// Parses a TRACK mode (eg. `MODE1/2352`), ignoring case.
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_uppercase().as_ref() {
+            "AUDIO" => Some(TrackMode::Audio),
+            "CDG" => Some(TrackMode::Cdg),
+            "MODE1/2048" => Some(TrackMode::Mode1_2048),
+            "MODE1/2352" => Some(TrackMode::Mode1_2352),
+            "MODE2/2048" => Some(TrackMode::Mode2_2048),
+            "MODE2/2324" => Some(TrackMode::Mode2_2324),
+            "MODE2/2336" => Some(TrackMode::Mode2_2336),
+            "MODE2/2352" => Some(TrackMode::Mode2_2352),
+            "CDI/2336" => Some(TrackMode::Cdi2336),
+            "CDI/2352" => Some(TrackMode::Cdi2352),
+            _ => None,
+        }
+    }
+
+    /// Returns the mode as written in a CUE sheet.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrackMode::Audio => "AUDIO",
+            TrackMode::Cdg => "CDG",
+            TrackMode::Mode1_2048 => "MODE1/2048",
+            TrackMode::Mode1_2352 => "MODE1/2352",
+            TrackMode::Mode2_2048 => "MODE2/2048",
+            TrackMode::Mode2_2324 => "MODE2/2324",
+            TrackMode::Mode2_2336 => "MODE2/2336",
+            TrackMode::Mode2_2352 => "MODE2/2352",
+            TrackMode::Cdi2336 => "CDI/2336",
+            TrackMode::Cdi2352 => "CDI/2352",
+        }
+    }
+
+    /// Returns the number of bytes a sector of this mode occupies in a BINARY image.
+    pub fn sector_size(self) -> u64 {
+        match self {
+            TrackMode::Mode1_2048 | TrackMode::Mode2_2048 => 2048,
+            TrackMode::Mode2_2324 => 2324,
+            TrackMode::Mode2_2336 | TrackMode::Cdi2336 => 2336,
+            TrackMode::Cdg => 2448,
+            TrackMode::Audio
+            | TrackMode::Mode1_2352
+            | TrackMode::Mode2_2352
+            | TrackMode::Cdi2352 => 2352,
+        }
+    }
+}
+
 /// Represents a TRACK in a [`CueFile`](struct.CueFile.html).
 #[derive(Clone, Debug, PartialEq)]
 pub struct Track {
@@ -53,6 +131,11 @@ pub struct Track {
     pub performer: Option<String>,
     /// (index, timestamp)
     pub indices: Vec<(String, Duration)>,
+    /// (index, timestamp) of indices that appear before this track's FILE, in the previous FILE
+    ///
+    /// Multi-file sheets with gaps appended to the previous track store the pregap of a track
+    /// as an INDEX 00 at the end of the previous FILE.
+    pub previous_file_indices: Vec<(String, Duration)>,
     /// Pregap of the track in `Duration`, converted from frames (75 frames = 1s)
     pub pregap: Option<Duration>,
     /// Postgap of the track in `Duration`, converted from frames (75 frames = 1s)
@@ -72,6 +155,9 @@ pub struct Track {
     pub songwriter: Option<String>,
     /// Raw lines from unhandled fields
     pub unknown: Vec<String>,
+    /// Number of the [`Session`](struct.Session.html) the track belongs to, set from
+    /// `REM SESSION` on multi-session sheets
+    pub session: Option<u32>,
 }
 
 impl Track {
@@ -86,12 +172,189 @@ impl Track {
             pregap: None,
             postgap: None,
             indices: Vec::new(),
+            previous_file_indices: Vec::new(),
             comments: Vec::new(),
             unknown: Vec::new(),
             flags: Vec::new(),
             isrc: None,
+            session: None,
         }
     }
+
+    /// Returns the parsed [`format`](#structfield.format) of the track, if it is a known mode.
+    pub fn mode(&self) -> Option<TrackMode> {
+        TrackMode::parse(&self.format)
+    }
+
+    /// Returns the timestamp of the INDEX numbered `no`, if present.
+    pub fn index(&self, no: u32) -> Option<Duration> {
+        self.indices
+            .iter()
+            .find(|(idx, _)| idx.parse::<u32>().ok() == Some(no))
+            .map(|(_, time)| *time)
+    }
+
+    /// Returns the start of the track proper: INDEX 01, or the first index if INDEX 01 is missing.
+    pub fn start(&self) -> Option<Duration> {
+        self.index(1)
+            .or_else(|| self.indices.first().map(|(_, time)| *time))
+    }
+
+    /// Returns the start of the track's pregap: INDEX 00, or [`start`](#method.start) if the track has no INDEX 00.
+    pub fn pregap_start(&self) -> Option<Duration> {
+        self.index(0).or_else(|| self.start())
+    }
+}
+
+/// A session of a multi-session disc (eg. an Enhanced CD), as written by `REM SESSION`.
+///
+/// Between two sessions, the disc has the lead-out of the first, the lead-in of the second and
+/// the pregap of the second's first track, none of which are stored in the image files. Missing
+/// lengths default to the Blue Book values: a 1:30 lead-out after the first session and 0:30
+/// after later ones, a 1:00 lead-in and a 0:02 pregap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    /// Session number, starting at 1
+    pub no: u32,
+    /// Length of the lead-in before the session (`REM LEAD-IN`)
+    pub lead_in: Option<Duration>,
+    /// Length of the pregap of the session's first track (`REM PREGAP`)
+    pub pregap: Option<Duration>,
+    /// Length of the lead-out after the session (`REM LEAD-OUT`)
+    pub lead_out: Option<Duration>,
+}
+
+impl Session {
+    /// Constructs a new [`Session`](struct.Session.html) with default gap lengths.
+    pub fn new(no: u32) -> Self {
+        Self {
+            no,
+            lead_in: None,
+            pregap: None,
+            lead_out: None,
+        }
+    }
+
+    /// Returns the length of the lead-out after this session in CD frames.
+    pub fn lead_out_frames(&self) -> u64 {
+        self.lead_out
+            .map(|lead_out| duration_to_frames(&lead_out))
+            .unwrap_or(if self.no <= 1 { 6750 } else { 2250 })
+    }
+
+    /// Returns the length of the lead-in and first pregap before this session in CD frames.
+    pub fn lead_in_frames(&self) -> u64 {
+        self.lead_in
+            .map_or(4500, |lead_in| duration_to_frames(&lead_in))
+            + self
+                .pregap
+                .map_or(150, |pregap| duration_to_frames(&pregap))
+    }
+}
+
+/// Decides which track the audio between INDEX 00 and INDEX 01 (the pregap) belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PregapMode {
+    /// Pregap is appended to the end of the previous track
+    Append,
+    /// Pregap is prepended to the start of the track it belongs to
+    Prepend,
+    /// Pregap is dropped
+    Discard,
+}
+
+/// An offset for [`Cue::shift`](struct.Cue.html#method.shift).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Offset {
+    /// CD frames (75 frames = 1s)
+    Frames(i64),
+    /// (samples, sample rate), rounded to the nearest frame, eg. a read offset correction
+    Samples(i64, u32),
+}
+
+impl Offset {
+    /// Parses an `mm:ss:ff` offset, negative if it starts with `-`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rcue::cue::Offset;
+    ///
+    /// assert_eq!(Offset::from_timestamp("-00:02:00").unwrap(), Offset::Frames(-150));
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Fails if the timestamp is not valid
+    pub fn from_timestamp(s: &str) -> Result<Self, CueError> {
+        let (sign, timestamp) = match s.strip_prefix('-') {
+            Some(timestamp) => (-1, timestamp),
+            None => (1, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let frames = duration_to_frames(&timestamp_to_duration(timestamp)?);
+        Ok(Offset::Frames(sign * frames as i64))
+    }
+
+    /// Returns the offset in CD frames.
+    ///
+    /// # Failures
+    ///
+    /// Fails if a sample rate is 0, or if the offset is too large to count in frames
+    pub fn frames(self) -> Result<i64, CueError> {
+        match self {
+            Offset::Frames(frames) => Ok(frames),
+            Offset::Samples(_, 0) => Err(CueError::Parse("sample rate of 0".to_string())),
+            Offset::Samples(samples, sample_rate) => {
+                let rate = i128::from(sample_rate);
+                let frames = (i128::from(samples).abs() * 75 + rate / 2) / rate;
+                i64::try_from(i128::from(samples.signum()) * frames).map_err(|_| {
+                    CueError::Parse(format!("offset of {} samples is out of range", samples))
+                })
+            }
+        }
+    }
+}
+
+/// The indices changed by [`Cue::shift`](struct.Cue.html#method.shift) and
+/// [`Cue::scale`](struct.Cue.html#method.scale).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// Every index of the sheet
+    Disc,
+    /// Indices in the FILE with this position (from 0), including a pregap at its end that
+    /// belongs to the first track of the next FILE
+    File(usize),
+}
+
+/// Decides what [`Cue::shift`](struct.Cue.html#method.shift) does with indices that would move
+/// before the start of their FILE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Underflow {
+    /// Move them to the start of the FILE
+    Clamp,
+    /// Fail, leaving the sheet unchanged
+    Fail,
+}
+
+/// Decides where the audio of a track removed by
+/// [`Cue::delete_track`](struct.Cue.html#method.delete_track) goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Audio becomes the end of the previous track
+    MergePrevious,
+    /// Audio becomes the start of the next track, which keeps its own metadata
+    MergeNext,
+    /// Audio becomes the pregap (INDEX 00) of the next track, which players skip
+    Pregap,
+}
+
+/// A range of CD frames (75 frames = 1s) in a [`CueFile`](struct.CueFile.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// First frame of the range
+    pub start: u64,
+    /// Frame after the last frame of the range, or `None` if the range runs to the end of the file
+    pub end: Option<u64>,
 }
 
 /// Represents a FILE in a [`Cue`](struct.Cue.html).
@@ -118,10 +381,101 @@ impl CueFile {
             comments: Vec::new(),
         }
     }
+
+    /// Returns the frame range of each track in this file, in the same order as
+    /// [`tracks`](#structfield.tracks), with pregaps assigned according to `mode`.
+    ///
+    /// Tracks without any INDEX have no span. A pregap at the end of this file that belongs to
+    /// the next FILE's first track is not known here; see
+    /// [`track_spans_before`](#method.track_spans_before).
+    pub fn track_spans(&self, mode: PregapMode) -> Vec<Option<Span>> {
+        self.track_spans_before(mode, None)
+    }
+
+    /// Returns the frame range of each track in this file like
+    /// [`track_spans`](#method.track_spans), where `next` is the FILE after this one in the
+    /// sheet.
+    ///
+    /// When the first track of `next` has its pregap at the end of this file
+    /// ([`previous_file_indices`](struct.Track.html#structfield.previous_file_indices)), the
+    /// last track of this file ends where that pregap starts unless `mode` is
+    /// [`Append`](enum.PregapMode.html#variant.Append).
+    pub fn track_spans_before(
+        &self,
+        mode: PregapMode,
+        next: Option<&CueFile>,
+    ) -> Vec<Option<Span>> {
+        let carried = match mode {
+            PregapMode::Append => None,
+            PregapMode::Prepend | PregapMode::Discard => {
+                next.and_then(|next| next.tracks.first()).and_then(|first| {
+                    first
+                        .previous_file_indices
+                        .iter()
+                        .map(|(_, time)| *time)
+                        .min()
+                })
+            }
+        };
+        self.tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let start = match mode {
+                    PregapMode::Prepend => track.pregap_start(),
+                    PregapMode::Append | PregapMode::Discard => track.start(),
+                }?;
+                let end = self.tracks[i + 1..]
+                    .iter()
+                    .filter_map(|next| match mode {
+                        PregapMode::Append => next.start(),
+                        PregapMode::Prepend | PregapMode::Discard => next.pregap_start(),
+                    })
+                    .next()
+                    .or(carried);
+
+                Some(Span {
+                    start: duration_to_frames(&start),
+                    end: end.map(|end| duration_to_frames(&end)),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the byte offset of `frame` in a BINARY or MOTOROLA image, where each track's
+    /// sectors take up the [`sector_size`](enum.TrackMode.html#method.sector_size) of its mode.
+    ///
+    /// Tracks of unknown mode and frames before the first track are assumed to be 2352 bytes
+    /// per sector.
+    pub fn sector_offset(&self, frame: u64) -> u64 {
+        let mut offset = 0;
+        let mut current: Option<(u64, u64)> = None;
+
+        for track in &self.tracks {
+            let start = match track.pregap_start() {
+                Some(start) => duration_to_frames(&start),
+                None => continue,
+            };
+            if start > frame {
+                break;
+            }
+            // Frames before the first track are 2352 bytes
+            offset += match current {
+                Some((previous, size)) => start.saturating_sub(previous) * size,
+                None => start * 2352,
+            };
+            current = Some((start, track.mode().map_or(2352, TrackMode::sector_size)));
+        }
+
+        match current {
+            Some((start, size)) => offset + (frame - start) * size,
+            None => frame * 2352,
+        }
+    }
 }
 
 /// Represents a CUE sheet.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Cue {
     /// Path to the data used for the following TRACK commands
     pub files: Vec<CueFile>,
@@ -139,6 +493,10 @@ pub struct Cue {
     pub comments: Vec<(String, String)>, // are REM fields unique?
     /// Unparsed lines
     pub unknown: Vec<String>,
+    /// Sessions of a multi-session sheet, in order; empty if the sheet has no `REM SESSION`
+    pub sessions: Vec<Session>,
+    /// Media type of the disc the image was made from (`REM ORIGINAL MEDIA-TYPE:`), eg. `CD`
+    pub original_media_type: Option<String>,
 }
 
 impl Cue {
@@ -153,6 +511,888 @@ impl Cue {
             catalog: None,
             comments: Vec::new(),
             unknown: Vec::new(),
+            sessions: Vec::new(),
+            original_media_type: None,
+        }
+    }
+
+    /// Returns the tracks of each session, in order. Sheets without sessions have a single
+    /// group numbered 1.
+    pub fn session_tracks(&self) -> Vec<(u32, Vec<&Track>)> {
+        let mut groups: Vec<(u32, Vec<&Track>)> = Vec::new();
+        for track in self.files.iter().flat_map(|file| &file.tracks) {
+            let no = track.session.unwrap_or(1);
+            match groups.last_mut() {
+                Some(group) if group.0 == no => group.1.push(track),
+                _ => groups.push((no, vec![track])),
+            }
+        }
+        groups
+    }
+
+    /// Returns the number of frames between the end of session `no - 1` and the start of
+    /// session `no`: the lead-out, lead-in and pregap that are not part of the image.
+    ///
+    /// The gap before the first session, or an unknown session, is 0.
+    pub fn session_gap(&self, no: u32) -> u64 {
+        let session = self.sessions.iter().position(|session| session.no == no);
+        match session {
+            Some(i) if i > 0 => {
+                self.sessions[i - 1].lead_out_frames() + self.sessions[i].lead_in_frames()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns the LBA (the absolute sector address, with INDEX 01 of a single-session disc's
+    /// first track at 0) of INDEX 01 of each track, in sheet order.
+    ///
+    /// FILEs are assumed to follow each other on the disc, so `file_lengths` must give the length
+    /// in CD frames of every FILE but the last. PREGAP and POSTGAP silence, which is not part of
+    /// any FILE, moves the tracks after it back, as does the
+    /// [`session_gap`](#method.session_gap) of each session before a track. Tracks without
+    /// INDEX 01 have no LBA.
+    ///
+    /// # Failures
+    ///
+    /// Fails if `file_lengths` is missing the length of a FILE that is followed by another.
+    pub fn track_lbas(&self, file_lengths: &[u64]) -> Result<Vec<Option<u64>>, CueError> {
+        let mut lbas = Vec::new();
+        let mut file_start = 0;
+        let mut gap = 0;
+        let mut session = None;
+
+        for (i, file) in self.files.iter().enumerate() {
+            for track in &file.tracks {
+                if track.session.is_some() && track.session != session {
+                    session = track.session;
+                    gap += self.session_gap(track.session.unwrap_or(1));
+                }
+                gap += track.pregap.as_ref().map_or(0, duration_to_frames);
+                lbas.push(
+                    track
+                        .index(1)
+                        .map(|start| file_start + gap + duration_to_frames(&start)),
+                );
+                gap += track.postgap.as_ref().map_or(0, duration_to_frames);
+            }
+            if i + 1 < self.files.len() {
+                file_start += file_lengths.get(i).ok_or_else(|| {
+                    CueError::Parse(format!("missing length of FILE {}", file.file))
+                })?;
+            }
+        }
+
+        Ok(lbas)
+    }
+
+    /// Checks that sessions and the tracks in them are in order: sessions are numbered from 1
+    /// without gaps, every track belongs to a listed session, no session is empty and session
+    /// numbers never decrease from one track to the next.
+    ///
+    /// # Failures
+    ///
+    /// Fails with the first ordering problem found.
+    pub fn validate_sessions(&self) -> Result<(), CueError> {
+        if self.sessions.is_empty() {
+            return match self.files.iter().flat_map(|file| &file.tracks).next() {
+                Some(track) if track.session.is_some() => Err(CueError::Parse(format!(
+                    "track {} has a session but the sheet has none",
+                    track.no
+                ))),
+                _ => Ok(()),
+            };
+        }
+
+        for (i, session) in self.sessions.iter().enumerate() {
+            if session.no as usize != i + 1 {
+                return Err(CueError::Parse(format!(
+                    "session {} out of order, expected session {}",
+                    session.no,
+                    i + 1
+                )));
+            }
+        }
+
+        let mut current = 0;
+        for track in self.files.iter().flat_map(|file| &file.tracks) {
+            let no = track.session.ok_or_else(|| {
+                CueError::Parse(format!("track {} is not in a session", track.no))
+            })?;
+            if no < current || no as usize > self.sessions.len() {
+                return Err(CueError::Parse(format!(
+                    "track {} in session {} is out of order",
+                    track.no, no
+                )));
+            }
+            if no > current + 1 {
+                return Err(CueError::Parse(format!(
+                    "session {} has no tracks",
+                    current + 1
+                )));
+            }
+            current = no;
+        }
+        if (current as usize) < self.sessions.len() {
+            return Err(CueError::Parse(format!(
+                "session {} has no tracks",
+                current + 1
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Points the only FILE of a single-file sheet at `file`, as needed when a sheet embedded in
+    /// an audio file names a different file than its host (eg. after renaming).
+    ///
+    /// Returns true if the FILE was changed. Sheets with several FILEs are left untouched.
+    pub fn rebind(&mut self, file: &str) -> bool {
+        match self.files.as_mut_slice() {
+            [only] if only.file != file => {
+                only.file = file.to_string();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Returns the FILE of track `no` and its position in that FILE.
+    fn locate(&self, no: u32) -> Result<(usize, usize), CueError> {
+        for (f, file) in self.files.iter().enumerate() {
+            let found = file
+                .tracks
+                .iter()
+                .position(|track| track.no.parse::<u32>().ok() == Some(no));
+            if let Some(t) = found {
+                return Ok((f, t));
+            }
+        }
+        Err(CueError::Parse(format!("no track {:02}", no)))
+    }
+
+    fn previous_position(&self, f: usize, t: usize) -> Option<(usize, usize)> {
+        if t > 0 {
+            return Some((f, t - 1));
+        }
+        (0..f)
+            .rev()
+            .find(|&pf| !self.files[pf].tracks.is_empty())
+            .map(|pf| (pf, self.files[pf].tracks.len() - 1))
+    }
+
+    fn next_position(&self, f: usize, t: usize) -> Option<(usize, usize)> {
+        if t + 1 < self.files[f].tracks.len() {
+            return Some((f, t + 1));
+        }
+        (f + 1..self.files.len())
+            .find(|&nf| !self.files[nf].tracks.is_empty())
+            .map(|nf| (nf, 0))
+    }
+
+    fn first_track_no(&self) -> u32 {
+        self.files
+            .iter()
+            .flat_map(|file| &file.tracks)
+            .next()
+            .and_then(|track| track.no.parse().ok())
+            .unwrap_or(1)
+    }
+
+    /// Renumbers the tracks consecutively in sheet order, starting from the number of the first
+    /// track.
+    pub fn renumber(&mut self) {
+        let first = self.first_track_no();
+        self.renumber_from(first);
+    }
+
+    fn renumber_from(&mut self, first: u32) {
+        let tracks = self
+            .files
+            .iter_mut()
+            .flat_map(|file| file.tracks.iter_mut());
+        for (i, track) in tracks.enumerate() {
+            track.no = format!("{:02}", first + i as u32);
+        }
+    }
+
+    /// Deletes track `no`, giving its audio to a neighbour according to `mode`, and renumbers
+    /// the tracks after it.
+    ///
+    /// The indices of the deleted track are dropped, including a pregap at the end of the
+    /// previous FILE. A FILE left without tracks continues the track before it. PREGAP and
+    /// POSTGAP silence that would end up inside a track is dropped.
+    ///
+    /// # Failures
+    ///
+    /// Fails if there is no track `no`, if the neighbour `mode` needs is missing or in another
+    /// session, or, with [`Pregap`](enum.DeleteMode.html#variant.Pregap), if the next track is
+    /// not in the same or the following FILE.
+    pub fn delete_track(&mut self, no: u32, mode: DeleteMode) -> Result<(), CueError> {
+        let position = self.locate(no)?;
+        self.delete_at(position, mode)
+    }
+
+    /// Merges the track after track `no` into it and renumbers the tracks after them. The second
+    /// track's audio, pregap included, becomes the end of track `no`, as with
+    /// [`delete_track`](#method.delete_track) and
+    /// [`MergePrevious`](enum.DeleteMode.html#variant.MergePrevious).
+    ///
+    /// # Failures
+    ///
+    /// Fails if there is no track `no`, if it is the last track or if the next track is in
+    /// another session.
+    pub fn merge_tracks(&mut self, no: u32) -> Result<(), CueError> {
+        let (f, t) = self.locate(no)?;
+        let next = self
+            .next_position(f, t)
+            .ok_or_else(|| CueError::Parse(format!("track {:02} is the last track", no)))?;
+        self.delete_at(next, DeleteMode::MergePrevious)
+    }
+
+    fn delete_at(&mut self, (f, t): (usize, usize), mode: DeleteMode) -> Result<(), CueError> {
+        let first = self.first_track_no();
+        let no = self.files[f].tracks[t].no.clone();
+        let (nf, nt) = match mode {
+            DeleteMode::MergePrevious => self
+                .previous_position(f, t)
+                .ok_or_else(|| CueError::Parse(format!("track {} has no previous track", no)))?,
+            DeleteMode::MergeNext | DeleteMode::Pregap => self
+                .next_position(f, t)
+                .ok_or_else(|| CueError::Parse(format!("track {} has no next track", no)))?,
+        };
+        let neighbour = &self.files[nf].tracks[nt];
+        if neighbour.session != self.files[f].tracks[t].session {
+            return Err(CueError::Parse(format!(
+                "tracks {} and {} are in different sessions",
+                no, neighbour.no
+            )));
         }
+
+        match mode {
+            DeleteMode::MergePrevious => {
+                let track = self.files[f].tracks.remove(t);
+                self.files[nf].tracks[nt].postgap = track.postgap;
+            }
+            DeleteMode::MergeNext => {
+                let next = self.files[nf].tracks.remove(nt);
+                let track = &mut self.files[f].tracks[t];
+                track.format = next.format;
+                track.title = next.title;
+                track.performer = next.performer;
+                track.songwriter = next.songwriter;
+                track.isrc = next.isrc;
+                track.flags = next.flags;
+                track.comments = next.comments;
+                track.unknown = next.unknown;
+                track.postgap = next.postgap;
+            }
+            DeleteMode::Pregap => {
+                let start = self.files[f].tracks[t]
+                    .pregap_start()
+                    .ok_or_else(|| CueError::Parse(format!("track {} has no INDEX", no)))?;
+                let pregap = vec![("00".to_string(), start)];
+                if nf == f {
+                    let next = &mut self.files[nf].tracks[nt];
+                    next.indices
+                        .retain(|(index, _)| index.parse::<u32>().ok() != Some(0));
+                    next.indices.splice(0..0, pregap);
+                } else if nf == f + 1 {
+                    let next = &mut self.files[nf].tracks[nt];
+                    next.indices
+                        .retain(|(index, _)| index.parse::<u32>().ok() != Some(0));
+                    next.previous_file_indices = pregap;
+                } else {
+                    return Err(CueError::Parse(format!(
+                        "track {} is not followed by a track in the same or the next FILE",
+                        no
+                    )));
+                }
+                let track = self.files[f].tracks.remove(t);
+                let nt = if nf == f { nt - 1 } else { nt };
+                self.files[nf].tracks[nt].pregap = track.pregap;
+            }
+        }
+
+        self.renumber_from(first);
+        Ok(())
+    }
+
+    /// Inserts `track` with its INDEX 01 at `at` in the FILE numbered `file` (from 0), renumbers
+    /// the tracks after it and returns the new track's number.
+    ///
+    /// The number, indices and session of `track` are replaced. It joins the session of the
+    /// track it is cut from, and takes the indices of that track after `at`, renumbered from
+    /// INDEX 02.
+    ///
+    /// # Failures
+    ///
+    /// Fails if there is no FILE `file`, or if `at` is the position of an index or inside a
+    /// pregap.
+    pub fn insert_track(
+        &mut self,
+        file: usize,
+        at: Duration,
+        track: Track,
+    ) -> Result<u32, CueError> {
+        let first = self.first_track_no();
+        let frames = duration_to_frames(&at);
+        let tracks = &self
+            .files
+            .get(file)
+            .ok_or_else(|| CueError::Parse(format!("no FILE {}", file)))?
+            .tracks;
+        let timestamp = frames_to_timestamp(frames);
+
+        for existing in tracks {
+            if existing
+                .indices
+                .iter()
+                .any(|(_, time)| duration_to_frames(time) == frames)
+            {
+                return Err(CueError::Parse(format!(
+                    "track {} has an index at {}",
+                    existing.no, timestamp
+                )));
+            }
+        }
+        // Pregaps in this FILE: INDEX 00 of its tracks, the rest of the pregap of a first track
+        // started in the previous FILE, and the pregap of the next FILE's first track
+        let mut pregaps: Vec<(&Track, u64, Option<u64>)> = tracks
+            .iter()
+            .filter_map(|existing| {
+                let start = existing.index(0)?;
+                let end = existing.index(1).map(|end| duration_to_frames(&end));
+                Some((existing, duration_to_frames(&start), end))
+            })
+            .collect();
+        if let Some(existing) = tracks.first() {
+            if !existing.previous_file_indices.is_empty() {
+                let end = existing.start().map(|end| duration_to_frames(&end));
+                pregaps.push((existing, 0, end));
+            }
+        }
+        if let Some(existing) = self
+            .files
+            .get(file + 1)
+            .and_then(|next| next.tracks.first())
+        {
+            if let Some(start) = existing
+                .previous_file_indices
+                .iter()
+                .map(|(_, time)| duration_to_frames(time))
+                .min()
+            {
+                pregaps.push((existing, start, None));
+            }
+        }
+        for (existing, start, end) in pregaps {
+            if start <= frames && end.is_none_or(|end| frames < end) {
+                return Err(CueError::Parse(format!(
+                    "{} is inside the pregap of track {}",
+                    timestamp, existing.no
+                )));
+            }
+        }
+
+        let t = tracks
+            .iter()
+            .take_while(|existing| {
+                existing
+                    .start()
+                    .is_none_or(|start| duration_to_frames(&start) < frames)
+            })
+            .count();
+        let session = match self.previous_position(file, t) {
+            Some((pf, pt)) => self.files[pf].tracks[pt].session,
+            None => self.files[file..]
+                .iter()
+                .flat_map(|next| &next.tracks)
+                .next()
+                .and_then(|next| next.session),
+        };
+
+        let mut track = track;
+        track.indices = vec![("01".to_string(), at)];
+        track.previous_file_indices.clear();
+        track.session = session;
+        if t > 0 {
+            let previous = &mut self.files[file].tracks[t - 1];
+            let (moved, kept): (Vec<_>, Vec<_>) = previous
+                .indices
+                .drain(..)
+                .partition(|(_, time)| duration_to_frames(time) > frames);
+            previous.indices = kept;
+            track.indices.extend(
+                moved
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (_, time))| (format!("{:02}", i + 2), time)),
+            );
+        }
+        self.files[file].tracks.insert(t, track);
+
+        self.renumber_from(first);
+        let position: usize = self.files[..file].iter().map(|f| f.tracks.len()).sum();
+        Ok(first + (position + t) as u32)
+    }
+
+    /// Splits track `no` at `at`, a position in its FILE, into two tracks and renumbers the
+    /// tracks after them, returning the number of the second part.
+    ///
+    /// The second part has the format, performer, songwriter and flags of track `no`, and its
+    /// indices after `at` as with [`insert_track`](#method.insert_track).
+    ///
+    /// # Failures
+    ///
+    /// Fails if there is no track `no`, if `at` is not between its INDEX 01 and the next track
+    /// of its FILE, or if `at` is the position of an index or inside a pregap.
+    pub fn split_track(&mut self, no: u32, at: Duration) -> Result<u32, CueError> {
+        let (f, t) = self.locate(no)?;
+        let frames = duration_to_frames(&at);
+        let track = &self.files[f].tracks[t];
+        let inside = track
+            .start()
+            .is_some_and(|start| duration_to_frames(&start) < frames)
+            && self.files[f]
+                .tracks
+                .get(t + 1)
+                .and_then(Track::pregap_start)
+                .is_none_or(|end| frames < duration_to_frames(&end));
+        if !inside {
+            return Err(CueError::Parse(format!(
+                "{} is not inside track {}",
+                frames_to_timestamp(frames),
+                track.no
+            )));
+        }
+
+        let mut part = Track::new("", &track.format);
+        part.performer = track.performer.clone();
+        part.songwriter = track.songwriter.clone();
+        part.flags = track.flags.clone();
+        self.insert_track(f, at, part)
+    }
+
+    // Calls `f` with the position of the FILE holding the audio, the track number, INDEX number
+    // and time of each index in `scope`. Indices of a FILE come in order.
+    fn for_each_index<F>(&mut self, scope: Scope, mut f: F) -> Result<(), CueError>
+    where
+        F: FnMut(usize, &str, &str, &mut Duration) -> Result<(), CueError>,
+    {
+        let in_scope = |file: usize| match scope {
+            Scope::Disc => true,
+            Scope::File(i) => file == i,
+        };
+        if let Scope::File(i) = scope {
+            if i >= self.files.len() {
+                return Err(CueError::Parse(format!("no FILE {}", i)));
+            }
+        }
+
+        for (i, file) in self.files.iter_mut().enumerate() {
+            for track in &mut file.tracks {
+                if in_scope(i) {
+                    for (index, time) in &mut track.indices {
+                        f(i, &track.no, index, time)?;
+                    }
+                }
+                // Indices before a FILE are in the previous one
+                if i > 0 && in_scope(i - 1) {
+                    for (index, time) in &mut track.previous_file_indices {
+                        f(i - 1, &track.no, index, time)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves every index in `scope` by `offset`, eg. after the audio was re-ripped with another
+    /// read offset or had leading silence trimmed. PREGAP and POSTGAP are left as they are.
+    ///
+    /// # Failures
+    ///
+    /// Fails, leaving the sheet unchanged, if there is no FILE for `scope`, if `offset` has a
+    /// sample rate of 0, if an index would move out of range, or with
+    /// [`Underflow::Fail`](enum.Underflow.html#variant.Fail) if an index would move before the
+    /// start of its FILE. With [`Underflow::Clamp`](enum.Underflow.html#variant.Clamp), fails if
+    /// two indices of a FILE would end up together at its start.
+    pub fn shift(
+        &mut self,
+        offset: Offset,
+        scope: Scope,
+        underflow: Underflow,
+    ) -> Result<(), CueError> {
+        let offset = offset.frames()?;
+        // The first index of each FILE moved to its start, and whether it was clamped
+        let mut at_start: Vec<Option<(String, bool)>> = vec![None; self.files.len()];
+        let mut shifted = self.clone();
+        shifted.for_each_index(scope, |file, no, index, time| {
+            let frames = i64::try_from(duration_to_frames(time))
+                .ok()
+                .and_then(|frames| frames.checked_add(offset))
+                .ok_or_else(|| {
+                    CueError::Parse(format!(
+                        "track {} INDEX {} would move out of range",
+                        no, index
+                    ))
+                })?;
+            if frames < 0 && underflow == Underflow::Fail {
+                return Err(CueError::Parse(format!(
+                    "track {} INDEX {} would move before the start of its FILE",
+                    no, index
+                )));
+            }
+            if frames <= 0 {
+                let name = format!("track {} INDEX {}", no, index);
+                match at_start[file] {
+                    Some((ref first, clamped)) if clamped || frames < 0 => {
+                        return Err(CueError::Parse(format!(
+                            "{} would be clamped onto {} at the start of its FILE",
+                            name, first
+                        )));
+                    }
+                    Some(_) => {}
+                    None => at_start[file] = Some((name, frames < 0)),
+                }
+            }
+            *time = frames_to_duration(frames.max(0) as u64);
+            Ok(())
+        })?;
+        *self = shifted;
+        Ok(())
+    }
+
+    /// Multiplies every index in `scope`, and the PREGAP and POSTGAP of the tracks in it, by
+    /// `to / from`, rounding to the nearest frame. `from` and `to` are the sample rates or
+    /// speeds of the source the sheet was timed against and of the audio it should describe.
+    ///
+    /// # Failures
+    ///
+    /// Fails, leaving the sheet unchanged, if `from` or `to` is 0, if there is no FILE for
+    /// `scope`, or if a scaled time is out of range.
+    pub fn scale(&mut self, from: u64, to: u64, scope: Scope) -> Result<(), CueError> {
+        if from == 0 || to == 0 {
+            return Err(CueError::Parse(format!("scale from {} to {}", from, to)));
+        }
+        let scale = |time: &Duration| {
+            let frames = duration_to_frames(time);
+            let scaled =
+                (u128::from(frames) * u128::from(to) + u128::from(from / 2)) / u128::from(from);
+            u64::try_from(scaled).map(frames_to_duration).map_err(|_| {
+                CueError::Parse(format!(
+                    "{} is out of range when scaled by {}/{}",
+                    frames_to_timestamp(frames),
+                    to,
+                    from
+                ))
+            })
+        };
+
+        let mut scaled = self.clone();
+        scaled.for_each_index(scope, |_, _, _, time| {
+            *time = scale(time)?;
+            Ok(())
+        })?;
+        for (i, file) in scaled.files.iter_mut().enumerate() {
+            if scope != Scope::Disc && scope != Scope::File(i) {
+                continue;
+            }
+            for track in &mut file.tracks {
+                track.pregap = track.pregap.as_ref().map(scale).transpose()?;
+                track.postgap = track.postgap.as_ref().map(scale).transpose()?;
+            }
+        }
+        *self = scaled;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::CueBuilder;
+    use parser::{parse, parse_from_file};
+    use std::io::Cursor;
+    use writer::to_string;
+
+    fn edited() -> Cue {
+        CueBuilder::new()
+            .file("a.wav", "WAVE", |f| {
+                f.track(1, "AUDIO", |t| t.title("One").index(1, "00:00:00"))
+                    .track(2, "AUDIO", |t| {
+                        t.title("Two")
+                            .performer("Someone")
+                            .postgap("00:02:00")
+                            .index(0, "01:00:00")
+                            .index(1, "01:02:00")
+                            .index(2, "02:00:00")
+                    })
+                    .track(3, "AUDIO", |t| t.title("Three").index(1, "03:00:00"))
+            })
+            .file("b.wav", "WAVE", |f| {
+                f.track(4, "AUDIO", |t| t.title("Four").index(1, "00:00:00"))
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn numbers(cue: &Cue) -> Vec<(String, Option<String>)> {
+        cue.files
+            .iter()
+            .flat_map(|file| &file.tracks)
+            .map(|track| (track.no.clone(), track.title.clone()))
+            .collect()
+    }
+
+    fn reparsed(cue: &Cue) -> Cue {
+        parse(&mut Cursor::new(to_string(cue).into_bytes()), true).unwrap()
+    }
+
+    #[test]
+    fn test_delete_track() {
+        let mut cue = edited();
+        cue.delete_track(2, DeleteMode::MergePrevious).unwrap();
+        assert_eq!(
+            numbers(&cue),
+            vec![
+                ("01".to_string(), Some("One".to_string())),
+                ("02".to_string(), Some("Three".to_string())),
+                ("03".to_string(), Some("Four".to_string())),
+            ]
+        );
+        assert_eq!(cue.files[0].tracks[0].postgap, Some(Duration::new(2, 0)));
+
+        let mut cue = edited();
+        cue.delete_track(2, DeleteMode::MergeNext).unwrap();
+        let track = &cue.files[0].tracks[1];
+        assert_eq!(track.no, "02");
+        assert_eq!(track.title, Some("Three".to_string()));
+        assert_eq!(track.performer, None);
+        assert_eq!(track.index(0), Some(Duration::new(60, 0)));
+        assert_eq!(track.postgap, None);
+
+        let mut cue = edited();
+        cue.delete_track(2, DeleteMode::Pregap).unwrap();
+        let track = &cue.files[0].tracks[1];
+        assert_eq!(track.title, Some("Three".to_string()));
+        assert_eq!(
+            track.indices,
+            vec![
+                ("00".to_string(), Duration::new(60, 0)),
+                ("01".to_string(), Duration::new(180, 0)),
+            ]
+        );
+
+        // Across a FILE boundary, the pregap goes to the end of the previous FILE
+        let mut cue = edited();
+        cue.delete_track(3, DeleteMode::Pregap).unwrap();
+        let track = &cue.files[1].tracks[0];
+        assert_eq!(track.no, "03");
+        assert_eq!(
+            track.previous_file_indices,
+            vec![("00".to_string(), Duration::new(180, 0))]
+        );
+        assert_eq!(reparsed(&cue), cue);
+
+        // The next track's own INDEX 00 gives way to the carried-over one
+        let mut cue = CueBuilder::new()
+            .file("a.wav", "WAVE", |f| {
+                f.track(1, "AUDIO", |t| t.index(1, "00:00:00"))
+                    .track(2, "AUDIO", |t| t.index(1, "01:00:00"))
+            })
+            .file("b.wav", "WAVE", |f| {
+                f.track(3, "AUDIO", |t| t.index(0, "00:00:00").index(1, "00:02:00"))
+            })
+            .build()
+            .unwrap();
+        cue.delete_track(2, DeleteMode::Pregap).unwrap();
+        let track = &cue.files[1].tracks[0];
+        assert_eq!(track.indices, vec![("01".to_string(), Duration::new(2, 0))]);
+        assert_eq!(
+            track.previous_file_indices,
+            vec![("00".to_string(), Duration::new(60, 0))]
+        );
+        assert_eq!(to_string(&cue).matches("INDEX 00").count(), 1);
+        assert_eq!(reparsed(&cue), cue);
+
+        // A FILE left without tracks continues the previous track
+        let mut cue = edited();
+        cue.delete_track(4, DeleteMode::MergePrevious).unwrap();
+        assert!(cue.files[1].tracks.is_empty());
+        assert_eq!(reparsed(&cue), cue);
+
+        let mut cue = edited();
+        assert!(cue.delete_track(1, DeleteMode::MergePrevious).is_err());
+        assert!(cue.delete_track(4, DeleteMode::MergeNext).is_err());
+        assert!(cue.delete_track(5, DeleteMode::MergeNext).is_err());
+        assert_eq!(cue, edited());
+        cue.delete_track(1, DeleteMode::MergeNext).unwrap();
+        assert_eq!(cue.files[0].tracks[0].no, "01");
+        assert_eq!(cue.files[0].tracks[0].title, Some("Two".to_string()));
+    }
+
+    #[test]
+    fn test_merge_tracks() {
+        let mut cue = edited();
+        cue.merge_tracks(3).unwrap();
+        assert_eq!(cue.files[0].tracks.len(), 3);
+        assert!(cue.files[1].tracks.is_empty());
+        assert!(cue.merge_tracks(3).is_err());
+
+        let mut cue = parse_from_file("test/fixtures/sessions.cue", true).unwrap();
+        assert!(cue.merge_tracks(2).is_err());
+        cue.merge_tracks(1).unwrap();
+        assert_eq!(cue.files[2].tracks[0].no, "02");
+        assert!(cue.validate_sessions().is_ok());
+    }
+
+    #[test]
+    fn test_insert_and_split_track() {
+        let mut cue = edited();
+        let mut track = Track::new("", "AUDIO");
+        track.title = Some("New".to_string());
+        assert_eq!(cue.insert_track(0, Duration::new(90, 0), track).unwrap(), 3);
+        assert_eq!(
+            numbers(&cue)
+                .into_iter()
+                .map(|(no, _)| no)
+                .collect::<Vec<_>>(),
+            vec!["01", "02", "03", "04", "05"]
+        );
+        // INDEX 02 of the track it was cut from moves to the new track
+        assert_eq!(
+            cue.files[0].tracks[2].indices,
+            vec![
+                ("01".to_string(), Duration::new(90, 0)),
+                ("02".to_string(), Duration::new(120, 0)),
+            ]
+        );
+        assert_eq!(cue.files[0].tracks[1].indices.len(), 2);
+
+        let mut cue = edited();
+        let second = cue.split_track(4, Duration::new(30, 0)).unwrap();
+        assert_eq!(second, 5);
+        assert_eq!(cue.files[1].tracks[1].title, None);
+        assert_eq!(reparsed(&cue), cue);
+        let second = cue.split_track(1, Duration::new(10, 0)).unwrap();
+        assert_eq!(second, 2);
+        assert_eq!(cue.files[0].tracks[2].title, Some("Two".to_string()));
+        assert_eq!(cue.files[0].tracks[2].no, "03");
+
+        let mut cue = edited();
+        let track = || Track::new("", "AUDIO");
+        assert!(cue.insert_track(0, Duration::new(180, 0), track()).is_err());
+        assert!(cue.insert_track(0, Duration::new(61, 0), track()).is_err());
+        assert!(cue.insert_track(2, Duration::new(0, 0), track()).is_err());
+        assert!(cue.split_track(1, Duration::new(70, 0)).is_err());
+        assert!(cue.split_track(2, Duration::new(61, 0)).is_err());
+        assert_eq!(cue, edited());
+
+        let mut cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert!(cue.split_track(1, Duration::new(256, 0)).is_err());
+        assert_eq!(cue.split_track(1, Duration::new(100, 0)).unwrap(), 2);
+        assert_eq!(cue.files[1].tracks[0].no, "03");
+    }
+
+    fn starts(cue: &Cue) -> Vec<u64> {
+        cue.files
+            .iter()
+            .flat_map(|file| &file.tracks)
+            .flat_map(|track| track.previous_file_indices.iter().chain(&track.indices))
+            .map(|(_, time)| duration_to_frames(time))
+            .collect()
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert_eq!(starts(&cue), vec![0, 19177, 0]);
+
+        cue.shift(Offset::Frames(75), Scope::Disc, Underflow::Fail)
+            .unwrap();
+        assert_eq!(starts(&cue), vec![75, 19252, 75]);
+
+        // The pregap at the end of the first FILE moves with it
+        cue.shift(
+            Offset::Samples(-588 * 10, 44100),
+            Scope::File(0),
+            Underflow::Fail,
+        )
+        .unwrap();
+        assert_eq!(starts(&cue), vec![65, 19242, 75]);
+
+        let before = cue.clone();
+        assert!(cue
+            .shift(Offset::Frames(-70), Scope::Disc, Underflow::Fail)
+            .is_err());
+        assert!(cue
+            .shift(Offset::Frames(1), Scope::File(2), Underflow::Fail)
+            .is_err());
+        assert!(cue
+            .shift(Offset::Samples(1, 0), Scope::Disc, Underflow::Fail)
+            .is_err());
+        assert!(cue
+            .shift(Offset::Frames(i64::MAX), Scope::Disc, Underflow::Fail)
+            .is_err());
+        assert_eq!(cue, before);
+
+        cue.shift(Offset::Frames(-70), Scope::Disc, Underflow::Clamp)
+            .unwrap();
+        assert_eq!(starts(&cue), vec![0, 19172, 5]);
+
+        // Both tracks of the first FILE would start at 00:00:00
+        let before = cue.clone();
+        let err = cue
+            .shift(Offset::Frames(-19200), Scope::File(0), Underflow::Clamp)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error: track 02 INDEX 00 would be clamped onto track 01 INDEX 01 at the start \
+             of its FILE"
+        );
+        assert_eq!(cue, before);
+    }
+
+    #[test]
+    fn test_offset() {
+        assert_eq!(
+            Offset::from_timestamp("00:01:05").unwrap(),
+            Offset::Frames(80)
+        );
+        assert_eq!(
+            Offset::from_timestamp("-00:01:05").unwrap(),
+            Offset::Frames(-80)
+        );
+        assert!(Offset::from_timestamp("-x").is_err());
+        assert_eq!(Offset::Samples(293, 44100).frames().unwrap(), 0);
+        assert_eq!(Offset::Samples(-294, 44100).frames().unwrap(), -1);
+        assert_eq!(Offset::Samples(48000, 48000).frames().unwrap(), 75);
+        assert!(Offset::Samples(i64::MIN, 44100).frames().is_ok());
+        assert!(Offset::Samples(i64::MIN, 1).frames().is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut cue = edited();
+        cue.scale(48000, 44100, Scope::File(0)).unwrap();
+        assert_eq!(starts(&cue), vec![0, 4134, 4272, 8269, 12403, 0]);
+        assert_eq!(
+            cue.files[0].tracks[1]
+                .postgap
+                .map(|gap| duration_to_frames(&gap)),
+            Some(138)
+        );
+
+        let mut cue = edited();
+        cue.scale(1, 2, Scope::Disc).unwrap();
+        assert_eq!(starts(&cue), vec![0, 9000, 9300, 18000, 27000, 0]);
+        let before = cue.clone();
+        assert!(cue.scale(0, 1, Scope::Disc).is_err());
+        assert!(cue.scale(1, 0, Scope::Disc).is_err());
+        assert!(cue.scale(1, 1, Scope::File(5)).is_err());
+        assert!(cue.scale(1, u64::MAX, Scope::Disc).is_err());
+        assert_eq!(cue, before);
     }
 }
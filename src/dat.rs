@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use cue::Cue;
+use errors::CueError;
+use hash::{hash_reader, FileHashes};
+use xml;
+
+/// A `<rom>` entry of a DAT file. Digests are lowercase hexadecimal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rom {
+    /// File name
+    pub name: String,
+    /// Size in bytes
+    pub size: Option<u64>,
+    /// CRC-32
+    pub crc32: Option<String>,
+    /// MD5
+    pub md5: Option<String>,
+    /// SHA-1
+    pub sha1: Option<String>,
+}
+
+impl Rom {
+    /// Returns the names of the fields of `hashes` that differ from this entry. Fields missing
+    /// from the entry are not compared.
+    pub fn mismatches(&self, hashes: &FileHashes) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.size.is_some_and(|size| size != hashes.size) {
+            fields.push("size");
+        }
+        let digests = [
+            ("crc", &self.crc32, &hashes.crc32),
+            ("md5", &self.md5, &hashes.md5),
+            ("sha1", &self.sha1, &hashes.sha1),
+        ];
+        for (field, expected, actual) in digests.iter() {
+            if expected.as_ref().is_some_and(|e| e != *actual) {
+                fields.push(field);
+            }
+        }
+        fields
+    }
+}
+
+/// A `<game>` (or `<machine>`) entry of a DAT file: one disc and its files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    /// Name of the entry
+    pub name: String,
+    /// Files of the disc, including its CUE sheet
+    pub roms: Vec<Rom>,
+}
+
+impl Game {
+    /// Returns the entry of the file named `name`, ignoring case.
+    pub fn rom(&self, name: &str) -> Option<&Rom> {
+        self.roms
+            .iter()
+            .find(|rom| rom.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Parses the games of a Logiqx XML DAT file, as published by redump and No-Intro.
+///
+/// # Example
+///
+/// ```
+/// use rcue::dat::parse_dat;
+///
+/// let dat = r#"<?xml version="1.0"?>
+/// <datafile>
+///   <game name="Disc">
+///     <rom name="Disc.cue" size="3" crc="352441c2"/>
+///   </game>
+/// </datafile>"#;
+/// let games = parse_dat(dat).unwrap();
+/// assert_eq!(games[0].roms[0].crc32, Some("352441c2".to_string()));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the XML is malformed, the root is not `<datafile>`, or a size is not a number.
+pub fn parse_dat(text: &str) -> Result<Vec<Game>, CueError> {
+    let root = xml::parse(text)?;
+    if root.name != "datafile" {
+        return Err(CueError::Parse(format!(
+            "expected <datafile>, found <{}>",
+            root.name
+        )));
+    }
+
+    let mut games = Vec::new();
+    for game in root
+        .children
+        .iter()
+        .filter(|c| c.name == "game" || c.name == "machine")
+    {
+        let mut roms = Vec::new();
+        for rom in game.children_named("rom") {
+            let digest = |name: &str| rom.attribute(name).map(|d| d.trim().to_lowercase());
+            let size = match rom.attribute("size") {
+                Some(size) => Some(
+                    size.trim()
+                        .parse()
+                        .map_err(|_| CueError::Parse(format!("bad size {} in DAT", size)))?,
+                ),
+                None => None,
+            };
+            roms.push(Rom {
+                name: rom.attribute("name").unwrap_or_default().to_string(),
+                size,
+                crc32: digest("crc"),
+                md5: digest("md5"),
+                sha1: digest("sha1"),
+            });
+        }
+        games.push(Game {
+            name: game.attribute("name").unwrap_or_default().to_string(),
+            roms,
+        });
+    }
+
+    Ok(games)
+}
+
+/// Outcome of checking one file against a DAT.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RomStatus {
+    /// The file matches its DAT entry
+    Match,
+    /// The file differs from its DAT entry in the listed fields (`size`, `crc`, `md5`, `sha1`)
+    Mismatch(Vec<&'static str>),
+    /// The DAT lists the file, but it could not be found
+    Missing,
+    /// The DAT does not list the file
+    NotInDat,
+}
+
+/// Result of checking the FILE of one track.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackVerification {
+    /// Track number as written in the sheet
+    pub track: String,
+    /// FILE the track is in, as written in the sheet
+    pub file: String,
+    /// Outcome for the FILE
+    pub status: RomStatus,
+}
+
+/// Result of [`verify`](fn.verify.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    /// Name of the DAT entry the sheet was checked against
+    pub game: String,
+    /// Outcome for each track, in sheet order
+    pub tracks: Vec<TrackVerification>,
+    /// Outcome for the sheet text
+    pub cue: RomStatus,
+}
+
+impl Verification {
+    /// Returns true if every track and the sheet text match the DAT.
+    pub fn is_verified(&self) -> bool {
+        self.cue == RomStatus::Match
+            && self
+                .tracks
+                .iter()
+                .all(|track| track.status == RomStatus::Match)
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Verifies a sheet and its files against the `games` of a DAT.
+///
+/// The DAT entry listing the most FILEs of `cue` (by file name) is used. Each FILE is resolved
+/// relative to `cue_dir` and hashed once; every track reports the outcome of its FILE. The sheet
+/// text `cue_text` is checked against the entry's `.cue` file. Since redump sheets use CRLF line
+/// endings, text that does not match as given is also tried with CRLF line endings.
+///
+/// # Failures
+///
+/// Fails if no DAT entry lists any FILE of the sheet, or a FILE can not be read for a reason
+/// other than not existing.
+pub fn verify(
+    cue: &Cue,
+    cue_text: &str,
+    cue_dir: &Path,
+    games: &[Game],
+) -> Result<Verification, CueError> {
+    let names: Vec<&str> = cue.files.iter().map(|f| file_name(&f.file)).collect();
+    let game = games
+        .iter()
+        .map(|game| {
+            let listed = names.iter().filter(|n| game.rom(n).is_some()).count();
+            (listed, game)
+        })
+        .filter(|(listed, _)| *listed > 0)
+        .fold(None, |best: Option<(usize, &Game)>, candidate| match best {
+            Some(best) if best.0 >= candidate.0 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(_, game)| game)
+        .ok_or_else(|| CueError::Parse("no DAT entry lists the sheet's files".to_string()))?;
+
+    let mut statuses: HashMap<&str, RomStatus> = HashMap::new();
+    let mut tracks = Vec::new();
+    for file in &cue.files {
+        if !statuses.contains_key(file.file.as_str()) {
+            let status = match game.rom(file_name(&file.file)) {
+                None => RomStatus::NotInDat,
+                Some(rom) => match File::open(cue_dir.join(file.file.replace('\\', "/"))) {
+                    Ok(mut f) => status(rom, &hash_reader(&mut f)?),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => RomStatus::Missing,
+                    Err(err) => return Err(err.into()),
+                },
+            };
+            statuses.insert(&file.file, status);
+        }
+        for track in &file.tracks {
+            tracks.push(TrackVerification {
+                track: track.no.clone(),
+                file: file.file.clone(),
+                status: statuses[file.file.as_str()].clone(),
+            });
+        }
+    }
+
+    let sheet = game
+        .roms
+        .iter()
+        .find(|rom| rom.name.to_lowercase().ends_with(".cue"));
+    let cue_status = match sheet {
+        None => RomStatus::NotInDat,
+        Some(rom) => {
+            let as_given = status(rom, &hash_reader(&mut cue_text.as_bytes())?);
+            if as_given == RomStatus::Match {
+                as_given
+            } else {
+                let crlf = cue_text.replace("\r\n", "\n").replace('\n', "\r\n");
+                match status(rom, &hash_reader(&mut crlf.as_bytes())?) {
+                    RomStatus::Match => RomStatus::Match,
+                    _ => as_given,
+                }
+            }
+        }
+    };
+
+    Ok(Verification {
+        game: game.name.clone(),
+        tracks,
+        cue: cue_status,
+    })
+}
+
+fn status(rom: &Rom, hashes: &FileHashes) -> RomStatus {
+    let mismatches = rom.mismatches(hashes);
+    if mismatches.is_empty() {
+        RomStatus::Match
+    } else {
+        RomStatus::Mismatch(mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse;
+    use std::fs;
+    use std::io::Cursor;
+    use test_util::temp_dir;
+
+    const SHEET: &str = "FILE \"Disc (Track 1).bin\" BINARY\r\n  TRACK 01 MODE1/2352\r\n    INDEX 01 00:00:00\r\nFILE \"Disc (Track 2).bin\" BINARY\r\n  TRACK 02 AUDIO\r\n    INDEX 00 00:00:00\r\n    INDEX 01 00:02:00\r\nFILE \"Disc (Track 3).bin\" BINARY\r\n  TRACK 03 AUDIO\r\n    INDEX 01 00:00:00\r\n";
+
+    fn rom(name: &str, data: &[u8]) -> String {
+        let hashes = hash_reader(&mut &data[..]).unwrap();
+        format!(
+            "<rom name=\"{}\" size=\"{}\" crc=\"{}\" md5=\"{}\" sha1=\"{}\"/>",
+            name,
+            hashes.size,
+            hashes.crc32.to_uppercase(),
+            hashes.md5,
+            hashes.sha1
+        )
+    }
+
+    fn dat() -> String {
+        format!(
+            "<?xml version=\"1.0\"?>
+<!DOCTYPE datafile PUBLIC \"-//Logiqx//DTD ROM Management Datafile//EN\" \"http://www.logiqx.com/Dats/datafile.dtd\">
+<datafile>
+  <header><name>Test</name></header>
+  <game name=\"Other\"><rom name=\"Other.bin\" size=\"1\" crc=\"00000000\"/></game>
+  <game name=\"Disc\">
+    <category>Games</category>
+    {}
+    {}
+    {}
+    {}
+  </game>
+</datafile>",
+            rom("Disc.cue", SHEET.as_bytes()),
+            rom("Disc (Track 1).bin", b"data track"),
+            rom("Disc (Track 2).bin", b"audio track"),
+            rom("Disc (Track 3).bin", b"missing"),
+        )
+    }
+
+    #[test]
+    fn test_parse_dat() {
+        let games = parse_dat(&dat()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].name, "Disc");
+        assert_eq!(games[1].roms.len(), 4);
+        assert_eq!(
+            games[1].rom("disc.CUE").unwrap().size,
+            Some(SHEET.len() as u64)
+        );
+        assert!(games[1].roms[1]
+            .crc32
+            .as_ref()
+            .unwrap()
+            .chars()
+            .all(|c| !c.is_ascii_uppercase()));
+
+        assert!(parse_dat("<other/>").is_err());
+        assert!(parse_dat("<datafile><game><rom size=\"x\"/></game></datafile>").is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = temp_dir("dat_verify");
+        fs::write(dir.join("Disc (Track 1).bin"), b"data track").unwrap();
+        fs::write(dir.join("Disc (Track 2).bin"), b"audio track!").unwrap();
+
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let games = parse_dat(&dat()).unwrap();
+        let verification = verify(&cue, SHEET, &dir, &games).unwrap();
+
+        assert_eq!(verification.game, "Disc");
+        assert_eq!(verification.cue, RomStatus::Match);
+        let statuses: Vec<&RomStatus> = verification.tracks.iter().map(|t| &t.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                &RomStatus::Match,
+                &RomStatus::Mismatch(vec!["size", "crc", "md5", "sha1"]),
+                &RomStatus::Missing
+            ]
+        );
+        assert!(!verification.is_verified());
+
+        // LF line endings still match the CRLF sheet in the DAT
+        let lf = SHEET.replace("\r\n", "\n");
+        assert_eq!(
+            verify(&cue, &lf, &dir, &games).unwrap().cue,
+            RomStatus::Match
+        );
+        let edited = SHEET.replace("AUDIO", "audio");
+        assert_eq!(
+            verify(&cue, &edited, &dir, &games).unwrap().cue,
+            RomStatus::Mismatch(vec!["crc", "md5", "sha1"])
+        );
+    }
+
+    #[test]
+    fn test_verify_all_match_and_unlisted() {
+        let dir = temp_dir("dat_all_match");
+        fs::write(dir.join("Disc (Track 1).bin"), b"data track").unwrap();
+        fs::write(dir.join("Disc (Track 2).bin"), b"audio track").unwrap();
+        fs::write(dir.join("Disc (Track 3).bin"), b"missing").unwrap();
+
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let games = parse_dat(&dat()).unwrap();
+        assert!(verify(&cue, SHEET, &dir, &games).unwrap().is_verified());
+
+        let renamed = SHEET.replace("Track 3", "Track 4");
+        let cue = parse(&mut Cursor::new(renamed.as_str()), true).unwrap();
+        let verification = verify(&cue, &renamed, &dir, &games).unwrap();
+        assert_eq!(verification.tracks[2].status, RomStatus::NotInDat);
+
+        let unrelated = parse(
+            &mut Cursor::new("FILE \"x.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n"),
+            true,
+        )
+        .unwrap();
+        assert!(verify(&unrelated, "", &dir, &games).is_err());
+    }
+}
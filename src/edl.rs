@@ -0,0 +1,303 @@
+use chapters::{chapters, to_cue, ChapterMark, ExportOptions, ImportOptions};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use util::frames_to_nanos;
+
+/// Formats a number of timecode frames at `fps` as a non-drop-frame timecode, `HH:MM:SS:FF`.
+///
+/// # Example
+///
+/// ```
+/// use rcue::edl::timecode;
+///
+/// assert_eq!(timecode(7731, 30), "00:04:17:21");
+/// ```
+pub fn timecode(frames: u64, fps: u32) -> String {
+    let fps = u64::from(fps);
+    let seconds = frames / fps;
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        frames % fps
+    )
+}
+
+/// Parses a non-drop-frame timecode, `HH:MM:SS:FF`, at `fps` into a number of timecode frames.
+///
+/// # Example
+///
+/// ```
+/// use rcue::edl::parse_timecode;
+///
+/// assert_eq!(parse_timecode("00:04:17:21", 30), Some(7731));
+/// assert_eq!(parse_timecode("00:04:17:30", 30), None);
+/// ```
+pub fn parse_timecode(s: &str, fps: u32) -> Option<u64> {
+    let parts: Vec<u64> = s
+        .split(':')
+        .map(|part| {
+            if part.len() == 2 && part.chars().all(|c| c.is_ascii_digit()) {
+                part.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<u64>>>()?;
+    match parts[..] {
+        [hours, minutes, seconds, frames]
+            if minutes < 60 && seconds < 60 && frames < u64::from(fps) =>
+        {
+            Some(((hours * 60 + minutes) * 60 + seconds) * u64::from(fps) + frames)
+        }
+        _ => None,
+    }
+}
+
+fn check_fps(fps: u32) -> Result<(), CueError> {
+    if fps == 0 {
+        Err(CueError::Parse("EDL frame rate must not be 0".to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Exports a single-file `cue` as a CMX3600 EDL, for an audio file `length` CD frames long.
+///
+/// Each track becomes a cut event on reel `AX`, audio channels `AA`, with source and record
+/// timecodes equal to its position in the file (see
+/// [`chapters`](../chapters/fn.chapters.html) for the spans). CD frames are converted to
+/// non-drop-frame timecode at `fps` with the
+/// [`rounding`](../chapters/struct.ExportOptions.html#structfield.rounding) option. Events carry
+/// a `* FROM CLIP NAME:` with the FILE and a `* COMMENT:` built from TITLE and PERFORMER
+/// according to the [`labels`](../chapters/struct.ExportOptions.html#structfield.labels) option.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ExportOptions;
+/// use rcue::edl::to_edl;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let edl = to_edl(&cue, 30000, 30, &ExportOptions::default()).unwrap();
+/// assert!(edl.starts_with("TITLE: Loveless\nFCM: NON-DROP FRAME\n"));
+/// assert!(edl.contains("\n* COMMENT: Loomer\n"));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE, or if `fps` is 0.
+pub fn to_edl(
+    cue: &Cue,
+    length: u64,
+    fps: u32,
+    options: &ExportOptions,
+) -> Result<String, CueError> {
+    check_fps(fps)?;
+    let tc = |frames: u64| {
+        let frames = options.rounding.div(
+            u128::from(frames_to_nanos(frames)) * u128::from(fps),
+            1_000_000_000,
+        );
+        timecode(frames as u64, fps)
+    };
+
+    let chapters = chapters(cue, length)?;
+    let file = &cue.files[0].file;
+    let title = cue.title.as_deref().unwrap_or(file);
+
+    let mut out = format!(
+        "TITLE: {}\nFCM: NON-DROP FRAME\n",
+        title.replace(['\r', '\n'], " ")
+    );
+    for (i, chapter) in chapters.iter().enumerate() {
+        let (start, end) = (tc(chapter.start), tc(chapter.end));
+        out.push_str(&format!(
+            "\n{:03}  AX       AA     C        {} {} {} {}\n",
+            i + 1,
+            start,
+            end,
+            start,
+            end
+        ));
+        out.push_str(&format!("* FROM CLIP NAME: {}\n", file));
+        if let Some(label) = options
+            .labels
+            .join(chapter.title.as_deref(), chapter.performer.as_deref())
+        {
+            out.push_str(&format!(
+                "* COMMENT: {}\n",
+                label.replace(['\r', '\n'], " ")
+            ));
+        }
+    }
+    Ok(out)
+}
+
+/// Imports a CMX3600 EDL as a single-file sheet for the audio file `file`.
+///
+/// Every event starts a track at its source in timecode (the position in the source clip), read
+/// at `fps` and rounded to CD frames as described in
+/// [`chapters::to_cue`](../chapters/fn.to_cue.html). The event's `* COMMENT:` or `* LOC:` marker
+/// name maps to TITLE and PERFORMER according to the
+/// [`labels`](../chapters/struct.ImportOptions.html#structfield.labels) option, and the EDL
+/// `TITLE:` becomes the disc TITLE.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ImportOptions;
+/// use rcue::edl::from_edl;
+///
+/// let edl = "TITLE: Mix\nFCM: NON-DROP FRAME\n\n\
+///            001  AX       AA     C        00:00:00:00 00:01:30:00 01:00:00:00 01:01:30:00\n\
+///            * COMMENT: Intro\n\n\
+///            002  AX       AA     C        00:01:30:00 00:03:00:00 01:01:30:00 01:03:00:00\n";
+/// let (cue, _) = from_edl(edl, "mix.wav", 25, &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.title, Some("Mix".to_string()));
+/// assert_eq!(cue.files[0].tracks[0].title, Some("Intro".to_string()));
+/// assert_eq!(cue.files[0].tracks[1].start().unwrap().as_secs(), 90);
+/// ```
+///
+/// # Failures
+///
+/// Fails if `fps` is 0, the EDL uses drop-frame timecode, an event has invalid timecodes, or
+/// there are more than 99 events.
+pub fn from_edl(
+    text: &str,
+    file: &str,
+    fps: u32,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    check_fps(fps)?;
+
+    let mut title = None;
+    let mut marks: Vec<ChapterMark> = Vec::new();
+
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("TITLE:") {
+            title = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("FCM:") {
+            if value.trim().eq_ignore_ascii_case("DROP FRAME") {
+                return Err(CueError::Parse(
+                    "drop-frame EDLs are not supported".to_string(),
+                ));
+            }
+        } else if let Some(comment) = line.strip_prefix('*') {
+            let comment = comment.trim();
+            let name = if let Some(name) = comment.strip_prefix("COMMENT:") {
+                name.trim().to_string()
+            } else if let Some(loc) = comment.strip_prefix("LOC:") {
+                // `* LOC: <timecode> <colour> <name>`
+                loc.split_whitespace().skip(2).collect::<Vec<_>>().join(" ")
+            } else {
+                continue;
+            };
+            if let Some(mark) = marks.last_mut() {
+                if mark.title.is_none() && !name.is_empty() {
+                    mark.title = Some(name);
+                }
+            }
+        } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let timecodes: Vec<&str> = line
+                .split_whitespace()
+                .filter(|field| field.contains(':') || field.contains(';'))
+                .collect();
+            if timecodes.len() < 4 {
+                return Err(CueError::Parse(format!(
+                    "line {}: event has fewer than 4 timecodes",
+                    i + 1
+                )));
+            }
+            let source_in = timecodes[timecodes.len() - 4];
+            let frames = parse_timecode(source_in, fps).ok_or_else(|| {
+                CueError::Parse(format!("line {}: bad timecode {}", i + 1, source_in))
+            })?;
+            marks.push(ChapterMark {
+                start: (u128::from(frames) * 1_000_000_000 / u128::from(fps)) as u64,
+                title: None,
+                performer: None,
+            });
+        }
+    }
+
+    let (mut cue, diagnostics) = to_cue(&marks, file, options)?;
+    cue.title = title;
+    Ok((cue, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chapters::{LabelMapping, Rounding};
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_good_cue() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let options = ExportOptions {
+            rounding: Rounding::Down,
+            labels: LabelMapping::PerformerTitle,
+        };
+        let expected = "TITLE: Loveless
+FCM: NON-DROP FRAME
+
+001  AX       AA     C        00:00:00:00 00:04:17:20 00:00:00:00 00:04:17:20
+* FROM CLIP NAME: My Bloody Valentine - Loveless.wav
+* COMMENT: My Bloody Valentine - Only Shallow
+
+002  AX       AA     C        00:04:17:20 00:06:40:00 00:04:17:20 00:06:40:00
+* FROM CLIP NAME: My Bloody Valentine - Loveless.wav
+* COMMENT: My Bloody Valentine - Loomer
+";
+        assert_eq!(to_edl(&cue, 30000, 30, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_round_trip_at_75_fps() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let edl = to_edl(&cue, 30000, 75, &ExportOptions::default()).unwrap();
+        let (imported, diagnostics) =
+            from_edl(&edl, "a.wav", 75, &ImportOptions::default()).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(imported.title, cue.title);
+        let titles: Vec<Option<String>> = chapters(&imported, 30000)
+            .unwrap()
+            .into_iter()
+            .map(|chapter| chapter.title)
+            .collect();
+        assert_eq!(
+            titles,
+            vec![Some("Only Shallow".to_string()), Some("Loomer".to_string())]
+        );
+        assert_eq!(
+            chapters(&imported, 30000).unwrap()[1].start,
+            chapters(&cue, 30000).unwrap()[1].start
+        );
+    }
+
+    #[test]
+    fn test_loc_and_dissolve() {
+        let edl = "001  AX  AA  C        00:00:00:00 00:00:10:00 01:00:00:00 01:00:10:00
+* LOC: 01:00:00:00 RED    Side A
+002  AX  AA  D    012 00:00:10:12 00:00:20:00 01:00:10:00 01:00:20:00
+";
+        let (cue, diagnostics) = from_edl(edl, "a.wav", 25, &ImportOptions::default()).unwrap();
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].title, Some("Side A".to_string()));
+        // 10s + 12/25 s = 10.48s = 786 CD frames
+        assert_eq!(chapters(&cue, 1000).unwrap()[1].start, 786);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let options = ImportOptions::default();
+        assert!(from_edl("FCM: DROP FRAME\n", "a.wav", 30, &options).is_err());
+        assert!(from_edl("001 AX AA C 00:00:00:00\n", "a.wav", 30, &options).is_err());
+        assert!(from_edl("", "a.wav", 0, &options).is_err());
+    }
+}
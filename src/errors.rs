@@ -52,3 +52,31 @@ impl From<std::num::ParseIntError> for CueError {
         CueError::Parse(format!("invalid timestamp: {}", err))
     }
 }
+
+/// A non-fatal problem found while converting to or from another format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Number of the track the problem concerns, if any
+    pub track: Option<u32>,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic about `track`, or the whole sheet if `None`.
+    pub fn new(track: Option<u32>, message: &str) -> Self {
+        Diagnostic {
+            track,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.track {
+            Some(track) => write!(f, "track {:02}: {}", track, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use std::convert::TryFrom;
+
+use chapters::{chapters, comment, to_cue, ChapterMark, ImportOptions};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+
+/// Escapes a metadata value for an FFMETADATA1 file.
+///
+/// `=`, `;`, `#`, `\` and newlines are escaped with a backslash.
+///
+/// # Example
+///
+/// ```
+/// use rcue::ffmetadata::escape;
+///
+/// assert_eq!(escape("a=b;c#d\\e\nf"), "a\\=b\\;c\\#d\\\\e\\\nf");
+/// ```
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if let '=' | ';' | '#' | '\\' | '\n' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Exports a single-file `cue` as an FFMETADATA1 file with one `[CHAPTER]` per track, for an
+/// audio file `length` CD frames long.
+///
+/// Chapters use `TIMEBASE=1/75`, so START and END are exact CD frames (see
+/// [`chapters`](../chapters/fn.chapters.html) for the spans). Chapter `title` and `artist` come
+/// from the track TITLE and PERFORMER. Global `title` and `album` come from the disc TITLE,
+/// `artist` from PERFORMER, `composer` from SONGWRITER, and `date`, `genre` and `comment` from
+/// the matching `REM` comments.
+///
+/// # Example
+///
+/// ```
+/// use rcue::ffmetadata::to_ffmetadata;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let metadata = to_ffmetadata(&cue, 30000).unwrap();
+/// assert!(metadata.starts_with(";FFMETADATA1\ntitle=Loveless\n"));
+/// assert!(metadata.ends_with("START=19327\nEND=30000\ntitle=Loomer\nartist=My Bloody Valentine\n"));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE.
+pub fn to_ffmetadata(cue: &Cue, length: u64) -> Result<String, CueError> {
+    fn field(out: &mut String, key: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            out.push_str(&format!("{}={}\n", key, escape(value)));
+        }
+    }
+
+    let mut out = String::from(";FFMETADATA1\n");
+
+    let title = cue.title.as_deref();
+    field(&mut out, "title", title);
+    field(&mut out, "album", title);
+    field(&mut out, "artist", cue.performer.as_deref());
+    field(&mut out, "composer", cue.songwriter.as_deref());
+    field(&mut out, "date", comment(cue, "DATE"));
+    field(&mut out, "genre", comment(cue, "GENRE"));
+    field(&mut out, "comment", comment(cue, "COMMENT"));
+
+    for chapter in chapters(cue, length)? {
+        out.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/75\nSTART={}\nEND={}\n",
+            chapter.start, chapter.end
+        ));
+        field(&mut out, "title", chapter.title.as_deref());
+        field(&mut out, "artist", chapter.performer.as_deref());
+    }
+
+    Ok(out)
+}
+
+// Splits FFMETADATA1 text into lines of a key and, if the line has an unescaped `=`, a value.
+// Escapes are resolved and comment lines are dropped.
+fn metadata_lines(text: &str) -> Vec<(String, Option<String>)> {
+    let mut lines = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while chars.peek().is_some() {
+        if let Some(';') | Some('#') = chars.peek() {
+            chars.by_ref().find(|c| *c == '\n');
+            continue;
+        }
+
+        let mut key = String::new();
+        let mut value: Option<String> = None;
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '\\' => match chars.next() {
+                    Some(escaped) => escaped,
+                    None => break,
+                },
+                '\n' => break,
+                '\r' if chars.peek() == Some(&'\n') => continue,
+                '=' if value.is_none() => {
+                    value = Some(String::new());
+                    continue;
+                }
+                c => c,
+            };
+            match value {
+                Some(ref mut value) => value.push(c),
+                None => key.push(c),
+            }
+        }
+
+        if !key.is_empty() || value.is_some() {
+            lines.push((key, value));
+        }
+    }
+
+    lines
+}
+
+fn parse_timebase(value: &str) -> Option<(u128, u128)> {
+    let mut parts = value.trim().splitn(2, '/');
+    let num = parts.next()?.trim().parse().ok()?;
+    let den = parts.next()?.trim().parse().ok()?;
+    if num == 0 || den == 0 {
+        None
+    } else {
+        Some((num, den))
+    }
+}
+
+/// Imports the chapters of an FFMETADATA1 file as a single-file sheet for the media file `file`.
+///
+/// Chapter START times are converted with their `TIMEBASE` (nanoseconds if it is missing) and
+/// rounded to CD frames as described in [`chapters::to_cue`](../chapters/fn.to_cue.html), and
+/// chapter `title` and `artist` become TITLE and PERFORMER. The global `title` (or `album`),
+/// `artist` (or `album_artist`) and `composer` become the disc TITLE, PERFORMER and SONGWRITER,
+/// and `date`, `genre` and `comment` become `REM` comments.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ImportOptions;
+/// use rcue::ffmetadata::from_ffmetadata;
+///
+/// let metadata = ";FFMETADATA1\ntitle=Book\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=90000\n\
+///                 title=Intro\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=90000\nEND=120000\n";
+/// let (cue, diagnostics) = from_ffmetadata(metadata, "book.m4b", &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.title, Some("Book".to_string()));
+/// assert_eq!(cue.files[0].tracks[0].title, Some("Intro".to_string()));
+/// assert_eq!(cue.files[0].tracks[1].start().unwrap().as_secs(), 90);
+/// assert!(diagnostics.is_empty());
+/// ```
+///
+/// # Failures
+///
+/// Fails if the text does not start with `;FFMETADATA1`, a chapter has no valid START or
+/// TIMEBASE or starts too late to count in nanoseconds, or there are more than 99 chapters.
+pub fn from_ffmetadata(
+    text: &str,
+    file: &str,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    struct Section {
+        timebase: Option<String>,
+        start: Option<String>,
+        title: Option<String>,
+        artist: Option<String>,
+    }
+
+    let text = text.trim_start_matches('\u{feff}');
+    if !text.starts_with(";FFMETADATA1") {
+        return Err(CueError::Parse("missing ;FFMETADATA1 header".to_string()));
+    }
+
+    let mut global: Vec<(String, String)> = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
+    // `None` outside sections, `Some(false)` in sections other than [CHAPTER]
+    let mut in_chapter: Option<bool> = None;
+
+    for (key, value) in metadata_lines(text) {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                let is_chapter = key.trim() == "[CHAPTER]";
+                if is_chapter {
+                    sections.push(Section {
+                        timebase: None,
+                        start: None,
+                        title: None,
+                        artist: None,
+                    });
+                }
+                in_chapter = Some(is_chapter);
+                continue;
+            }
+        };
+
+        match (in_chapter, sections.last_mut()) {
+            (None, _) => global.push((key.to_ascii_lowercase(), value)),
+            (Some(true), Some(section)) => match key.to_ascii_lowercase().as_str() {
+                "timebase" => section.timebase = Some(value),
+                "start" => section.start = Some(value),
+                "title" => section.title = Some(value),
+                "artist" => section.artist = Some(value),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    let mut marks = Vec::new();
+    for (i, section) in sections.into_iter().enumerate() {
+        let (num, den) = match section.timebase {
+            Some(ref timebase) => parse_timebase(timebase).ok_or_else(|| {
+                CueError::Parse(format!("chapter {}: bad TIMEBASE {}", i + 1, timebase))
+            })?,
+            None => (1, 1_000_000_000),
+        };
+        let start: u128 = section
+            .start
+            .as_ref()
+            .and_then(|start| start.trim().parse().ok())
+            .ok_or_else(|| CueError::Parse(format!("chapter {}: missing START", i + 1)))?;
+        let start = start
+            .checked_mul(num)
+            .and_then(|start| start.checked_mul(1_000_000_000))
+            .and_then(|nanos| nanos.checked_add(den / 2))
+            .and_then(|nanos| u64::try_from(nanos / den).ok())
+            .ok_or_else(|| CueError::Parse(format!("chapter {}: START out of range", i + 1)))?;
+        marks.push(ChapterMark {
+            start,
+            title: section.title,
+            performer: section.artist,
+        });
+    }
+
+    let (mut cue, diagnostics) = to_cue(&marks, file, options)?;
+
+    let global_value = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|key| global.iter().find(|(k, _)| k == key))
+            .map(|(_, v)| v.clone())
+            .next()
+    };
+    cue.title = global_value(&["title", "album"]);
+    cue.performer = global_value(&["artist", "album_artist"]);
+    cue.songwriter = global_value(&["composer"]);
+    for key in &["date", "genre", "comment"] {
+        if let Some(value) = global_value(&[key]) {
+            cue.comments.push((key.to_ascii_uppercase(), value));
+        }
+    }
+
+    Ok((cue, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_good_cue() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let expected = r#";FFMETADATA1
+title=Loveless
+album=Loveless
+artist=My Bloody Valentine
+composer=foobar
+date=1991
+genre=Alternative
+comment=ExactAudioCopy v0.95b4
+
+[CHAPTER]
+TIMEBASE=1/75
+START=0
+END=19327
+title=Only Shallow
+artist=My Bloody Valentine
+
+[CHAPTER]
+TIMEBASE=1/75
+START=19327
+END=30000
+title=Loomer
+artist=My Bloody Valentine
+"#;
+        assert_eq!(to_ffmetadata(&cue, 30000).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_escaped_title() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[0].title = Some("a=b; #1\\2".to_string());
+        let metadata = to_ffmetadata(&cue, 30000).unwrap();
+        assert!(metadata.contains("\ntitle=a\\=b\\; \\#1\\\\2\n"));
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[0].title = Some("a=b; #1\\2\nline".to_string());
+        let metadata = to_ffmetadata(&cue, 30000).unwrap();
+
+        let (imported, diagnostics) =
+            from_ffmetadata(&metadata, "Loveless.mka", &ImportOptions::default()).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(imported.title, cue.title);
+        assert_eq!(imported.performer, cue.performer);
+        assert_eq!(imported.songwriter, cue.songwriter);
+        assert_eq!(imported.comments.len(), 3);
+        assert_eq!(
+            chapters(&imported, 30000).unwrap(),
+            chapters(&cue, 30000).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_import_timebase_rounding() {
+        let metadata = ";FFMETADATA1\n[STREAM]\ntitle=ignored\n[CHAPTER]\nSTART=0\n\
+                        [CHAPTER]\nTIMEBASE=1/44100\nSTART=441000\n\
+                        [CHAPTER]\nTIMEBASE=1/1000\nSTART=20010\n";
+        let (cue, diagnostics) =
+            from_ffmetadata(metadata, "a.mka", &ImportOptions::default()).unwrap();
+        assert!(cue.title.is_none());
+        let starts: Vec<u64> = chapters(&cue, 10000)
+            .unwrap()
+            .iter()
+            .map(|chapter| chapter.start)
+            .collect();
+        assert_eq!(starts, vec![0, 750, 1501]);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                Some(3),
+                "start rounded by 3333333ns to frame 1501"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_import_invalid() {
+        let options = ImportOptions::default();
+        assert!(from_ffmetadata("title=x\n", "a.mka", &options).is_err());
+        assert!(from_ffmetadata(";FFMETADATA1\n[CHAPTER]\nEND=1\n", "a.mka", &options).is_err());
+        assert!(from_ffmetadata(
+            ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/0\nSTART=1\n",
+            "a.mka",
+            &options
+        )
+        .is_err());
+        for start in &["1000000000000000000000000000000", "18446744074"] {
+            let metadata = format!(";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1\nSTART={}\n", start);
+            match from_ffmetadata(&metadata, "a.mka", &options) {
+                Err(CueError::Parse(message)) => {
+                    assert_eq!(message, "chapter 1: START out of range")
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,751 @@
+use std::io::{self, BufReader, Read, Write};
+
+use cue::{Cue, CueFile, Track, TrackMode};
+use errors::CueError;
+use parser::parse;
+use util::{duration_to_frames, frames_to_duration, FRAMES_PER_SECOND};
+use writer;
+
+/// Metadata block type of `STREAMINFO`
+pub const STREAMINFO: u8 = 0;
+/// Metadata block type of `VORBIS_COMMENT`
+pub const VORBIS_COMMENT: u8 = 4;
+/// Metadata block type of `CUESHEET`
+pub const CUESHEET: u8 = 5;
+
+/// Field name of the Vorbis comment holding a CUE sheet
+pub const CUESHEET_TAG: &str = "CUESHEET";
+
+/// A raw FLAC metadata block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataBlock {
+    /// Block type (eg. [`CUESHEET`](constant.CUESHEET.html))
+    pub kind: u8,
+    /// Block contents, without the block header
+    pub data: Vec<u8>,
+}
+
+/// The fields of a `STREAMINFO` block needed to convert sample offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Samples per second, per channel
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u8,
+    /// Bits per sample
+    pub bits_per_sample: u8,
+    /// Samples per channel in the stream, or 0 if unknown
+    pub total_samples: u64,
+}
+
+/// A track of a native `CUESHEET` block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheetTrack {
+    /// Offset of the track's first index point from the start of the stream, in samples
+    pub offset: u64,
+    /// Track number; 170 (CD) or 255 is the lead-out track
+    pub number: u8,
+    /// International Standard Recording Code, or an empty string
+    pub isrc: String,
+    /// Whether the track is audio (as opposed to data)
+    pub audio: bool,
+    /// Whether the track has pre-emphasis
+    pub pre_emphasis: bool,
+    /// (index number, offset in samples from the track offset)
+    pub indices: Vec<(u8, u64)>,
+}
+
+/// A native FLAC `CUESHEET` metadata block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueSheetBlock {
+    /// Media Catalog Number, or an empty string
+    pub catalog: String,
+    /// Number of lead-in samples (88200 for CDs)
+    pub lead_in: u64,
+    /// Whether the sheet describes a CD
+    pub is_cd: bool,
+    /// Tracks, including the lead-out track
+    pub tracks: Vec<CueSheetTrack>,
+}
+
+fn invalid(reason: &str) -> CueError {
+    CueError::Parse(format!("invalid FLAC metadata: {}", reason))
+}
+
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |n, b| n << 8 | u64::from(*b))
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .rev()
+        .fold(0, |n, b| n << 8 | u32::from(*b))
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| char::from(*b))
+        .collect()
+}
+
+fn padded(s: &str, len: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = s.bytes().take(len).collect();
+    bytes.resize(len, 0);
+    bytes
+}
+
+impl CueSheetBlock {
+    /// Parses the contents of a `CUESHEET` block.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the block is truncated.
+    pub fn parse(data: &[u8]) -> Result<Self, CueError> {
+        if data.len() < 396 {
+            return Err(invalid("CUESHEET block too short"));
+        }
+
+        let mut block = CueSheetBlock {
+            catalog: ascii(&data[0..128]),
+            lead_in: be(&data[128..136]),
+            is_cd: data[136] & 0x80 != 0,
+            tracks: Vec::new(),
+        };
+
+        let mut pos = 396;
+        for _ in 0..data[395] {
+            let track = data
+                .get(pos..pos + 36)
+                .ok_or_else(|| invalid("CUESHEET track truncated"))?;
+            let mut parsed = CueSheetTrack {
+                offset: be(&track[0..8]),
+                number: track[8],
+                isrc: ascii(&track[9..21]),
+                audio: track[21] & 0x80 == 0,
+                pre_emphasis: track[21] & 0x40 != 0,
+                indices: Vec::new(),
+            };
+            pos += 36;
+
+            for _ in 0..track[35] {
+                let index = data
+                    .get(pos..pos + 12)
+                    .ok_or_else(|| invalid("CUESHEET index truncated"))?;
+                parsed.indices.push((index[8], be(&index[0..8])));
+                pos += 12;
+            }
+            block.tracks.push(parsed);
+        }
+
+        Ok(block)
+    }
+
+    /// Serializes the block contents, without the block header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = padded(&self.catalog, 128);
+        data.extend_from_slice(&self.lead_in.to_be_bytes());
+        data.push(if self.is_cd { 0x80 } else { 0 });
+        data.extend(vec![0u8; 258]);
+        data.push(self.tracks.len() as u8);
+
+        for track in &self.tracks {
+            data.extend_from_slice(&track.offset.to_be_bytes());
+            data.push(track.number);
+            data.extend(padded(&track.isrc, 12));
+            let mut flags = 0;
+            if !track.audio {
+                flags |= 0x80;
+            }
+            if track.pre_emphasis {
+                flags |= 0x40;
+            }
+            data.push(flags);
+            data.extend(vec![0u8; 13]);
+            data.push(track.indices.len() as u8);
+
+            for (number, offset) in &track.indices {
+                data.extend_from_slice(&offset.to_be_bytes());
+                data.push(*number);
+                data.extend_from_slice(&[0, 0, 0]);
+            }
+        }
+
+        data
+    }
+
+    /// Converts the block into a single-file [`Cue`](../cue/struct.Cue.html) whose FILE is
+    /// `file_name` of type `WAVE`.
+    ///
+    /// Sample offsets are rounded down to CD frames using `sample_rate`. The lead-out track is
+    /// dropped, ISRCs are kept, pre-emphasis becomes the `PRE` flag and data tracks become
+    /// `MODE1/2352`.
+    pub fn to_cue(&self, file_name: &str, sample_rate: u32) -> Cue {
+        let to_frames = |samples: u64| samples * FRAMES_PER_SECOND / u64::from(sample_rate.max(1));
+
+        let mut file = CueFile::new(file_name, "WAVE");
+        for track in self.tracks.iter().filter(|t| !is_lead_out(t.number)) {
+            let mode = if track.audio {
+                TrackMode::Audio
+            } else {
+                TrackMode::Mode1_2352
+            };
+            let mut converted = Track::new(&format!("{:02}", track.number), mode.as_str());
+            if !track.isrc.is_empty() {
+                converted.isrc = Some(track.isrc.clone());
+            }
+            if track.pre_emphasis {
+                converted.flags.push("PRE".to_string());
+            }
+            for (number, offset) in &track.indices {
+                converted.indices.push((
+                    format!("{:02}", number),
+                    frames_to_duration(to_frames(track.offset + offset)),
+                ));
+            }
+            file.tracks.push(converted);
+        }
+
+        let mut cue = Cue::new();
+        if !self.catalog.is_empty() {
+            cue.catalog = Some(self.catalog.clone());
+        }
+        cue.files.push(file);
+        cue
+    }
+
+    /// Builds a block from a single-file [`Cue`](../cue/struct.Cue.html), for a stream of
+    /// `total_samples` samples at `sample_rate`.
+    ///
+    /// Streams at 44.1KHz are flagged as CDs, with a lead-in of 88200 samples and lead-out track
+    /// 170; other streams use lead-out track 255.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the sheet does not have exactly one FILE, if a track number is not a number from
+    /// 1 to 99 or an index number is not a number from 0 to 99, or if a track has no INDEX or
+    /// has a PREGAP or POSTGAP.
+    pub fn from_cue(cue: &Cue, sample_rate: u32, total_samples: u64) -> Result<Self, CueError> {
+        if cue.files.len() != 1 {
+            return Err(CueError::Parse(
+                "an embedded CUESHEET must have exactly one FILE".to_string(),
+            ));
+        }
+
+        let is_cd = sample_rate == 44_100;
+        let to_samples = |frames: u64| frames * u64::from(sample_rate) / FRAMES_PER_SECOND;
+        let number = |s: &str, min: u8| {
+            s.parse::<u8>()
+                .ok()
+                .filter(|n| (min..100).contains(n))
+                .ok_or_else(|| CueError::Parse(format!("invalid track or index number: {}", s)))
+        };
+
+        let mut block = CueSheetBlock {
+            catalog: cue.catalog.clone().unwrap_or_default(),
+            lead_in: if is_cd { 88_200 } else { 0 },
+            is_cd,
+            tracks: Vec::new(),
+        };
+
+        for track in &cue.files[0].tracks {
+            let track_number = number(&track.no, 1)?;
+            if track.pregap.is_some() || track.postgap.is_some() {
+                return Err(CueError::Parse(format!(
+                    "track {} has a PREGAP or POSTGAP, which is not in the audio stream",
+                    track.no
+                )));
+            }
+            let start = match track.pregap_start() {
+                Some(start) => to_samples(duration_to_frames(&start)),
+                None => {
+                    return Err(CueError::Parse(format!("track {} has no INDEX", track.no)));
+                }
+            };
+            let mut indices = Vec::new();
+            for (idx, time) in &track.indices {
+                let offset = to_samples(duration_to_frames(time)).saturating_sub(start);
+                indices.push((number(idx, 0)?, offset));
+            }
+
+            block.tracks.push(CueSheetTrack {
+                offset: start,
+                number: track_number,
+                isrc: track.isrc.clone().unwrap_or_default(),
+                audio: track.mode().is_none_or(|m| m == TrackMode::Audio),
+                pre_emphasis: track.flags.iter().any(|f| f.eq_ignore_ascii_case("PRE")),
+                indices,
+            });
+        }
+
+        block.tracks.push(CueSheetTrack {
+            offset: total_samples,
+            number: if is_cd { 170 } else { 255 },
+            isrc: String::new(),
+            audio: true,
+            pre_emphasis: false,
+            indices: Vec::new(),
+        });
+
+        Ok(block)
+    }
+}
+
+fn is_lead_out(number: u8) -> bool {
+    number == 170 || number == 255
+}
+
+/// The metadata blocks of a FLAC stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlacMetadata {
+    /// Blocks in stream order; `STREAMINFO` is first
+    pub blocks: Vec<MetadataBlock>,
+}
+
+impl FlacMetadata {
+    /// Reads the `fLaC` marker and all metadata blocks, leaving `reader` at the first audio frame.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the stream is not FLAC or the metadata is truncated.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, CueError> {
+        let mut marker = [0u8; 4];
+        reader.read_exact(&mut marker)?;
+        if &marker != b"fLaC" {
+            return Err(invalid("missing fLaC marker"));
+        }
+
+        let mut blocks = Vec::new();
+        loop {
+            let mut header = [0u8; 4];
+            reader.read_exact(&mut header)?;
+            let mut data = vec![0u8; be(&header[1..4]) as usize];
+            reader.read_exact(&mut data)?;
+            blocks.push(MetadataBlock {
+                kind: header[0] & 0x7f,
+                data,
+            });
+            if header[0] & 0x80 != 0 {
+                return Ok(FlacMetadata { blocks });
+            }
+        }
+    }
+
+    /// Serializes the `fLaC` marker and all blocks, flagging the final block as last.
+    ///
+    /// # Failures
+    ///
+    /// Fails if a block is longer than the 24-bit block length allows (16 MiB - 1 byte).
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CueError> {
+        let mut bytes = b"fLaC".to_vec();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.data.len() > 0xFF_FFFF {
+                return Err(CueError::Parse(format!(
+                    "metadata block of {} bytes is too long for FLAC",
+                    block.data.len()
+                )));
+            }
+            let last = if i + 1 == self.blocks.len() { 0x80 } else { 0 };
+            bytes.push(block.kind | last);
+            bytes.extend_from_slice(&(block.data.len() as u32).to_be_bytes()[1..4]);
+            bytes.extend_from_slice(&block.data);
+        }
+        Ok(bytes)
+    }
+
+    fn block(&self, kind: u8) -> Option<&MetadataBlock> {
+        self.blocks.iter().find(|b| b.kind == kind)
+    }
+
+    fn replace_block(&mut self, kind: u8, data: Vec<u8>) {
+        match self.blocks.iter().position(|b| b.kind == kind) {
+            Some(i) => self.blocks[i].data = data,
+            None => {
+                let at = self.blocks.len().min(1);
+                self.blocks.insert(at, MetadataBlock { kind, data });
+            }
+        }
+    }
+
+    /// Returns the parsed `STREAMINFO` block.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the block is missing or truncated.
+    pub fn stream_info(&self) -> Result<StreamInfo, CueError> {
+        let data = match self.block(STREAMINFO) {
+            Some(block) if block.data.len() >= 18 => &block.data,
+            _ => return Err(invalid("missing STREAMINFO block")),
+        };
+        let packed = be(&data[10..18]);
+
+        Ok(StreamInfo {
+            sample_rate: (packed >> 44) as u32,
+            channels: ((packed >> 41) & 0x7) as u8 + 1,
+            bits_per_sample: ((packed >> 36) & 0x1f) as u8 + 1,
+            total_samples: packed & 0xf_ffff_ffff,
+        })
+    }
+
+    /// Returns the native `CUESHEET` block, if present.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the block is truncated.
+    pub fn cuesheet(&self) -> Result<Option<CueSheetBlock>, CueError> {
+        self.block(CUESHEET)
+            .map(|block| CueSheetBlock::parse(&block.data))
+            .transpose()
+    }
+
+    /// Replaces or adds the native `CUESHEET` block.
+    pub fn set_cuesheet(&mut self, cuesheet: &CueSheetBlock) {
+        self.replace_block(CUESHEET, cuesheet.to_bytes());
+    }
+
+    /// Returns the (field name, value) pairs of the `VORBIS_COMMENT` block.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the block is truncated.
+    pub fn vorbis_comments(&self) -> Result<Vec<(String, String)>, CueError> {
+        let data = match self.block(VORBIS_COMMENT) {
+            Some(block) => &block.data,
+            None => return Ok(Vec::new()),
+        };
+        let (_, comments) = parse_vorbis_comments(data)?;
+        Ok(comments)
+    }
+
+    /// Returns the CUE sheet text stored in the `CUESHEET` Vorbis comment, if present.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the `VORBIS_COMMENT` block is truncated.
+    pub fn cuesheet_tag(&self) -> Result<Option<String>, CueError> {
+        Ok(self
+            .vorbis_comments()?
+            .into_iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(CUESHEET_TAG))
+            .map(|(_, value)| value))
+    }
+
+    /// Replaces or adds the `CUESHEET` Vorbis comment, keeping all other comments.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the existing `VORBIS_COMMENT` block is truncated.
+    pub fn set_cuesheet_tag(&mut self, sheet: &str) -> Result<(), CueError> {
+        let (vendor, mut comments) = match self.block(VORBIS_COMMENT) {
+            Some(block) => parse_vorbis_comments(&block.data)?,
+            None => ("rcue".to_string(), Vec::new()),
+        };
+        comments.retain(|(key, _)| !key.eq_ignore_ascii_case(CUESHEET_TAG));
+        comments.push((CUESHEET_TAG.to_string(), sheet.to_string()));
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        data.extend_from_slice(vendor.as_bytes());
+        data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for (key, value) in &comments {
+            let comment = format!("{}={}", key, value);
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        self.replace_block(VORBIS_COMMENT, data);
+        Ok(())
+    }
+}
+
+fn parse_vorbis_comments(data: &[u8]) -> Result<(String, Vec<(String, String)>), CueError> {
+    fn field<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], CueError> {
+        let len = data
+            .get(*pos..*pos + 4)
+            .map(le32)
+            .ok_or_else(|| invalid("VORBIS_COMMENT truncated"))? as usize;
+        let value = data
+            .get(*pos + 4..*pos + 4 + len)
+            .ok_or_else(|| invalid("VORBIS_COMMENT truncated"))?;
+        *pos += 4 + len;
+        Ok(value)
+    }
+
+    let mut pos = 0;
+    let vendor = String::from_utf8_lossy(field(data, &mut pos)?).into_owned();
+    let count = data
+        .get(pos..pos + 4)
+        .map(le32)
+        .ok_or_else(|| invalid("VORBIS_COMMENT truncated"))?;
+    pos += 4;
+
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let comment = String::from_utf8_lossy(field(data, &mut pos)?).into_owned();
+        let mut parts = comment.splitn(2, '=');
+        let key = parts.next().unwrap_or("").to_string();
+        let value = parts.next().unwrap_or("").to_string();
+        comments.push((key, value));
+    }
+
+    Ok((vendor, comments))
+}
+
+/// Reads the CUE sheet embedded in a FLAC stream.
+///
+/// The `CUESHEET` Vorbis comment is preferred as it carries titles and performers; it is parsed
+/// leniently. Otherwise the native `CUESHEET` block is converted with
+/// [`CueSheetBlock::to_cue`](struct.CueSheetBlock.html#method.to_cue), referencing `file_name`.
+///
+/// Returns `None` if the stream has no embedded sheet.
+///
+/// # Failures
+///
+/// Fails if the stream is not FLAC or its metadata is invalid.
+pub fn read_cue<R: Read>(reader: &mut R, file_name: &str) -> Result<Option<Cue>, CueError> {
+    let metadata = FlacMetadata::read(reader)?;
+
+    if let Some(sheet) = metadata.cuesheet_tag()? {
+        let mut buf_reader = BufReader::new(sheet.as_bytes());
+        return parse(&mut buf_reader, false).map(Some);
+    }
+
+    match metadata.cuesheet()? {
+        Some(block) => {
+            let info = metadata.stream_info()?;
+            Ok(Some(block.to_cue(file_name, info.sample_rate)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Copies a FLAC stream from `input` to `output`, replacing its metadata with `metadata`.
+///
+/// Audio frames are copied byte for byte.
+///
+/// # Failures
+///
+/// Fails if `input` is not FLAC, if a block of `metadata` is too long (see
+/// [`FlacMetadata::to_bytes`](struct.FlacMetadata.html#method.to_bytes)), or on IO errors.
+pub fn rewrite<R: Read, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    metadata: &FlacMetadata,
+) -> Result<(), CueError> {
+    FlacMetadata::read(input)?;
+    output.write_all(&metadata.to_bytes()?)?;
+    io::copy(input, output)?;
+    Ok(())
+}
+
+/// Copies a FLAC stream from `input` to `output`, embedding `cue` as both a native `CUESHEET`
+/// block and a `CUESHEET` Vorbis comment.
+///
+/// # Failures
+///
+/// Fails if `input` is not FLAC, if `cue` can not be stored in a `CUESHEET` block (see
+/// [`CueSheetBlock::from_cue`](struct.CueSheetBlock.html#method.from_cue)), if the sheet makes a
+/// metadata block too long, or on IO errors.
+pub fn write_cue<R: Read, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    cue: &Cue,
+) -> Result<(), CueError> {
+    let mut metadata = FlacMetadata::read(input)?;
+    let info = metadata.stream_info()?;
+    metadata.set_cuesheet(&CueSheetBlock::from_cue(
+        cue,
+        info.sample_rate,
+        info.total_samples,
+    )?);
+    metadata.set_cuesheet_tag(&writer::to_string(cue))?;
+
+    output.write_all(&metadata.to_bytes()?)?;
+    io::copy(input, output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{parse, parse_from_file};
+    use std::io::Cursor;
+
+    const AUDIO: &[u8] = b"\xff\xf8audio frames";
+
+    fn stream_info(sample_rate: u64, total_samples: u64) -> MetadataBlock {
+        let mut data = vec![0u8; 34];
+        let packed = sample_rate << 44 | 1 << 41 | 15 << 36 | total_samples;
+        data[10..18].copy_from_slice(&packed.to_be_bytes());
+        MetadataBlock {
+            kind: STREAMINFO,
+            data,
+        }
+    }
+
+    fn flac(blocks: Vec<MetadataBlock>) -> Vec<u8> {
+        let mut bytes = FlacMetadata { blocks }.to_bytes().unwrap();
+        bytes.extend_from_slice(AUDIO);
+        bytes
+    }
+
+    #[test]
+    fn test_stream_info() {
+        let metadata = FlacMetadata {
+            blocks: vec![stream_info(44_100, 1_000_000)],
+        };
+        let info = metadata.stream_info().unwrap();
+        assert_eq!(info.sample_rate, 44_100);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.total_samples, 1_000_000);
+    }
+
+    #[test]
+    fn test_native_block_round_trip() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let block = CueSheetBlock::from_cue(&cue, 44_100, 20_000 * 588).unwrap();
+        assert!(block.is_cd);
+        assert_eq!(block.tracks.len(), 3);
+        assert_eq!(block.tracks[1].offset, 19327 * 588);
+        assert_eq!(block.tracks[2].number, 170);
+        assert_eq!(block.tracks[0].isrc, "USRC17609839");
+        assert!(block.tracks[0].pre_emphasis);
+
+        let parsed = CueSheetBlock::parse(&block.to_bytes()).unwrap();
+        assert_eq!(parsed, block);
+
+        let converted = parsed.to_cue("Loveless.flac", 44_100);
+        let tracks = &converted.files[0].tracks;
+        assert_eq!(converted.files[0].file, "Loveless.flac");
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].indices, cue.files[0].tracks[1].indices);
+        assert_eq!(tracks[0].isrc, Some("USRC17609839".to_string()));
+        assert_eq!(tracks[0].flags, vec!["PRE"]);
+    }
+
+    #[test]
+    fn test_block_from_unsupported_sheet() {
+        let sheet = "FILE \"a.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        let error = |sheet: &str| {
+            let cue = parse(&mut Cursor::new(sheet), false).unwrap();
+            match CueSheetBlock::from_cue(&cue, 44_100, 20_000 * 588) {
+                Err(CueError::Parse(message)) => message,
+                other => panic!("expected an error, got {:?}", other),
+            }
+        };
+
+        assert_eq!(
+            error(&sheet.replace("TRACK 01", "TRACK 00")),
+            "invalid track or index number: 00"
+        );
+        assert_eq!(
+            error(&format!("{}  TRACK 02 AUDIO\n", sheet)),
+            "track 02 has no INDEX"
+        );
+        assert_eq!(
+            error(&sheet.replace("    INDEX", "    PREGAP 00:02:00\n    INDEX")),
+            "track 01 has a PREGAP or POSTGAP, which is not in the audio stream"
+        );
+    }
+
+    #[test]
+    fn test_read_native_block() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let block = CueSheetBlock::from_cue(&cue, 44_100, 20_000 * 588).unwrap();
+        let bytes = flac(vec![
+            stream_info(44_100, 20_000 * 588),
+            MetadataBlock {
+                kind: CUESHEET,
+                data: block.to_bytes(),
+            },
+        ]);
+        let read = read_cue(&mut Cursor::new(bytes), "a.flac")
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.files[0].tracks.len(), 2);
+    }
+
+    #[test]
+    fn test_write_and_read_tag() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let input = flac(vec![stream_info(44_100, 20_000 * 588)]);
+        let mut output = Vec::new();
+        write_cue(&mut Cursor::new(input), &mut output, &cue).unwrap();
+        assert!(output.ends_with(AUDIO));
+
+        let metadata = FlacMetadata::read(&mut Cursor::new(&output)).unwrap();
+        assert!(metadata.cuesheet().unwrap().is_some());
+        assert_eq!(metadata.blocks[0].kind, STREAMINFO);
+
+        let read = read_cue(&mut Cursor::new(output), "a.flac")
+            .unwrap()
+            .unwrap();
+        assert_eq!(read, cue);
+    }
+
+    #[test]
+    fn test_set_tag_keeps_other_comments() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&6u32.to_le_bytes());
+        data.extend_from_slice(b"vendor");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for comment in &["ARTIST=Someone", "cuesheet=old"] {
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        let mut metadata = FlacMetadata {
+            blocks: vec![
+                stream_info(44_100, 0),
+                MetadataBlock {
+                    kind: VORBIS_COMMENT,
+                    data,
+                },
+            ],
+        };
+        assert_eq!(metadata.cuesheet_tag().unwrap(), Some("old".to_string()));
+
+        metadata.set_cuesheet_tag("new").unwrap();
+        assert_eq!(
+            metadata.vorbis_comments().unwrap(),
+            vec![
+                ("ARTIST".to_string(), "Someone".to_string()),
+                ("CUESHEET".to_string(), "new".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_embedded_sheet() {
+        let bytes = flac(vec![stream_info(44_100, 0)]);
+        assert!(read_cue(&mut Cursor::new(bytes), "a.flac")
+            .unwrap()
+            .is_none());
+        assert!(read_cue(&mut Cursor::new(b"RIFF".to_vec()), "a.flac").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_keeps_audio() {
+        let input = flac(vec![stream_info(44_100, 0)]);
+        let mut metadata = FlacMetadata::read(&mut Cursor::new(&input)).unwrap();
+        metadata.set_cuesheet_tag("REM A b").unwrap();
+        let mut output = Vec::new();
+        rewrite(&mut Cursor::new(input), &mut output, &metadata).unwrap();
+        assert!(output.ends_with(AUDIO));
+        assert_eq!(
+            FlacMetadata::read(&mut Cursor::new(output)).unwrap(),
+            metadata
+        );
+
+        metadata.blocks.push(MetadataBlock {
+            kind: VORBIS_COMMENT,
+            data: vec![0; 0x100_0000],
+        });
+        let mut output = Vec::new();
+        let input = flac(vec![stream_info(44_100, 0)]);
+        assert!(rewrite(&mut Cursor::new(input), &mut output, &metadata).is_err());
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,468 @@
+use std::io::{self, Read};
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Streaming CRC-32 (IEEE 802.3, as used by ZIP and DAT files).
+///
+/// # Example
+///
+/// ```
+/// use rcue::hash::Crc32;
+///
+/// let mut crc = Crc32::new();
+/// crc.update(b"123456789");
+/// assert_eq!(crc.finish(), 0xCBF4_3926);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Constructs a new [`Crc32`](struct.Crc32.html).
+    pub fn new() -> Self {
+        Crc32 { crc: 0xFFFF_FFFF }
+    }
+
+    /// Adds `data` to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.crc =
+                CRC32_TABLE[((self.crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    /// Returns the checksum of the data so far.
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+// Appends the message length and padding of MD5 and SHA-1, which both use 64-byte blocks.
+fn pad(buffer: &[u8], length: u64, big_endian: bool) -> Vec<u8> {
+    let mut tail = buffer.to_vec();
+    tail.push(0x80);
+    while tail.len() % 64 != 56 {
+        tail.push(0);
+    }
+    let bits = length.wrapping_mul(8);
+    if big_endian {
+        tail.extend_from_slice(&bits.to_be_bytes());
+    } else {
+        tail.extend_from_slice(&bits.to_le_bytes());
+    }
+    tail
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76a_a478,
+    0xe8c7_b756,
+    0x2420_70db,
+    0xc1bd_ceee,
+    0xf57c_0faf,
+    0x4787_c62a,
+    0xa830_4613,
+    0xfd46_9501,
+    0x6980_98d8,
+    0x8b44_f7af,
+    0xffff_5bb1,
+    0x895c_d7be,
+    0x6b90_1122,
+    0xfd98_7193,
+    0xa679_438e,
+    0x49b4_0821,
+    0xf61e_2562,
+    0xc040_b340,
+    0x265e_5a51,
+    0xe9b6_c7aa,
+    0xd62f_105d,
+    0x0244_1453,
+    0xd8a1_e681,
+    0xe7d3_fbc8,
+    0x21e1_cde6,
+    0xc337_07d6,
+    0xf4d5_0d87,
+    0x455a_14ed,
+    0xa9e3_e905,
+    0xfcef_a3f8,
+    0x676f_02d9,
+    0x8d2a_4c8a,
+    0xfffa_3942,
+    0x8771_f681,
+    0x6d9d_6122,
+    0xfde5_380c,
+    0xa4be_ea44,
+    0x4bde_cfa9,
+    0xf6bb_4b60,
+    0xbebf_bc70,
+    0x289b_7ec6,
+    0xeaa1_27fa,
+    0xd4ef_3085,
+    0x0488_1d05,
+    0xd9d4_d039,
+    0xe6db_99e5,
+    0x1fa2_7cf8,
+    0xc4ac_5665,
+    0xf429_2244,
+    0x432a_ff97,
+    0xab94_23a7,
+    0xfc93_a039,
+    0x655b_59c3,
+    0x8f0c_cc92,
+    0xffef_f47d,
+    0x8584_5dd1,
+    0x6fa8_7e4f,
+    0xfe2c_e6e0,
+    0xa301_4314,
+    0x4e08_11a1,
+    0xf753_7e82,
+    0xbd3a_f235,
+    0x2ad7_d2bb,
+    0xeb86_d391,
+];
+
+/// Streaming MD5.
+///
+/// # Example
+///
+/// ```
+/// use rcue::hash::{to_hex, Md5};
+///
+/// let mut md5 = Md5::new();
+/// md5.update(b"abc");
+/// assert_eq!(to_hex(&md5.finish()), "900150983cd24fb0d6963f7d28e17f72");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Md5 {
+    state: [u32; 4],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Md5 {
+    /// Constructs a new [`Md5`](struct.Md5.html).
+    pub fn new() -> Self {
+        Md5 {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    fn block(&mut self, block: &[u8]) {
+        let mut m = [0u32; 16];
+        for (i, word) in m.iter_mut().enumerate() {
+            *word = u32::from_le_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(MD5_CONSTANTS[i])
+                .wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(MD5_SHIFTS[i]));
+        }
+
+        for (state, value) in self.state.iter_mut().zip(&[a, b, c, d]) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+
+    /// Adds `data` to the digest.
+    pub fn update(&mut self, data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        self.buffer.extend_from_slice(data);
+        let full = self.buffer.len() / 64 * 64;
+        let buffer = std::mem::take(&mut self.buffer);
+        for block in buffer[..full].chunks(64) {
+            self.block(block);
+        }
+        self.buffer = buffer[full..].to_vec();
+    }
+
+    /// Returns the digest of the data so far.
+    pub fn finish(&self) -> [u8; 16] {
+        let mut md5 = self.clone();
+        for block in pad(&self.buffer, self.length, false).chunks(64) {
+            md5.block(block);
+        }
+        let mut digest = [0u8; 16];
+        for (bytes, word) in digest.chunks_mut(4).zip(&md5.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+/// Streaming SHA-1.
+///
+/// # Example
+///
+/// ```
+/// use rcue::hash::{to_hex, Sha1};
+///
+/// let mut sha1 = Sha1::new();
+/// sha1.update(b"abc");
+/// assert_eq!(to_hex(&sha1.finish()), "a9993e364706816aba3e25717850c26c9cd0d89d");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha1 {
+    /// Constructs a new [`Sha1`](struct.Sha1.html).
+    pub fn new() -> Self {
+        Sha1 {
+            state: [
+                0x6745_2301,
+                0xEFCD_AB89,
+                0x98BA_DCFE,
+                0x1032_5476,
+                0xC3D2_E1F0,
+            ],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    fn block(&mut self, block: &[u8]) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5A82_7999),
+                1 => (b ^ c ^ d, 0x6ED9_EBA1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in self.state.iter_mut().zip(&[a, b, c, d, e]) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+
+    /// Adds `data` to the digest.
+    pub fn update(&mut self, data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        self.buffer.extend_from_slice(data);
+        let full = self.buffer.len() / 64 * 64;
+        let buffer = std::mem::take(&mut self.buffer);
+        for block in buffer[..full].chunks(64) {
+            self.block(block);
+        }
+        self.buffer = buffer[full..].to_vec();
+    }
+
+    /// Returns the digest of the data so far.
+    pub fn finish(&self) -> [u8; 20] {
+        let mut sha1 = self.clone();
+        for block in pad(&self.buffer, self.length, true).chunks(64) {
+            sha1.block(block);
+        }
+        let mut digest = [0u8; 20];
+        for (bytes, word) in digest.chunks_mut(4).zip(&sha1.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// Formats bytes as lowercase hexadecimal.
+///
+/// # Example
+///
+/// ```
+/// use rcue::hash::to_hex;
+///
+/// assert_eq!(to_hex(&[0x0a, 0xff]), "0aff");
+/// ```
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Size, CRC-32, MD5 and SHA-1 of a file, with the digests in lowercase hexadecimal as in DAT
+/// files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileHashes {
+    /// Size in bytes
+    pub size: u64,
+    /// CRC-32, 8 hexadecimal digits
+    pub crc32: String,
+    /// MD5, 32 hexadecimal digits
+    pub md5: String,
+    /// SHA-1, 40 hexadecimal digits
+    pub sha1: String,
+}
+
+/// Hashes everything `reader` yields in a single pass.
+///
+/// # Example
+///
+/// ```
+/// use rcue::hash::hash_reader;
+///
+/// let hashes = hash_reader(&mut &b"abc"[..]).unwrap();
+/// assert_eq!(hashes.size, 3);
+/// assert_eq!(hashes.crc32, "352441c2");
+/// ```
+///
+/// # Failures
+///
+/// Fails if reading fails.
+pub fn hash_reader<R: Read>(reader: &mut R) -> io::Result<FileHashes> {
+    let mut crc32 = Crc32::new();
+    let mut md5 = Md5::new();
+    let mut sha1 = Sha1::new();
+    let mut size = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        crc32.update(&buffer[..read]);
+        md5.update(&buffer[..read]);
+        sha1.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok(FileHashes {
+        size,
+        crc32: format!("{:08x}", crc32.finish()),
+        md5: to_hex(&md5.finish()),
+        sha1: to_hex(&sha1.finish()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        let empty = hash_reader(&mut &b""[..]).unwrap();
+        assert_eq!(empty.crc32, "00000000");
+        assert_eq!(empty.md5, "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(empty.sha1, "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+
+        let fox = hash_reader(&mut &b"The quick brown fox jumps over the lazy dog"[..]).unwrap();
+        assert_eq!(fox.crc32, "414fa339");
+        assert_eq!(fox.md5, "9e107d9d372bb6826bd81d3542a419d6");
+        assert_eq!(fox.sha1, "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12");
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        // 1,000,000 repetitions of "a", fed in uneven chunks
+        let data = vec![b'a'; 1_000_000];
+        let mut md5 = Md5::new();
+        let mut sha1 = Sha1::new();
+        for chunk in data.chunks(63) {
+            md5.update(chunk);
+            sha1.update(chunk);
+        }
+        assert_eq!(to_hex(&md5.finish()), "7707d6ae4e027c70eea2a935c2296f21");
+        assert_eq!(
+            to_hex(&sha1.finish()),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+
+        // Lengths around the 56-byte padding boundary
+        let message = [b'x'; 56];
+        let mut md5 = Md5::new();
+        md5.update(&message);
+        let mut split = Md5::new();
+        split.update(&message[..20]);
+        split.update(&message[20..]);
+        assert_eq!(md5.finish(), split.finish());
+    }
+}
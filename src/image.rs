@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cue::{Cue, CueFile, Session, Track, TrackMode};
+use errors::{CueError, Diagnostic};
+use util::frames_to_duration;
+
+// A track read from an image descriptor, with indices as absolute LBAs.
+struct ImageTrack {
+    no: u32,
+    mode: TrackMode,
+    control: u8,
+    session: u32,
+    indices: Vec<(u32, i64)>,
+    isrc: Option<String>,
+}
+
+// Q sub-channel CONTROL bits
+const CONTROL_PRE_EMPHASIS: u8 = 0x01;
+const CONTROL_COPY: u8 = 0x02;
+const CONTROL_DATA: u8 = 0x04;
+const CONTROL_FOUR_CHANNEL: u8 = 0x08;
+
+// Builds a single-file sheet from `tracks`, sorted by number. On multi-session discs, the
+// lead-out to lead-in gaps are not part of the image, so each session's LBAs are moved back by
+// the gaps before it, measured from the `lead_outs` (session to lead-out LBA) where known.
+fn image_cue(
+    file: &str,
+    tracks: Vec<ImageTrack>,
+    lead_outs: &HashMap<u32, i64>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Cue {
+    let multi_session = tracks.iter().any(|track| track.session > 1);
+    let mut cue = Cue::new();
+    let mut cue_file = CueFile::new(file, "BINARY");
+    let mut offset = 0;
+
+    for image_track in tracks {
+        let mut track = Track::new(&format!("{:02}", image_track.no), image_track.mode.as_str());
+        if multi_session {
+            if cue.sessions.last().map(|session| session.no) != Some(image_track.session) {
+                let session = Session::new(image_track.session);
+                let first = image_track.indices.first().map_or(0, |(_, lba)| *lba);
+                if let Some(previous) = cue.sessions.last_mut() {
+                    match lead_outs.get(&previous.no) {
+                        Some(lead_out) if first - lead_out >= session.lead_in_frames() as i64 => {
+                            let frames = (first - lead_out) as u64 - session.lead_in_frames();
+                            previous.lead_out = Some(frames_to_duration(frames));
+                        }
+                        _ => diagnostics.push(Diagnostic::new(
+                            Some(image_track.no),
+                            &format!(
+                                "no usable lead-out for session {}, assuming standard gaps",
+                                previous.no
+                            ),
+                        )),
+                    }
+                }
+                cue.sessions.push(session);
+                offset += cue.session_gap(image_track.session) as i64;
+            }
+            track.session = Some(image_track.session);
+        }
+
+        let control = image_track.control;
+        if image_track.mode == TrackMode::Audio {
+            if control & CONTROL_FOUR_CHANNEL != 0 {
+                track.flags.push("4CH".to_string());
+            }
+            if control & CONTROL_PRE_EMPHASIS != 0 {
+                track.flags.push("PRE".to_string());
+            }
+        }
+        if control & CONTROL_COPY != 0 {
+            track.flags.push("DCP".to_string());
+        }
+        if (control & CONTROL_DATA != 0) != (image_track.mode != TrackMode::Audio) {
+            diagnostics.push(Diagnostic::new(
+                Some(image_track.no),
+                &format!(
+                    "CONTROL {:#04x} does not match mode {}",
+                    control,
+                    image_track.mode.as_str()
+                ),
+            ));
+        }
+        track.isrc = image_track.isrc;
+
+        for (index, lba) in image_track.indices {
+            let lba = lba - offset;
+            if lba < 0 {
+                diagnostics.push(Diagnostic::new(
+                    Some(image_track.no),
+                    &format!(
+                        "INDEX {:02} at LBA {} is before the image, dropped",
+                        index, lba
+                    ),
+                ));
+                continue;
+            }
+            track
+                .indices
+                .push((format!("{:02}", index), frames_to_duration(lba as u64)));
+        }
+        cue_file.tracks.push(track);
+    }
+
+    cue.files.push(cue_file);
+    cue
+}
+
+fn parse_number(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Reads a CloneCD `.ccd` descriptor into a [`Cue`](../cue/struct.Cue.html) for its raw image
+/// `image` (usually the `.img` file next to it).
+///
+/// The image is a single BINARY FILE of 2352-byte sectors, so tracks are AUDIO, MODE1/2352 or
+/// MODE2/2352 according to their `MODE`. Indices are placed at their absolute LBAs; indices
+/// before LBA 0 are dropped with a diagnostic. Flags come from the CONTROL field of the track's
+/// TOC entry, and the disc CATALOG and track ISRC are read when present. On multi-session discs,
+/// tracks are grouped into [`sessions`](../cue/struct.Cue.html#structfield.sessions), whose
+/// lead-outs are measured from the lead-out (point A2) entries and left out of the image.
+///
+/// # Example
+///
+/// ```
+/// use rcue::image::from_ccd;
+///
+/// let ccd = "[CloneCD]\nVersion=3\n[Disc]\nTocEntries=1\nSessions=1\n\
+///            [Entry 0]\nSession=1\nPoint=0x01\nControl=0x04\nPLBA=0\n\
+///            [TRACK 1]\nMODE=1\nINDEX 1=0\n";
+/// let (cue, diagnostics) = from_ccd(ccd, "disc.img").unwrap();
+/// assert_eq!(cue.files[0].format, "BINARY");
+/// assert_eq!(cue.files[0].tracks[0].format, "MODE1/2352");
+/// assert!(diagnostics.is_empty());
+/// ```
+///
+/// # Failures
+///
+/// Fails if the descriptor has no tracks, or a track has an unknown MODE, an invalid INDEX or no
+/// INDEX 01.
+pub fn from_ccd(text: &str, image: &str) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push((line[1..line.len() - 1].trim().to_string(), Vec::new()));
+        } else if let (Some(eq), Some(section)) = (line.find('='), sections.last_mut()) {
+            section.1.push((
+                line[..eq].trim().to_string(),
+                line[eq + 1..].trim().to_string(),
+            ));
+        }
+    }
+    let value = |entries: &[(String, String)], key: &str| -> Option<String> {
+        entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut catalog = None;
+    // Track number to (session, control)
+    let mut entries: HashMap<i64, (u32, u8)> = HashMap::new();
+    // Session to lead-out LBA
+    let mut lead_outs = HashMap::new();
+    let mut tracks = Vec::new();
+
+    for (name, section) in &sections {
+        let upper = name.to_ascii_uppercase();
+        if upper == "DISC" {
+            catalog = value(section, "CATALOG");
+        } else if upper.starts_with("ENTRY ") {
+            let point = value(section, "Point").and_then(|p| parse_number(&p));
+            let session = value(section, "Session")
+                .and_then(|s| parse_number(&s))
+                .unwrap_or(1);
+            if point == Some(0xA2) {
+                if let Some(lba) = value(section, "PLBA").and_then(|p| parse_number(&p)) {
+                    lead_outs.insert(session as u32, lba);
+                }
+            } else if let Some(point @ 1..=99) = point {
+                let control = value(section, "Control")
+                    .and_then(|c| parse_number(&c))
+                    .unwrap_or(0);
+                entries.insert(point, (session as u32, control as u8));
+            }
+        } else if let Some(no) = upper.strip_prefix("TRACK ") {
+            let no: u32 = no
+                .trim()
+                .parse()
+                .map_err(|_| CueError::Parse(format!("bad CCD section [{}]", name)))?;
+            let mode = match value(section, "MODE").and_then(|m| parse_number(&m)) {
+                Some(0) => TrackMode::Audio,
+                Some(1) => TrackMode::Mode1_2352,
+                Some(2) => TrackMode::Mode2_2352,
+                other => {
+                    return Err(CueError::Parse(format!(
+                        "track {}: unknown MODE {:?}",
+                        no, other
+                    )))
+                }
+            };
+
+            let mut indices = Vec::new();
+            for (key, lba) in section {
+                let upper = key.to_ascii_uppercase();
+                if let Some(index) = upper.strip_prefix("INDEX") {
+                    let index: u32 = index
+                        .trim()
+                        .parse()
+                        .map_err(|_| CueError::Parse(format!("track {}: bad key {}", no, key)))?;
+                    let lba = parse_number(lba)
+                        .ok_or_else(|| CueError::Parse(format!("track {}: bad LBA {}", no, lba)))?;
+                    indices.push((index, lba));
+                }
+            }
+            indices.sort();
+            if !indices.iter().any(|(index, _)| *index == 1) {
+                return Err(CueError::Parse(format!("track {} has no INDEX 1", no)));
+            }
+
+            tracks.push(ImageTrack {
+                no,
+                mode,
+                control: if mode == TrackMode::Audio {
+                    0
+                } else {
+                    CONTROL_DATA
+                },
+                session: 1,
+                indices,
+                isrc: value(section, "ISRC"),
+            });
+        }
+    }
+
+    if tracks.is_empty() {
+        return Err(CueError::Parse("CCD has no [TRACK] sections".to_string()));
+    }
+    tracks.sort_by_key(|track| track.no);
+    for track in tracks.iter_mut() {
+        match entries.get(&i64::from(track.no)) {
+            Some(&(session, control)) => {
+                track.session = session;
+                track.control = control;
+            }
+            None => diagnostics.push(Diagnostic::new(
+                Some(track.no),
+                "no TOC entry, flags and session unknown",
+            )),
+        }
+    }
+
+    let mut cue = image_cue(image, tracks, &lead_outs, &mut diagnostics);
+    cue.catalog = catalog;
+    Ok((cue, diagnostics))
+}
+
+const MDS_SIGNATURE: &[u8] = b"MEDIA DESCRIPTOR";
+
+fn le16(bytes: &[u8], offset: usize) -> Result<u16, CueError> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| CueError::Parse("MDS truncated".to_string()))
+}
+
+fn le32(bytes: &[u8], offset: usize) -> Result<u32, CueError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| CueError::Parse("MDS truncated".to_string()))
+}
+
+fn byte(bytes: &[u8], offset: usize) -> Result<u8, CueError> {
+    bytes
+        .get(offset)
+        .cloned()
+        .ok_or_else(|| CueError::Parse("MDS truncated".to_string()))
+}
+
+// Reads the name of a track's data file; `*.mdf` stands for the descriptor's own name.
+fn mds_file_name(bytes: &[u8], offset: usize, mds_file: &str) -> Result<String, CueError> {
+    let name_offset = le32(bytes, offset)? as usize;
+    let wide = byte(bytes, offset + 4)? != 0;
+    let rest = bytes
+        .get(name_offset..)
+        .ok_or_else(|| CueError::Parse("MDS truncated".to_string()))?;
+
+    let name = if wide {
+        let units: Vec<u16> = rest
+            .chunks(2)
+            .filter(|c| c.len() == 2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|u| *u != 0)
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        let len = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+        String::from_utf8_lossy(&rest[..len]).into_owned()
+    };
+
+    match name.strip_prefix('*') {
+        Some(extension) => {
+            let stem = Path::new(mds_file)
+                .with_extension("")
+                .to_string_lossy()
+                .into_owned();
+            Ok(format!("{}{}", stem, extension))
+        }
+        None => Ok(name),
+    }
+}
+
+/// Reads an Alcohol 120% `.mds` descriptor into a [`Cue`](../cue/struct.Cue.html). `mds_file` is
+/// the descriptor's own file name, which the usual `*.mdf` data file name refers to.
+///
+/// The data file is a single BINARY FILE. Track modes come from each track's mode and sector
+/// size (2048, 2336 or 2352 bytes). INDEX 01 is placed at the track's absolute start LBA, and
+/// INDEX 00 that many pregap sectors before it for tracks after the first. Flags come from the
+/// track's CONTROL field. On multi-session discs, tracks are grouped into
+/// [`sessions`](../cue/struct.Cue.html#structfield.sessions), whose lead-outs are measured from
+/// the session end addresses and left out of the image. Interleaved sub-channel data, which a
+/// BINARY FILE cannot describe, is reported as a diagnostic.
+///
+/// # Failures
+///
+/// Fails if the descriptor has no `MEDIA DESCRIPTOR` signature, is truncated, has no tracks or
+/// a track has an unsupported mode.
+pub fn from_mds(bytes: &[u8], mds_file: &str) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    if !bytes.starts_with(MDS_SIGNATURE) {
+        return Err(CueError::Parse(
+            "missing MEDIA DESCRIPTOR signature".to_string(),
+        ));
+    }
+
+    let mut diagnostics = Vec::new();
+    let session_count = le16(bytes, 0x14)? as usize;
+    let sessions_offset = le32(bytes, 0x50)? as usize;
+    let mut file = None;
+    let mut lead_outs = HashMap::new();
+    let mut tracks = Vec::new();
+
+    for s in 0..session_count {
+        let session = sessions_offset + s * 0x18;
+        let session_no = u32::from(le16(bytes, session + 0x08)?);
+        lead_outs.insert(session_no, i64::from(le32(bytes, session + 0x04)? as i32));
+        let block_count = byte(bytes, session + 0x0A)? as usize;
+        let blocks_offset = le32(bytes, session + 0x14)? as usize;
+
+        for b in 0..block_count {
+            let block = blocks_offset + b * 0x50;
+            let point = byte(bytes, block + 0x04)?;
+            if !(1..=99).contains(&point) {
+                continue;
+            }
+            let no = u32::from(point);
+            let mode_byte = byte(bytes, block)? & 0x0F;
+            let subchannel = byte(bytes, block + 0x01)?;
+            let control = byte(bytes, block + 0x02)? & 0x0F;
+            let sector_size = u64::from(le16(bytes, block + 0x10)?);
+            let start = i64::from(le32(bytes, block + 0x24)? as i32);
+
+            let data_size = if subchannel != 0 {
+                diagnostics.push(Diagnostic::new(
+                    Some(no),
+                    "interleaved sub-channel data cannot be described by a BINARY FILE",
+                ));
+                sector_size.saturating_sub(96)
+            } else {
+                sector_size
+            };
+            let mode = match (mode_byte, data_size) {
+                (0x09, _) => TrackMode::Audio,
+                (0x0A, 2048) => TrackMode::Mode1_2048,
+                (0x0A, 2352) => TrackMode::Mode1_2352,
+                (0x0B..=0x0D, 2048) => TrackMode::Mode2_2048,
+                (0x0B..=0x0D, 2324) => TrackMode::Mode2_2324,
+                (0x0B..=0x0D, 2336) => TrackMode::Mode2_2336,
+                (0x0B..=0x0D, 2352) => TrackMode::Mode2_2352,
+                _ => {
+                    return Err(CueError::Parse(format!(
+                        "track {}: unsupported mode {:#04x} with {}-byte sectors",
+                        no, mode_byte, sector_size
+                    )))
+                }
+            };
+
+            let extra_offset = le32(bytes, block + 0x0C)? as usize;
+            let pregap = if extra_offset != 0 && no > 1 {
+                i64::from(le32(bytes, extra_offset)?)
+            } else {
+                0
+            };
+            let mut indices = Vec::new();
+            if pregap > 0 {
+                indices.push((0, start - pregap));
+            }
+            indices.push((1, start));
+
+            if le32(bytes, block + 0x30)? > 0 {
+                let name_offset = le32(bytes, block + 0x34)? as usize;
+                let name = mds_file_name(bytes, name_offset, mds_file)?;
+                if file.as_ref().is_some_and(|file| *file != name) {
+                    diagnostics.push(Diagnostic::new(
+                        Some(no),
+                        &format!("data file {} differs from the first track's", name),
+                    ));
+                }
+                file.get_or_insert(name);
+            }
+
+            tracks.push(ImageTrack {
+                no,
+                mode,
+                control,
+                session: session_no,
+                indices,
+                isrc: None,
+            });
+        }
+    }
+
+    if tracks.is_empty() {
+        return Err(CueError::Parse("MDS has no tracks".to_string()));
+    }
+    tracks.sort_by_key(|track| track.no);
+    let file = file.unwrap_or_else(|| {
+        Path::new(mds_file)
+            .with_extension("mdf")
+            .to_string_lossy()
+            .into_owned()
+    });
+    let cue = image_cue(&file, tracks, &lead_outs, &mut diagnostics);
+    Ok((cue, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use util::duration_to_frames;
+
+    fn lbas(track: &Track) -> Vec<(String, u64)> {
+        track
+            .indices
+            .iter()
+            .map(|(index, time)| (index.clone(), duration_to_frames(time)))
+            .collect()
+    }
+
+    const CCD: &str = "[CloneCD]
+Version=3
+[Disc]
+TocEntries=5
+Sessions=2
+CATALOG=0724384260910
+[Session 1]
+PreGapMode=0
+[Session 2]
+PreGapMode=2
+[Entry 0]
+Session=1
+Point=0xa2
+Control=0x00
+PLBA=19477
+[Entry 1]
+Session=1
+Point=0x01
+Control=0x02
+PLBA=0
+[Entry 2]
+Session=1
+Point=0x02
+Control=0x01
+PLBA=19327
+[Entry 3]
+Session=2
+Point=0x03
+Control=0x04
+PLBA=30877
+[TRACK 1]
+MODE=0
+INDEX 1=0
+[TRACK 2]
+MODE=0
+ISRC=USRC17609839
+INDEX 0=19177
+INDEX 1=19327
+[TRACK 3]
+MODE=2
+INDEX 1=30877
+";
+
+    #[test]
+    fn test_ccd() {
+        let (cue, diagnostics) = from_ccd(CCD, "disc.img").unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(cue.catalog, Some("0724384260910".to_string()));
+        assert_eq!(cue.files[0].file, "disc.img");
+
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].flags, vec!["DCP".to_string()]);
+        assert_eq!(tracks[0].session, Some(1));
+        assert_eq!(tracks[1].flags, vec!["PRE".to_string()]);
+        assert_eq!(tracks[1].isrc, Some("USRC17609839".to_string()));
+        assert_eq!(
+            lbas(&tracks[1]),
+            vec![("00".to_string(), 19177), ("01".to_string(), 19327)]
+        );
+        assert_eq!(tracks[2].format, "MODE2/2352");
+        assert_eq!(tracks[2].session, Some(2));
+        assert_eq!(lbas(&tracks[2]), vec![("01".to_string(), 19477)]);
+
+        assert_eq!(cue.sessions.len(), 2);
+        assert_eq!(cue.sessions[0].lead_out, Some(Duration::new(90, 0)));
+        assert_eq!(
+            cue.track_lbas(&[]).unwrap(),
+            vec![Some(0), Some(19327), Some(30877)]
+        );
+    }
+
+    #[test]
+    fn test_ccd_invalid() {
+        assert!(from_ccd("[CloneCD]\nVersion=3\n", "a.img").is_err());
+        assert!(from_ccd("[TRACK 1]\nMODE=3\nINDEX 1=0\n", "a.img").is_err());
+        assert!(from_ccd("[TRACK 1]\nMODE=0\nINDEX 0=0\n", "a.img").is_err());
+
+        let (_, diagnostics) = from_ccd("[TRACK 1]\nMODE=0\nINDEX 1=0\n", "a.img").unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                Some(1),
+                "no TOC entry, flags and session unknown"
+            )]
+        );
+    }
+
+    // Builds an MDS descriptor with one session and the given (mode, control, point, sector
+    // size, start LBA, pregap) track blocks, plus an A0 block, all reading `*.mdf`.
+    fn mds(tracks: &[(u8, u8, u8, u16, u32, u32)]) -> Vec<u8> {
+        let blocks = tracks.len() + 1;
+        let sessions_offset = 0x58;
+        let blocks_offset = sessions_offset + 0x18;
+        let extra_offset = blocks_offset + blocks * 0x50;
+        let filename_offset = extra_offset + tracks.len() * 8;
+        let name_offset = filename_offset + 16;
+
+        let mut bytes = vec![0u8; name_offset];
+        bytes[..16].copy_from_slice(MDS_SIGNATURE);
+        bytes[0x14] = 1;
+        bytes[0x50..0x54].copy_from_slice(&(sessions_offset as u32).to_le_bytes());
+
+        let session = sessions_offset;
+        bytes[session + 0x08] = 1;
+        bytes[session + 0x0A] = blocks as u8;
+        bytes[session + 0x0B] = 1;
+        bytes[session + 0x14..session + 0x18]
+            .copy_from_slice(&(blocks_offset as u32).to_le_bytes());
+
+        // A0 block first, as in real descriptors
+        bytes[blocks_offset + 0x04] = 0xA0;
+        for (i, &(mode, control, point, size, start, pregap)) in tracks.iter().enumerate() {
+            let block = blocks_offset + (i + 1) * 0x50;
+            let extra = extra_offset + i * 8;
+            bytes[block] = mode;
+            bytes[block + 0x02] = 0x10 | control;
+            bytes[block + 0x04] = point;
+            bytes[block + 0x0C..block + 0x10].copy_from_slice(&(extra as u32).to_le_bytes());
+            bytes[block + 0x10..block + 0x12].copy_from_slice(&size.to_le_bytes());
+            bytes[block + 0x24..block + 0x28].copy_from_slice(&start.to_le_bytes());
+            bytes[block + 0x30] = 1;
+            bytes[block + 0x34..block + 0x38]
+                .copy_from_slice(&(filename_offset as u32).to_le_bytes());
+            bytes[extra..extra + 4].copy_from_slice(&pregap.to_le_bytes());
+        }
+        bytes[filename_offset..filename_offset + 4]
+            .copy_from_slice(&(name_offset as u32).to_le_bytes());
+        bytes.extend_from_slice(b"*.mdf\0");
+        bytes
+    }
+
+    #[test]
+    fn test_mds() {
+        let bytes = mds(&[
+            (0xAA, 0x04, 1, 2048, 0, 150),
+            (0xA9, 0x02, 2, 2352, 1000, 150),
+        ]);
+        let (cue, diagnostics) = from_mds(&bytes, "images/disc.mds").unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(cue.files[0].file, "images/disc.mdf");
+        assert_eq!(cue.files[0].format, "BINARY");
+
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].format, "MODE1/2048");
+        assert_eq!(lbas(&tracks[0]), vec![("01".to_string(), 0)]);
+        assert_eq!(tracks[1].format, "AUDIO");
+        assert_eq!(tracks[1].flags, vec!["DCP".to_string()]);
+        assert_eq!(
+            lbas(&tracks[1]),
+            vec![("00".to_string(), 850), ("01".to_string(), 1000)]
+        );
+        assert!(cue.sessions.is_empty());
+        assert_eq!(tracks[1].session, None);
+    }
+
+    #[test]
+    fn test_mds_subchannel_and_control() {
+        let mut bytes = mds(&[(0xAB, 0x00, 1, 2448, 0, 0)]);
+        // Sub-channel flag of the first track block
+        bytes[0x58 + 0x18 + 0x50 + 0x01] = 0x08;
+        let (cue, diagnostics) = from_mds(&bytes, "disc.mds").unwrap();
+        assert_eq!(cue.files[0].tracks[0].format, "MODE2/2352");
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_mds_invalid() {
+        assert!(from_mds(b"NOT A DESCRIPTOR", "disc.mds").is_err());
+        let bytes = mds(&[(0xA9, 0x00, 1, 2352, 0, 0)]);
+        assert!(from_mds(&bytes[..0x60], "disc.mds").is_err());
+        assert!(from_mds(&mds(&[(0xA9, 0x00, 1, 2048, 0, 0)])[..], "disc.mds").is_ok());
+        assert!(from_mds(&mds(&[(0xAA, 0x04, 1, 2000, 0, 0)]), "disc.mds").is_err());
+    }
+}
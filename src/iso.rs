@@ -0,0 +1,449 @@
+use std::io::{Read, Seek, Write};
+
+use cue::{Cue, PregapMode, TrackMode};
+use errors::CueError;
+use track_reader::{TrackReader, TrackReaderOptions};
+use util::duration_to_frames;
+
+const SYNC: [u8; 12] = [
+    0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00,
+];
+
+// Mode 2 sub-header submode bit marking Form 2 sectors
+const SUBMODE_FORM2: u8 = 0x20;
+
+const fn edc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut edc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            edc = if edc & 1 != 0 {
+                (edc >> 1) ^ 0xD801_8001
+            } else {
+                edc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = edc;
+        i += 1;
+    }
+    table
+}
+
+const EDC_TABLE: [u32; 256] = edc_table();
+
+// CD-ROM error detection code over `data`
+fn edc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |edc, byte| {
+        (edc >> 8) ^ EDC_TABLE[((edc ^ u32::from(*byte)) & 0xFF) as usize]
+    })
+}
+
+fn bcd(value: u64) -> u8 {
+    (((value / 10) << 4) | (value % 10)) as u8
+}
+
+/// Options for [`extract_iso`](fn.extract_iso.html).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IsoOptions {
+    /// LBA of the track's INDEX 01, which sector headers are checked against. Defaults to the
+    /// position of INDEX 01 in its FILE, which is right for single-file images and for the
+    /// first FILE of multi-file images.
+    pub start_lba: Option<u64>,
+    /// Check the EDC of each sector in addition to its header
+    pub check_edc: bool,
+}
+
+/// What is wrong with a sector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectorErrorKind {
+    /// The sync pattern is missing
+    Sync,
+    /// The header address (minute, second, frame) is not the expected one
+    Address([u8; 3]),
+    /// The header mode does not match the track's mode
+    Mode(u8),
+    /// A Mode 2 Form 2 sector, which has no 2048-byte user data; its first 2048 bytes are written
+    Form2,
+    /// The EDC does not match the sector's contents
+    Edc,
+    /// The image ends in the middle of the sector; the missing bytes are written as zeros
+    Truncated,
+}
+
+/// A bad sector found by [`extract_iso`](fn.extract_iso.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectorError {
+    /// LBA of the sector
+    pub lba: u64,
+    /// What is wrong with it
+    pub kind: SectorErrorKind,
+}
+
+/// Result of [`extract_iso`](fn.extract_iso.html).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IsoReport {
+    /// Number of 2048-byte sectors written
+    pub sectors: u64,
+    /// Bad sectors, in order; they are written as read
+    pub errors: Vec<SectorError>,
+}
+
+impl IsoReport {
+    /// Returns the number of bad sectors.
+    pub fn bad_sectors(&self) -> usize {
+        self.errors.len()
+    }
+}
+
+// Checks a sector and returns the offset of its 2048 bytes of user data.
+fn check_sector(
+    sector: &[u8],
+    mode: TrackMode,
+    lba: u64,
+    check_edc: bool,
+    errors: &mut Vec<SectorError>,
+) -> usize {
+    let mut error = |kind| errors.push(SectorError { lba, kind });
+
+    // MODE2/2336 sectors start at the sub-header
+    let header = match mode {
+        TrackMode::Mode2_2336 => None,
+        _ => Some(&sector[..16]),
+    };
+    if let Some(header) = header {
+        if header[..12] != SYNC {
+            error(SectorErrorKind::Sync);
+        }
+        let address = lba + 150;
+        let expected = [
+            bcd(address / 4500),
+            bcd(address / 75 % 60),
+            bcd(address % 75),
+        ];
+        if header[12..15] != expected {
+            error(SectorErrorKind::Address([
+                header[12], header[13], header[14],
+            ]));
+        }
+        let expected_mode = if mode == TrackMode::Mode1_2352 { 1 } else { 2 };
+        if header[15] != expected_mode {
+            error(SectorErrorKind::Mode(header[15]));
+        }
+    }
+
+    if mode == TrackMode::Mode1_2352 {
+        if check_edc
+            && edc(&sector[..2064])
+                != u32::from_le_bytes([sector[2064], sector[2065], sector[2066], sector[2067]])
+        {
+            error(SectorErrorKind::Edc);
+        }
+        return 16;
+    }
+
+    let subheader = if header.is_some() { 16 } else { 0 };
+    let data = subheader + 8;
+    if sector[subheader + 2] & SUBMODE_FORM2 != 0 {
+        error(SectorErrorKind::Form2);
+    } else if check_edc {
+        let stored = &sector[data + 2048..data + 2052];
+        if edc(&sector[subheader..data + 2048])
+            != u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]])
+        {
+            error(SectorErrorKind::Edc);
+        }
+    }
+    data
+}
+
+/// Writes the 2048-byte user data of each sector of the data track numbered `track` to
+/// `output`, reading the track's FILE from `image`, and returns the bad sectors found.
+///
+/// MODE1/2352 and MODE2/2352 sectors have their sync pattern, header address and mode checked;
+/// MODE2/2352 and MODE2/2336 tracks must be Mode 2 Form 1 (XA), and their sub-header and
+/// EDC/ECC are stripped as well. With `check_edc`, each raw sector's EDC is verified too.
+/// MODE1/2048 and MODE2/2048 tracks are already 2048 bytes per sector and are copied as is.
+/// The track runs from its INDEX 01 to the next track's pregap.
+///
+/// # Example
+///
+/// ```
+/// use rcue::iso::{extract_iso, IsoOptions};
+/// use rcue::parser::parse;
+/// use std::io::Cursor;
+///
+/// let sheet = "FILE \"a.bin\" BINARY\n  TRACK 01 MODE1/2048\n    INDEX 01 00:00:00\n";
+/// let cue = parse(&mut Cursor::new(sheet), true).unwrap();
+/// let mut iso = Vec::new();
+/// let report = extract_iso(&cue, 1, Cursor::new(vec![0u8; 4096]), &mut iso, &IsoOptions::default()).unwrap();
+/// assert_eq!(report.sectors, 2);
+/// assert_eq!(iso.len(), 4096);
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet has no track numbered `track`, the track is not a data track, or
+/// reading or writing fails.
+pub fn extract_iso<R: Read + Seek, W: Write>(
+    cue: &Cue,
+    track: u32,
+    image: R,
+    output: &mut W,
+    options: &IsoOptions,
+) -> Result<IsoReport, CueError> {
+    let (f, position) = cue
+        .files
+        .iter()
+        .enumerate()
+        .find_map(|(f, file)| {
+            file.tracks
+                .iter()
+                .position(|t| t.no.parse::<u32>().ok() == Some(track))
+                .map(|position| (f, position))
+        })
+        .ok_or_else(|| CueError::Parse(format!("no track {}", track)))?;
+    let file = &cue.files[f];
+    let entry = &file.tracks[position];
+
+    let mode = entry.mode();
+    let (sector_size, raw) = match mode {
+        Some(TrackMode::Mode1_2048) | Some(TrackMode::Mode2_2048) => (2048, false),
+        Some(TrackMode::Mode1_2352) | Some(TrackMode::Mode2_2352) => (2352, true),
+        Some(TrackMode::Mode2_2336) => (2336, true),
+        _ => {
+            return Err(CueError::Parse(format!(
+                "track {} is {}, not a data track",
+                track, entry.format
+            )))
+        }
+    };
+    let mode = mode.unwrap_or(TrackMode::Mode1_2048);
+    let start_lba = match options.start_lba {
+        Some(lba) => lba,
+        None => entry.index(1).map_or(0, |start| duration_to_frames(&start)),
+    };
+
+    let mut reader = TrackReader::new(
+        image,
+        file,
+        cue.files.get(f + 1),
+        position,
+        TrackReaderOptions {
+            pregap: PregapMode::Discard,
+            wav_header: false,
+        },
+    )?;
+
+    let mut report = IsoReport::default();
+    let mut sector = vec![0u8; sector_size];
+    loop {
+        let mut read = 0;
+        while read < sector_size {
+            match reader.read(&mut sector[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        if read == 0 {
+            break;
+        }
+
+        let lba = start_lba + report.sectors;
+        if read < sector_size {
+            report.errors.push(SectorError {
+                lba,
+                kind: SectorErrorKind::Truncated,
+            });
+            sector[read..].iter_mut().for_each(|b| *b = 0);
+        }
+
+        let data = if raw {
+            check_sector(&sector, mode, lba, options.check_edc, &mut report.errors)
+        } else {
+            0
+        };
+        output.write_all(&sector[data..data + 2048])?;
+        report.sectors += 1;
+
+        if read < sector_size {
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse;
+    use std::io::Cursor;
+
+    // Builds a raw sector at `lba` whose user data is filled with `fill`.
+    fn raw_sector(mode: TrackMode, lba: u64, fill: u8) -> Vec<u8> {
+        let mut sector = vec![0u8; 2352];
+        sector[..12].copy_from_slice(&SYNC);
+        let address = lba + 150;
+        sector[12] = bcd(address / 4500);
+        sector[13] = bcd(address / 75 % 60);
+        sector[14] = bcd(address % 75);
+        if mode == TrackMode::Mode1_2352 {
+            sector[15] = 1;
+            sector[16..2064].iter_mut().for_each(|b| *b = fill);
+            let edc = edc(&sector[..2064]);
+            sector[2064..2068].copy_from_slice(&edc.to_le_bytes());
+        } else {
+            sector[15] = 2;
+            sector[24..2072].iter_mut().for_each(|b| *b = fill);
+            let edc = edc(&sector[16..2072]);
+            sector[2072..2076].copy_from_slice(&edc.to_le_bytes());
+        }
+        sector
+    }
+
+    fn sheet(mode: &str) -> Cue {
+        let text = format!(
+            "FILE \"a.bin\" BINARY\n  TRACK 01 {}\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:00:03\n    INDEX 01 00:00:05\n",
+            mode
+        );
+        parse(&mut Cursor::new(text), true).unwrap()
+    }
+
+    fn options() -> IsoOptions {
+        IsoOptions {
+            start_lba: None,
+            check_edc: true,
+        }
+    }
+
+    #[test]
+    fn test_mode1() {
+        let mut image = Vec::new();
+        for lba in 0..3 {
+            image.extend(raw_sector(TrackMode::Mode1_2352, lba, lba as u8 + 1));
+        }
+        // Pregap and audio of track 02
+        image.extend(vec![0u8; 4 * 2352]);
+
+        let mut iso = Vec::new();
+        let report = extract_iso(
+            &sheet("MODE1/2352"),
+            1,
+            Cursor::new(image),
+            &mut iso,
+            &options(),
+        )
+        .unwrap();
+        assert_eq!(
+            report,
+            IsoReport {
+                sectors: 3,
+                errors: Vec::new()
+            }
+        );
+        assert_eq!(iso.len(), 3 * 2048);
+        assert!(iso[2048..4096].iter().all(|b| *b == 2));
+    }
+
+    #[test]
+    fn test_mode2_bad_sectors() {
+        let mut image = Vec::new();
+        image.extend(raw_sector(TrackMode::Mode2_2352, 0, 1));
+        // Wrong address
+        image.extend(raw_sector(TrackMode::Mode2_2352, 7, 2));
+        // Form 2 and a corrupted byte
+        let mut form2 = raw_sector(TrackMode::Mode2_2352, 2, 3);
+        form2[18] = SUBMODE_FORM2;
+        image.extend(form2);
+        image.extend(vec![0u8; 4 * 2352]);
+        image[100] ^= 0xFF;
+
+        let mut iso = Vec::new();
+        let report = extract_iso(
+            &sheet("MODE2/2352"),
+            1,
+            Cursor::new(image),
+            &mut iso,
+            &IsoOptions {
+                start_lba: Some(0),
+                check_edc: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(report.sectors, 3);
+        assert_eq!(report.bad_sectors(), 3);
+        assert_eq!(
+            report.errors,
+            vec![
+                SectorError {
+                    lba: 0,
+                    kind: SectorErrorKind::Edc
+                },
+                SectorError {
+                    lba: 1,
+                    kind: SectorErrorKind::Address([0x00, 0x02, 0x07])
+                },
+                SectorError {
+                    lba: 2,
+                    kind: SectorErrorKind::Form2
+                },
+            ]
+        );
+        assert!(iso[2048..4096].iter().all(|b| *b == 2));
+    }
+
+    #[test]
+    fn test_start_lba_and_truncation() {
+        let mut image = raw_sector(TrackMode::Mode1_2352, 1000, 1);
+        image.extend(&raw_sector(TrackMode::Mode1_2352, 1001, 2)[..100]);
+        let cue = parse(
+            &mut Cursor::new(
+                "FILE \"a.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+            ),
+            true,
+        )
+        .unwrap();
+
+        let mut iso = Vec::new();
+        let report = extract_iso(
+            &cue,
+            1,
+            Cursor::new(image.clone()),
+            &mut iso,
+            &IsoOptions {
+                start_lba: Some(1000),
+                check_edc: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(report.sectors, 2);
+        assert_eq!(
+            report.errors,
+            vec![SectorError {
+                lba: 1001,
+                kind: SectorErrorKind::Truncated
+            }]
+        );
+        assert_eq!(iso.len(), 4096);
+
+        let report = extract_iso(&cue, 1, Cursor::new(image), &mut Vec::new(), &options()).unwrap();
+        assert_eq!(
+            report.errors[0],
+            SectorError {
+                lba: 0,
+                kind: SectorErrorKind::Address([0x00, 0x15, 0x25])
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_track() {
+        let cue = sheet("MODE1/2352");
+        let image = || Cursor::new(vec![0u8; 2352]);
+        assert!(extract_iso(&cue, 2, image(), &mut Vec::new(), &options()).is_err());
+        assert!(extract_iso(&cue, 3, image(), &mut Vec::new(), &options()).is_err());
+    }
+}
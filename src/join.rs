@@ -0,0 +1,298 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use cue::{Cue, CueFile};
+use errors::CueError;
+use util::{duration_to_frames, frames_to_duration, FRAMES_PER_SECOND};
+use wav::{copy_pcm, write_header, PcmSource};
+
+/// Rewrites a multi-file `cue` into a single-file sheet referencing `file_name` (type `WAVE`),
+/// given the length in CD frames of each FILE in `cue.files`.
+///
+/// INDEX timestamps are shifted by the cumulative length of the preceding files. Indices stored
+/// in the previous FILE ([`Track::previous_file_indices`](../cue/struct.Track.html#structfield.previous_file_indices))
+/// are shifted by the offset of that file, so pregaps keep their position. FILE comments are
+/// merged in order.
+///
+/// # Example
+///
+/// ```
+/// use rcue::join::joined_cue;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+/// let joined = joined_cue(&cue, &[19327, 30000], "Loveless.wav").unwrap();
+/// assert_eq!(joined.files.len(), 1);
+/// assert_eq!(joined.files[0].tracks[1].indices.len(), 2);
+/// ```
+///
+/// # Failures
+///
+/// Fails if the number of lengths does not match the number of files.
+pub fn joined_cue(cue: &Cue, file_lengths: &[u64], file_name: &str) -> Result<Cue, CueError> {
+    if file_lengths.len() != cue.files.len() {
+        return Err(CueError::Parse(format!(
+            "expected {} file lengths, got {}",
+            cue.files.len(),
+            file_lengths.len()
+        )));
+    }
+
+    fn shift(indices: &[(String, Duration)], offset: u64) -> Vec<(String, Duration)> {
+        indices
+            .iter()
+            .map(|(idx, time)| {
+                (
+                    idx.clone(),
+                    frames_to_duration(duration_to_frames(time) + offset),
+                )
+            })
+            .collect()
+    }
+
+    let mut joined = CueFile::new(file_name, "WAVE");
+    let mut offset = 0;
+    let mut previous_offset = 0;
+
+    for (file, length) in cue.files.iter().zip(file_lengths) {
+        joined.comments.extend(file.comments.iter().cloned());
+
+        for track in &file.tracks {
+            let mut track = track.clone();
+            let mut indices = shift(&track.previous_file_indices, previous_offset);
+            indices.extend(shift(&track.indices, offset));
+            track.indices = indices;
+            track.previous_file_indices.clear();
+            joined.tracks.push(track);
+        }
+
+        previous_offset = offset;
+        offset += length;
+    }
+
+    let mut result = cue.clone();
+    result.files = vec![joined];
+    Ok(result)
+}
+
+/// Concatenates the audio of every file in `cue` into a single WAVE written to `output`, and
+/// returns the rewritten single-file sheet (see [`joined_cue`](fn.joined_cue.html)).
+///
+/// `sources` are the readers for each of `cue.files`, in order. All sources must share the same
+/// channel count, sample rate and bit depth. Big endian (`MOTOROLA`) sources are converted to
+/// little endian. When file lengths are not a whole number of CD frames, the start of each file
+/// in the joined audio is rounded to the nearest frame when shifting INDEX timestamps.
+///
+/// # Failures
+///
+/// Fails if the number of sources does not match the number of files, if a source can not be
+/// read or has an unsupported type, or if the sources have different sample layouts.
+pub fn join_sources<R: Read + Seek, W: Write>(
+    cue: &Cue,
+    sources: &mut [R],
+    output: &mut W,
+    file_name: &str,
+) -> Result<Cue, CueError> {
+    if sources.len() != cue.files.len() {
+        return Err(CueError::Parse(format!(
+            "expected {} sources, got {}",
+            cue.files.len(),
+            sources.len()
+        )));
+    }
+
+    let mut pcms = Vec::new();
+    for (file, source) in cue.files.iter().zip(sources.iter_mut()) {
+        pcms.push(PcmSource::open(source, &file.format)?);
+    }
+
+    let format = match pcms.first() {
+        Some(pcm) => pcm.format,
+        None => return Err(CueError::Parse("no FILE to join".to_string())),
+    };
+    let compatible = |pcm: &PcmSource| {
+        pcm.format.channels == format.channels
+            && pcm.format.sample_rate == format.sample_rate
+            && pcm.format.bits_per_sample == format.bits_per_sample
+    };
+    if !pcms.iter().all(compatible) {
+        return Err(CueError::Parse(
+            "files to join have different sample formats".to_string(),
+        ));
+    }
+
+    // Each file's start is rounded from the samples before it, so rounding errors do not add up
+    let rate = u64::from(format.sample_rate);
+    let mut lengths = Vec::new();
+    let mut samples = 0;
+    let mut start = 0;
+    for pcm in &pcms {
+        samples += pcm.data_len / format.block_align();
+        let end = (samples * FRAMES_PER_SECOND + rate / 2) / rate;
+        lengths.push(end - start);
+        start = end;
+    }
+    let total = samples * format.block_align();
+
+    let mut out_format = format;
+    out_format.big_endian = false;
+    write_header(output, &out_format, total)?;
+    for (pcm, source) in pcms.iter().zip(sources.iter_mut()) {
+        source.seek(SeekFrom::Start(pcm.data_offset))?;
+        let len = pcm.data_len - pcm.data_len % format.block_align();
+        copy_pcm(source, output, len, &pcm.format)?;
+    }
+
+    joined_cue(cue, &lengths, file_name)
+}
+
+/// Joins the files referenced by `cue` into the WAVE file at `output`, and returns the rewritten
+/// single-file sheet referencing the file name of `output`.
+///
+/// FILE paths are resolved relative to `cue_dir`. See [`join_sources`](fn.join_sources.html).
+///
+/// # Failures
+///
+/// Fails if a referenced file can not be read, or if the files can not be joined.
+pub fn join(cue: &Cue, cue_dir: &Path, output: &Path) -> Result<Cue, CueError> {
+    let mut sources = Vec::new();
+    for file in &cue.files {
+        sources.push(File::open(cue_dir.join(file.file.replace('\\', "/")))?);
+    }
+
+    let file_name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut writer = BufWriter::new(File::create(output)?);
+    let joined = join_sources(cue, &mut sources, &mut writer, &file_name)?;
+    writer.flush()?;
+
+    Ok(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse;
+    use std::fs;
+    use std::io::Cursor;
+    use test_util::temp_dir;
+    use wav::{header, read_header, PcmFormat};
+
+    const SHEET: &str = r#"FILE "01.wav" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Two"
+    INDEX 00 00:00:03
+FILE "02.wav" WAVE
+    INDEX 01 00:00:00
+  TRACK 03 AUDIO
+    INDEX 01 00:00:02
+"#;
+
+    fn wav(frames: u64, value: u8) -> Vec<u8> {
+        let len = PcmFormat::cd().frames_to_bytes(frames);
+        let mut wav = header(&PcmFormat::cd(), len);
+        wav.extend(vec![value; len as usize]);
+        wav
+    }
+
+    fn frames(indices: &[(String, Duration)]) -> Vec<(&str, u64)> {
+        indices
+            .iter()
+            .map(|(idx, time)| (idx.as_str(), duration_to_frames(time)))
+            .collect()
+    }
+
+    #[test]
+    fn test_joined_cue_carries_gaps() {
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let joined = joined_cue(&cue, &[5, 4], "joined.wav").unwrap();
+        assert_eq!(joined.files.len(), 1);
+        assert_eq!(joined.files[0].file, "joined.wav");
+
+        let tracks = &joined.files[0].tracks;
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(frames(&tracks[0].indices), vec![("01", 0)]);
+        assert_eq!(frames(&tracks[1].indices), vec![("00", 3), ("01", 5)]);
+        assert_eq!(frames(&tracks[2].indices), vec![("01", 7)]);
+        assert_eq!(tracks[1].title, Some("Two".to_string()));
+        assert!(tracks[1].previous_file_indices.is_empty());
+    }
+
+    #[test]
+    fn test_joined_cue_length_mismatch() {
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        assert!(joined_cue(&cue, &[5], "joined.wav").is_err());
+    }
+
+    #[test]
+    fn test_join_sources() {
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let mut sources = vec![Cursor::new(wav(5, 1)), Cursor::new(wav(4, 2))];
+        let mut output = Cursor::new(Vec::new());
+        let joined = join_sources(&cue, &mut sources, &mut output, "joined.wav").unwrap();
+        assert_eq!(frames(&joined.files[0].tracks[2].indices), vec![("01", 7)]);
+
+        let source = read_header(&mut output).unwrap();
+        assert_eq!(source.data_len, 9 * 2352);
+        let data = output.into_inner();
+        assert_eq!(data[44 + 5 * 2352 - 1], 1);
+        assert_eq!(data[44 + 5 * 2352], 2);
+    }
+
+    #[test]
+    fn test_join_sources_rounds_offsets_once() {
+        let sheet = "FILE \"1.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+                     FILE \"2.wav\" WAVE\n  TRACK 02 AUDIO\n    INDEX 01 00:00:00\n\
+                     FILE \"3.wav\" WAVE\n  TRACK 03 AUDIO\n    INDEX 01 00:00:00\n";
+        let cue = parse(&mut Cursor::new(sheet), true).unwrap();
+        // 5.5 frames each: the files start at 5.5 and 11 frames
+        let half = |value| {
+            let len = 5 * 2352 + 294 * 4;
+            let mut wav = header(&PcmFormat::cd(), len);
+            wav.extend(vec![value; len as usize]);
+            Cursor::new(wav)
+        };
+        let mut sources = vec![half(1), half(2), half(3)];
+        let mut output = Cursor::new(Vec::new());
+        let joined = join_sources(&cue, &mut sources, &mut output, "joined.wav").unwrap();
+        let starts: Vec<u64> = joined.files[0]
+            .tracks
+            .iter()
+            .map(|track| duration_to_frames(&track.indices[0].1))
+            .collect();
+        assert_eq!(starts, vec![0, 6, 11]);
+    }
+
+    #[test]
+    fn test_join_mismatched_formats() {
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let mono = PcmFormat {
+            channels: 1,
+            ..PcmFormat::cd()
+        };
+        let mut sources = vec![Cursor::new(wav(5, 1)), Cursor::new(header(&mono, 0))];
+        let mut output = Vec::new();
+        assert!(join_sources(&cue, &mut sources, &mut output, "joined.wav").is_err());
+    }
+
+    #[test]
+    fn test_join_files() {
+        let dir = temp_dir("join_files");
+        fs::write(dir.join("01.wav"), wav(5, 1)).unwrap();
+        fs::write(dir.join("02.wav"), wav(4, 2)).unwrap();
+
+        let cue = parse(&mut Cursor::new(SHEET), true).unwrap();
+        let joined = join(&cue, &dir, &dir.join("image.wav")).unwrap();
+        assert_eq!(joined.files[0].file, "image.wav");
+        assert_eq!(
+            fs::metadata(dir.join("image.wav")).unwrap().len(),
+            44 + 9 * 2352
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use cue::{Cue, CueFile, Session, Track};
+use util::{duration_to_frames, frames_to_timestamp};
+
+/// Quotes and escapes `s` as a JSON string.
+///
+/// # Example
+///
+/// ```
+/// use rcue::json::escape;
+///
+/// assert_eq!(escape("say \"hi\"\n"), r#""say \"hi\"\n""#);
+/// ```
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn string(value: Option<&String>) -> String {
+    value.map_or("null".to_string(), |s| escape(s))
+}
+
+fn number(value: Option<u32>) -> String {
+    value.map_or("null".to_string(), |n| n.to_string())
+}
+
+fn time(value: Option<Duration>) -> String {
+    value.map_or("null".to_string(), |d| {
+        escape(&frames_to_timestamp(duration_to_frames(&d)))
+    })
+}
+
+fn array<T, F: Fn(&T, &str) -> String>(items: &[T], indent: &str, item: F) -> String {
+    if items.is_empty() {
+        return "[]".to_string();
+    }
+    let inner = format!("{}  ", indent);
+    let items: Vec<String> = items
+        .iter()
+        .map(|i| format!("{}{}", inner, item(i, &inner)))
+        .collect();
+    format!("[\n{}\n{}]", items.join(",\n"), indent)
+}
+
+fn object(fields: &[(&str, String)], indent: &str) -> String {
+    let inner = format!("{}  ", indent);
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}{}: {}", inner, escape(key), value))
+        .collect();
+    format!("{{\n{}\n{}}}", fields.join(",\n"), indent)
+}
+
+fn comments(comments: &[(String, String)], indent: &str) -> String {
+    array(comments, indent, |(key, value), _| {
+        format!("[{}, {}]", escape(key), escape(value))
+    })
+}
+
+fn strings(values: &[String], indent: &str) -> String {
+    array(values, indent, |value, _| escape(value))
+}
+
+fn indices(indices: &[(String, Duration)], indent: &str) -> String {
+    array(indices, indent, |(no, time), _| {
+        let frames = duration_to_frames(time);
+        format!(
+            "{{\"no\": {}, \"time\": {}, \"frames\": {}}}",
+            escape(no),
+            escape(&frames_to_timestamp(frames)),
+            frames
+        )
+    })
+}
+
+fn track(track: &Track, indent: &str) -> String {
+    let inner = format!("{}  ", indent);
+    object(
+        &[
+            ("no", escape(&track.no)),
+            ("format", escape(&track.format)),
+            ("session", number(track.session)),
+            ("title", string(track.title.as_ref())),
+            ("performer", string(track.performer.as_ref())),
+            ("songwriter", string(track.songwriter.as_ref())),
+            ("isrc", string(track.isrc.as_ref())),
+            ("flags", strings(&track.flags, &inner)),
+            ("pregap", time(track.pregap)),
+            ("postgap", time(track.postgap)),
+            (
+                "previous_file_indices",
+                indices(&track.previous_file_indices, &inner),
+            ),
+            ("indices", indices(&track.indices, &inner)),
+            ("comments", comments(&track.comments, &inner)),
+            ("unknown", strings(&track.unknown, &inner)),
+        ],
+        indent,
+    )
+}
+
+fn file(file: &CueFile, indent: &str) -> String {
+    let inner = format!("{}  ", indent);
+    object(
+        &[
+            ("file", escape(&file.file)),
+            ("format", escape(&file.format)),
+            ("comments", comments(&file.comments, &inner)),
+            ("tracks", array(&file.tracks, &inner, track)),
+        ],
+        indent,
+    )
+}
+
+fn session(session: &Session) -> String {
+    format!(
+        "{{\"no\": {}, \"lead_in\": {}, \"pregap\": {}, \"lead_out\": {}}}",
+        session.no,
+        time(session.lead_in),
+        time(session.pregap),
+        time(session.lead_out)
+    )
+}
+
+/// Serializes `cue` as pretty-printed JSON.
+///
+/// Every field of the model is written, `null` when absent. Durations are written as
+/// `mm:ss:ff` timestamps; indices also have their position in CD frames.
+///
+/// # Example
+///
+/// ```
+/// use rcue::json::to_json;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let json = to_json(&cue);
+/// assert!(json.contains(r#""title": "Loveless""#));
+/// assert!(json.contains(r#"{"no": "01", "time": "04:17:52", "frames": 19327}"#));
+/// ```
+pub fn to_json(cue: &Cue) -> String {
+    let indent = "  ";
+    let mut out = object(
+        &[
+            ("title", string(cue.title.as_ref())),
+            ("performer", string(cue.performer.as_ref())),
+            ("songwriter", string(cue.songwriter.as_ref())),
+            ("catalog", string(cue.catalog.as_ref())),
+            ("cd_text_file", string(cue.cd_text_file.as_ref())),
+            (
+                "original_media_type",
+                string(cue.original_media_type.as_ref()),
+            ),
+            ("comments", comments(&cue.comments, indent)),
+            ("sessions", array(&cue.sessions, indent, |s, _| session(s))),
+            ("files", array(&cue.files, indent, file)),
+            ("unknown", strings(&cue.unknown, indent)),
+        ],
+        "",
+    );
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\\b\t\u{1}"), "\"a\\\\b\\t\\u0001\"");
+        assert_eq!(escape("マジコ"), "\"マジコ\"");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let cue = parse_from_file("test/fixtures/sessions.cue", true).unwrap();
+        let json = to_json(&cue);
+        assert!(json.starts_with("{\n  \"title\": "));
+        assert!(json.contains("\"sessions\": [\n    {\"no\": 1, \"lead_in\": null"));
+        assert!(json.contains("\"session\": 2,"));
+        assert!(json.ends_with("}\n"));
+
+        let empty = to_json(&Cue::new());
+        assert!(empty.contains("\"files\": [],"));
+        assert!(empty.contains("\"title\": null,"));
+    }
+}
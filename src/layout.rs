@@ -0,0 +1,412 @@
+use std::time::Duration;
+
+use cue::{Cue, CueFile, Track};
+use errors::CueError;
+use util::{duration_to_frames, fill_template, frames_to_duration};
+
+/// File layouts of a ripped disc, as produced by Exact Audio Copy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// One FILE for the whole disc, with pregaps as INDEX 00
+    SingleFile,
+    /// One FILE per track, with each pregap appended to the end of the previous track's FILE
+    GapsAppended,
+    /// One FILE per track without pregap audio, with pregaps written as PREGAP ("noncompliant")
+    GapsLeftOut,
+}
+
+/// Detects the [`Layout`](enum.Layout.html) of `cue`.
+///
+/// Sheets with one FILE are [`SingleFile`](enum.Layout.html#variant.SingleFile). Multi-file
+/// sheets with pregaps at the end of the previous FILE are
+/// [`GapsAppended`](enum.Layout.html#variant.GapsAppended), and those with PREGAP commands but
+/// no pregap audio are [`GapsLeftOut`](enum.Layout.html#variant.GapsLeftOut). Multi-file sheets
+/// without any pregap could be either and return `None`, as do sheets with pregaps at the start
+/// of their own track's FILE.
+///
+/// # Example
+///
+/// ```
+/// use rcue::layout::{detect, Layout};
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+/// assert_eq!(detect(&cue), Some(Layout::GapsAppended));
+/// ```
+pub fn detect(cue: &Cue) -> Option<Layout> {
+    if cue.files.len() == 1 {
+        return Some(Layout::SingleFile);
+    }
+
+    let tracks = || cue.files.iter().flat_map(|file| file.tracks.iter());
+    let appended = cue.files.iter().any(|file| {
+        file.tracks
+            .first()
+            .is_some_and(|first| !first.previous_file_indices.is_empty())
+    });
+    // A pregap inside its own track's FILE, other than the first track's (hidden track one audio)
+    let prepended = tracks()
+        .skip(1)
+        .any(|track| track.index(0).is_some() && track.index(1).is_some());
+
+    if appended && !prepended {
+        Some(Layout::GapsAppended)
+    } else if !appended && !prepended && tracks().any(|track| track.pregap.is_some()) {
+        Some(Layout::GapsLeftOut)
+    } else {
+        None
+    }
+}
+
+/// Options for [`convert`](fn.convert.html).
+#[derive(Clone, Debug)]
+pub struct LayoutOptions {
+    /// Filename pattern of the target FILEs, filled by
+    /// [`fill_template`](../util/fn.fill_template.html).
+    ///
+    /// Multi-file layouts fill `{no}`, `{title}` and `{performer}` from each track (`{title}`
+    /// falls back to `Track {no}`). [`SingleFile`](enum.Layout.html#variant.SingleFile) fills
+    /// `{title}` and `{performer}` from the disc.
+    pub pattern: String,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            pattern: "{no} - {title}.wav".to_string(),
+        }
+    }
+}
+
+// A track placed on the concatenated audio of all source files.
+struct Placed<'a> {
+    track: &'a Track,
+    source: usize,
+    indices: Vec<(String, u64)>,
+}
+
+impl<'a> Placed<'a> {
+    fn index(&self, no: u32) -> Option<u64> {
+        self.indices
+            .iter()
+            .find(|(idx, _)| idx.parse::<u32>().ok() == Some(no))
+            .map(|(_, frame)| *frame)
+    }
+
+    fn start(&self) -> Option<u64> {
+        self.index(1)
+            .or_else(|| self.indices.first().map(|(_, frame)| *frame))
+    }
+
+    fn pregap_start(&self) -> Option<u64> {
+        self.index(0).or_else(|| self.start())
+    }
+}
+
+/// Converts `cue` to another file [`Layout`](enum.Layout.html), keeping every track at the same
+/// absolute position on the disc. Only the sheet is rewritten; no audio is touched.
+///
+/// The source layout is not needed: indices of each FILE, including
+/// [`Track::previous_file_indices`](../cue/struct.Track.html#structfield.previous_file_indices),
+/// are placed on the concatenated audio using `file_lengths`, the length of each source FILE in
+/// CD frames. The length of the last FILE may be omitted.
+///
+/// PREGAP commands (silence that is not in any file) are kept. Converting to
+/// [`GapsLeftOut`](enum.Layout.html#variant.GapsLeftOut) turns INDEX 00 pregaps into PREGAP
+/// commands, as the pregap audio is not part of the target files.
+///
+/// # Example
+///
+/// ```
+/// use rcue::layout::{convert, Layout, LayoutOptions};
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+/// let options = LayoutOptions { pattern: "{performer} - {title}.wav".to_string() };
+/// let single = convert(&cue, &[19327], Layout::SingleFile, &options).unwrap();
+/// assert_eq!(single.files[0].file, "My Bloody Valentine - Loveless.wav");
+/// assert_eq!(single.files[0].tracks[1].indices.len(), 2);
+/// ```
+///
+/// # Failures
+///
+/// Fails if fewer file lengths than needed are given, or if a track has no INDEX.
+pub fn convert(
+    cue: &Cue,
+    file_lengths: &[u64],
+    to: Layout,
+    options: &LayoutOptions,
+) -> Result<Cue, CueError> {
+    if file_lengths.len() + 1 < cue.files.len() {
+        return Err(CueError::Parse(format!(
+            "expected at least {} file lengths, got {}",
+            cue.files.len() - 1,
+            file_lengths.len()
+        )));
+    }
+
+    fn place(indices: &[(String, Duration)], offset: u64) -> Vec<(String, u64)> {
+        indices
+            .iter()
+            .map(|(idx, time)| (idx.clone(), offset + duration_to_frames(time)))
+            .collect()
+    }
+
+    let mut placed = Vec::new();
+    let mut offset = 0;
+    let mut previous_offset = 0;
+    for (source, file) in cue.files.iter().enumerate() {
+        for track in &file.tracks {
+            let mut indices = place(&track.previous_file_indices, previous_offset);
+            indices.extend(place(&track.indices, offset));
+            if indices.is_empty() {
+                return Err(CueError::Parse(format!("TRACK {} has no INDEX", track.no)));
+            }
+            placed.push(Placed {
+                track,
+                source,
+                indices,
+            });
+        }
+        previous_offset = offset;
+        offset += file_lengths.get(source).cloned().unwrap_or(0);
+    }
+
+    let mut converted = cue.clone();
+    converted.files = match to {
+        Layout::SingleFile => vec![single_file(cue, &placed, options)],
+        Layout::GapsAppended | Layout::GapsLeftOut => {
+            multi_file(cue, &placed, to == Layout::GapsLeftOut, options)
+        }
+    };
+    Ok(converted)
+}
+
+fn relative(indices: &[(String, u64)], origin: u64) -> Vec<(String, Duration)> {
+    indices
+        .iter()
+        .map(|(idx, frame)| {
+            (
+                idx.clone(),
+                frames_to_duration(frame.saturating_sub(origin)),
+            )
+        })
+        .collect()
+}
+
+fn single_file(cue: &Cue, placed: &[Placed], options: &LayoutOptions) -> CueFile {
+    let name = fill_template(
+        &options.pattern,
+        &[
+            ("title", cue.title.as_ref().map_or("", |s| s.as_str())),
+            (
+                "performer",
+                cue.performer.as_ref().map_or("", |s| s.as_str()),
+            ),
+        ],
+    );
+    let format = cue.files.first().map_or("WAVE", |f| f.format.as_str());
+
+    let mut file = CueFile::new(&name, format);
+    for source in &cue.files {
+        file.comments.extend(source.comments.iter().cloned());
+    }
+    for p in placed {
+        let mut track = p.track.clone();
+        track.indices = relative(&p.indices, 0);
+        track.previous_file_indices.clear();
+        file.tracks.push(track);
+    }
+    file
+}
+
+fn multi_file(
+    cue: &Cue,
+    placed: &[Placed],
+    gaps_left_out: bool,
+    options: &LayoutOptions,
+) -> Vec<CueFile> {
+    let mut files: Vec<CueFile> = Vec::new();
+    let mut commented = vec![false; cue.files.len()];
+    let mut previous_origin = 0;
+
+    for (i, p) in placed.iter().enumerate() {
+        // The first file also holds audio before the first track's INDEX 01 unless gaps are
+        // left out; every other file starts at its track's INDEX 01.
+        let start = p.start().unwrap_or(0);
+        let origin = if i == 0 && !gaps_left_out {
+            p.pregap_start().unwrap_or(0)
+        } else {
+            start
+        };
+
+        let mut track = p.track.clone();
+        let (before, after): (Vec<_>, Vec<_>) = p
+            .indices
+            .iter()
+            .cloned()
+            .partition(|(_, frame)| *frame < origin);
+        track.indices = relative(&after, origin);
+        track.previous_file_indices.clear();
+
+        if gaps_left_out {
+            let gap = start.saturating_sub(p.pregap_start().unwrap_or(start));
+            let silence = track.pregap.map_or(0, |pregap| duration_to_frames(&pregap));
+            if gap + silence > 0 {
+                track.pregap = Some(frames_to_duration(gap + silence));
+            }
+        } else if i > 0 {
+            track.previous_file_indices = relative(&before, previous_origin);
+        }
+
+        let title = track
+            .title
+            .clone()
+            .unwrap_or_else(|| format!("Track {}", track.no));
+        let name = fill_template(
+            &options.pattern,
+            &[
+                ("no", &track.no),
+                ("title", &title),
+                (
+                    "performer",
+                    track.performer.as_ref().map_or("", |s| s.as_str()),
+                ),
+            ],
+        );
+
+        let mut file = CueFile::new(&name, &cue.files[p.source].format);
+        if !commented[p.source] {
+            file.comments = cue.files[p.source].comments.clone();
+            commented[p.source] = true;
+        }
+        file.tracks.push(track);
+        files.push(file);
+        previous_origin = origin;
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{parse, parse_from_file};
+    use std::io::Cursor;
+
+    const SINGLE: &str = r#"TITLE "Disc"
+FILE "image.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "One"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Two"
+    INDEX 00 00:00:10
+    INDEX 01 00:00:12
+  TRACK 03 AUDIO
+    TITLE "Three"
+    PREGAP 00:00:05
+    INDEX 01 00:00:20
+    INDEX 02 00:00:22
+"#;
+
+    fn frames(indices: &[(String, Duration)]) -> Vec<(&str, u64)> {
+        indices
+            .iter()
+            .map(|(idx, time)| (idx.as_str(), duration_to_frames(time)))
+            .collect()
+    }
+
+    fn single() -> Cue {
+        parse(&mut Cursor::new(SINGLE), true).unwrap()
+    }
+
+    #[test]
+    fn test_single_to_gaps_appended() {
+        let cue = convert(
+            &single(),
+            &[],
+            Layout::GapsAppended,
+            &LayoutOptions::default(),
+        )
+        .unwrap();
+        let names: Vec<&str> = cue.files.iter().map(|f| f.file.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["01 - One.wav", "02 - Two.wav", "03 - Three.wav"]
+        );
+
+        let two = &cue.files[1].tracks[0];
+        assert_eq!(frames(&two.previous_file_indices), vec![("00", 10)]);
+        assert_eq!(frames(&two.indices), vec![("01", 0)]);
+
+        let three = &cue.files[2].tracks[0];
+        assert!(three.previous_file_indices.is_empty());
+        assert_eq!(frames(&three.indices), vec![("01", 0), ("02", 2)]);
+        assert_eq!(three.pregap, Some(frames_to_duration(5)));
+    }
+
+    #[test]
+    fn test_single_to_gaps_left_out() {
+        let cue = convert(
+            &single(),
+            &[],
+            Layout::GapsLeftOut,
+            &LayoutOptions::default(),
+        )
+        .unwrap();
+        let two = &cue.files[1].tracks[0];
+        assert_eq!(frames(&two.indices), vec![("01", 0)]);
+        assert_eq!(two.pregap, Some(frames_to_duration(2)));
+        assert_eq!(cue.files[0].tracks[0].pregap, None);
+    }
+
+    #[test]
+    fn test_round_trip_through_gaps_appended() {
+        let options = LayoutOptions::default();
+        let multi = convert(&single(), &[], Layout::GapsAppended, &options).unwrap();
+        let options = LayoutOptions {
+            pattern: "{title}.wav".to_string(),
+        };
+        let back = convert(&multi, &[12, 8], Layout::SingleFile, &options).unwrap();
+        assert_eq!(back.files.len(), 1);
+        assert_eq!(back.files[0].file, "Disc.wav");
+        assert_eq!(back.files[0].tracks, single().files[0].tracks);
+    }
+
+    #[test]
+    fn test_gaps_appended_fixture() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        let single = convert(
+            &cue,
+            &[19327],
+            Layout::SingleFile,
+            &LayoutOptions::default(),
+        )
+        .unwrap();
+        let loomer = &single.files[0].tracks[1];
+        assert_eq!(frames(&loomer.indices), vec![("00", 19177), ("01", 19327)]);
+    }
+
+    #[test]
+    fn test_missing_file_lengths() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert!(convert(&cue, &[], Layout::SingleFile, &LayoutOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        let options = LayoutOptions::default();
+        assert_eq!(detect(&single()), Some(Layout::SingleFile));
+        for layout in &[Layout::GapsAppended, Layout::GapsLeftOut] {
+            let cue = convert(&single(), &[], *layout, &options).unwrap();
+            assert_eq!(detect(&cue), Some(*layout));
+        }
+
+        let mut prepended = convert(&single(), &[], Layout::GapsLeftOut, &options).unwrap();
+        prepended.files[1].tracks[0]
+            .indices
+            .insert(0, ("00".to_string(), Duration::new(0, 0)));
+        assert_eq!(detect(&prepended), None);
+    }
+}
@@ -42,11 +42,72 @@
 //!
 //! [GitHub repository](https://github.com/gyng/rcue)
 
+/// Reading and writing CUE sheets embedded in APEv2 tags
+pub mod ape;
+/// Audacity label track import and export
+pub mod audacity;
+/// Builder for constructing sheets programmatically
+pub mod builder;
+/// Tracks of single-file sheets as chapters
+pub mod chapters;
+/// Per-track CRC-32, EAC and AccurateRip checksums
+pub mod checksum;
 /// Structs and types
 pub mod cue;
+/// Verification of disc images against Logiqx XML DAT files
+pub mod dat;
+/// CMX3600 EDL import and export
+pub mod edl;
 /// Errors module
 pub mod errors;
+/// FFmpeg metadata (FFMETADATA1) chapter export
+pub mod ffmetadata;
+/// Reading and writing CUE sheets embedded in FLAC files
+#[cfg(feature = "flac")]
+pub mod flac;
+/// CRC-32, MD5 and SHA-1 hashing
+pub mod hash;
+/// CloneCD and Alcohol 120% image descriptor import
+pub mod image;
+/// Extraction of 2048-byte ISO images from raw data tracks
+pub mod iso;
+/// Joining per-track files into a single-file image
+pub mod join;
+/// JSON serialization of the sheet model
+pub mod json;
+/// Conversion between single-file and multi-file sheet layouts
+pub mod layout;
+/// Lint rules for common CUE sheet mistakes, with suggested fixes
+pub mod lint;
+/// Matroska chapters XML export
+pub mod matroska;
+/// OGM simple chapters export
+pub mod ogm;
 /// Parser implementation
 pub mod parser;
+/// M3U8, XSPF and PLS playlist export
+pub mod playlist;
+/// Reaper region list CSV import and export
+pub mod reaper;
+/// Resolving FILE references to files on disk
+pub mod resolve;
+/// EAC and XLD rip log parsing and cross-checking
+pub mod riplog;
+/// Splitting single-file images into per-track WAVE files
+#[cfg(feature = "split")]
+pub mod split;
+/// cdrdao TOC reading and writing
+pub mod toc;
+/// Per-track `Read + Seek` views of single-file images
+pub mod track_reader;
 /// Utility functions
 pub mod util;
+/// WAVE and raw PCM helpers
+pub mod wav;
+/// Serializer implementation
+pub mod writer;
+/// Minimal XML reader for chapter and DAT files
+mod xml;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr;
+use std::time::Duration;
+
+use cue::{Command, Cue, Track};
+use parser::tokenize_line;
+use util::{duration_to_frames, frames_to_timestamp};
+
+/// A lint rule. Each rule has a stable [`id`](#method.id) used to report and toggle it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// TITLE given twice for the disc or a track (the parser keeps the last one)
+    DuplicateTitle,
+    /// Two tracks with the same number
+    DuplicateTrack,
+    /// A track numbered lower than the one before it, or not numbered
+    TrackOrder,
+    /// Index numbers or times going backwards within a track or FILE
+    IndexOrder,
+    /// A track without INDEX 01
+    MissingIndex01,
+    /// INDEX 00 after INDEX 01
+    Index00After01,
+    /// An INDEX, PREGAP or POSTGAP timestamp that is not `mm:ss:ff`
+    BadTimestamp,
+    /// A timestamp with 75 frames or more, or 60 seconds or more
+    FramesOverflow,
+    /// PREGAP on a track that also has INDEX 00
+    PregapWithIndex00,
+    /// An empty quoted string, such as `TITLE ""`
+    EmptyString,
+    /// A FILE path with backslash separators
+    BackslashPath,
+    /// More than 99 tracks, or a track numbered over 99
+    TooManyTracks,
+    /// A TRACK before any FILE, or a track command before any TRACK (the parser drops them)
+    Orphan,
+}
+
+impl Rule {
+    /// All rules, in the order they are documented.
+    pub const ALL: [Rule; 13] = [
+        Rule::DuplicateTitle,
+        Rule::DuplicateTrack,
+        Rule::TrackOrder,
+        Rule::IndexOrder,
+        Rule::MissingIndex01,
+        Rule::Index00After01,
+        Rule::BadTimestamp,
+        Rule::FramesOverflow,
+        Rule::PregapWithIndex00,
+        Rule::EmptyString,
+        Rule::BackslashPath,
+        Rule::TooManyTracks,
+        Rule::Orphan,
+    ];
+
+    /// Returns the rule's stable identifier, such as `duplicate-title`.
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::DuplicateTitle => "duplicate-title",
+            Rule::DuplicateTrack => "duplicate-track",
+            Rule::TrackOrder => "track-order",
+            Rule::IndexOrder => "index-order",
+            Rule::MissingIndex01 => "missing-index-01",
+            Rule::Index00After01 => "index-00-after-01",
+            Rule::BadTimestamp => "bad-timestamp",
+            Rule::FramesOverflow => "frames-overflow",
+            Rule::PregapWithIndex00 => "pregap-with-index-00",
+            Rule::EmptyString => "empty-string",
+            Rule::BackslashPath => "backslash-path",
+            Rule::TooManyTracks => "too-many-tracks",
+            Rule::Orphan => "orphan",
+        }
+    }
+
+    /// Returns the rule identified by `id`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rcue::lint::Rule;
+    ///
+    /// assert_eq!(Rule::from_id("missing-index-01"), Some(Rule::MissingIndex01));
+    /// assert_eq!(Rule::from_id("no-such-rule"), None);
+    /// ```
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.iter().cloned().find(|rule| rule.id() == id)
+    }
+}
+
+/// Options for [`lint_with`](fn.lint_with.html).
+#[derive(Clone, Debug, Default)]
+pub struct LintOptions {
+    /// Rules not to check
+    pub disabled: Vec<Rule>,
+}
+
+impl LintOptions {
+    /// Returns whether `rule` is checked.
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+/// A suggested edit of one line of the sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    /// Line to edit, starting from 1
+    pub line: usize,
+    /// The new line, or `None` to delete it
+    pub replacement: Option<String>,
+}
+
+/// A problem found by [`lint`](fn.lint.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintFinding {
+    /// Rule that found the problem
+    pub rule: Rule,
+    /// Line of the sheet, starting from 1, if known
+    pub line: Option<usize>,
+    /// Number of the track the problem concerns, if any
+    pub track: Option<u32>,
+    /// Description of the problem
+    pub message: String,
+    /// Suggested fix, if the problem has an unambiguous one
+    pub fix: Option<Fix>,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{} [{}]", self.message, self.rule.id())
+    }
+}
+
+/// The text of a sheet, which [`lint`](fn.lint.html) reads for problems the parsed
+/// [`Cue`](../cue/struct.Cue.html) no longer shows and to find line numbers.
+#[derive(Clone, Copy, Debug)]
+pub struct Source<'a> {
+    text: &'a str,
+}
+
+impl<'a> Source<'a> {
+    /// Wraps the text of a sheet.
+    pub fn new(text: &'a str) -> Self {
+        Source { text }
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &'a str> {
+        self.text.lines()
+    }
+}
+
+// Lines of a TRACK command and of its PREGAP, in the order the parser creates tracks
+struct TrackLines {
+    track: usize,
+    pregap: Option<usize>,
+}
+
+// Checks a `mm:ss:ff` timestamp, returning the normalized timestamp if it overflows.
+fn check_timestamp(timestamp: &str) -> Result<Option<String>, ()> {
+    let parts: Vec<&str> = timestamp.split(':').collect();
+    let numbers: Vec<u64> = parts
+        .iter()
+        .filter(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    match numbers[..] {
+        [minutes, seconds, frames] if parts.len() == 3 => {
+            if seconds >= 60 || frames >= 75 {
+                Ok(Some(frames_to_timestamp(
+                    (minutes * 60 + seconds) * 75 + frames,
+                )))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Err(()),
+    }
+}
+
+// Rules read from the text, returning the lines of each TRACK for the rules on the parsed sheet
+fn lint_source(source: &Source, findings: &mut Vec<LintFinding>) -> Vec<TrackLines> {
+    let mut tracks: Vec<TrackLines> = Vec::new();
+    let mut in_file = false;
+    // Line of the TITLE of the disc (0) or of each track (1..)
+    let mut titles: HashMap<usize, usize> = HashMap::new();
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let track = tracks.len();
+        let mut finding = |rule: Rule, message: String, fix: Option<Fix>| {
+            findings.push(LintFinding {
+                rule,
+                line: Some(line),
+                track: None,
+                message,
+                fix,
+            });
+        };
+        let delete = Some(Fix {
+            line,
+            replacement: None,
+        });
+
+        let command = match tokenize_line(text) {
+            Ok(command) => command,
+            Err(_) => continue,
+        };
+        match command {
+            Command::File(ref path, _) => {
+                in_file = true;
+                if path.is_empty() {
+                    finding(Rule::EmptyString, "empty FILE path".to_string(), None);
+                } else if path.contains('\\') {
+                    let fix = Some(Fix {
+                        line,
+                        replacement: Some(text.replacen(
+                            path.as_str(),
+                            &path.replace('\\', "/"),
+                            1,
+                        )),
+                    });
+                    finding(
+                        Rule::BackslashPath,
+                        format!("FILE path {} uses backslashes", path),
+                        fix,
+                    );
+                }
+            }
+            Command::Track(..) if in_file => tracks.push(TrackLines {
+                track: line,
+                pregap: None,
+            }),
+            Command::Track(..) => {
+                finding(Rule::Orphan, "TRACK before any FILE".to_string(), delete)
+            }
+            Command::Index(..)
+            | Command::Pregap(_)
+            | Command::Postgap(_)
+            | Command::Flags(_)
+            | Command::Isrc(_)
+                if tracks.is_empty() =>
+            {
+                let name = text.split_whitespace().next().unwrap_or("").to_uppercase();
+                finding(Rule::Orphan, format!("{} before any TRACK", name), delete);
+            }
+            Command::Title(ref title) => {
+                if title.is_empty() {
+                    finding(Rule::EmptyString, "empty TITLE".to_string(), delete.clone());
+                }
+                if let Some(previous) = titles.insert(track, line) {
+                    finding(
+                        Rule::DuplicateTitle,
+                        format!(
+                            "TITLE already given on line {}, the last one is used",
+                            previous
+                        ),
+                        Some(Fix {
+                            line: previous,
+                            replacement: None,
+                        }),
+                    );
+                }
+            }
+            Command::Performer(ref value)
+            | Command::Songwriter(ref value)
+            | Command::Catalog(ref value)
+            | Command::CdTextFile(ref value)
+                if value.is_empty() =>
+            {
+                let name = text.split_whitespace().next().unwrap_or("").to_uppercase();
+                finding(Rule::EmptyString, format!("empty {}", name), delete);
+            }
+            Command::Index(_, ref time)
+            | Command::Pregap(ref time)
+            | Command::Postgap(ref time) => {
+                let name = text.split_whitespace().next().unwrap_or("").to_uppercase();
+                if let Command::Pregap(_) = command {
+                    if let Some(track) = tracks.last_mut() {
+                        track.pregap = Some(line);
+                    }
+                }
+                match check_timestamp(time) {
+                    Ok(None) => {}
+                    Ok(Some(normalized)) => {
+                        let fix = Some(Fix {
+                            line,
+                            replacement: Some(text.replacen(time.as_str(), &normalized, 1)),
+                        });
+                        finding(
+                            Rule::FramesOverflow,
+                            format!(
+                                "{} timestamp {} overflows, meaning {}",
+                                name, time, normalized
+                            ),
+                            fix,
+                        );
+                    }
+                    Err(()) => finding(
+                        Rule::BadTimestamp,
+                        format!("{} timestamp {:?} is not mm:ss:ff", name, time),
+                        None,
+                    ),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tracks
+}
+
+// Renumbers the TRACK command on `line` to `no`, keeping its indentation and mode.
+fn renumber(source: &Source, line: Option<usize>, no: u32, track: &Track) -> Option<Fix> {
+    let line = line?;
+    let text = source.lines().nth(line - 1)?;
+    let indent = &text[..text.len() - text.trim_start().len()];
+    Some(Fix {
+        line,
+        replacement: Some(format!("{}TRACK {:02} {}", indent, no, track.format)),
+    })
+}
+
+/// Lints a sheet with all rules. `cue` should be parsed from `source` in non-strict mode, as
+/// strict parsing rejects some of the problems found here.
+///
+/// See [`lint_with`](fn.lint_with.html).
+///
+/// # Example
+///
+/// ```
+/// use rcue::lint::{lint, Rule, Source};
+/// use rcue::parser::parse_from_file;
+///
+/// let text = std::fs::read_to_string("test/fixtures/duplicate_title.cue").unwrap();
+/// let cue = parse_from_file("test/fixtures/duplicate_title.cue", false).unwrap();
+/// let findings = lint(&cue, &Source::new(&text));
+/// assert_eq!(findings.len(), 1);
+/// assert_eq!(findings[0].rule, Rule::DuplicateTitle);
+/// assert_eq!(findings[0].line, Some(8));
+/// ```
+pub fn lint(cue: &Cue, source: &Source) -> Vec<LintFinding> {
+    lint_with(cue, source, &LintOptions::default())
+}
+
+/// Lints a sheet with the rules enabled in `options`, returning findings ordered by line
+/// (findings about the whole sheet first).
+///
+/// Findings carry a suggested [`Fix`](struct.Fix.html) where one is unambiguous: deleting the
+/// earlier duplicate TITLE (keeping what the sheet means to the parser), deleting an empty
+/// string, an orphan command or a PREGAP doubling an INDEX 00, renumbering a duplicate track,
+/// normalizing an overflowing timestamp and replacing backslashes in a FILE path. See
+/// [`apply_fixes`](fn.apply_fixes.html).
+pub fn lint_with(cue: &Cue, source: &Source, options: &LintOptions) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let lines = lint_source(source, &mut findings);
+
+    let tracks: Vec<&Track> = cue.files.iter().flat_map(|f| f.tracks.iter()).collect();
+    // Only trust the TRACK lines when they match the parsed tracks one to one
+    let line_of = |i: usize| {
+        if lines.len() == tracks.len() {
+            lines.get(i)
+        } else {
+            None
+        }
+    };
+
+    if tracks.len() > 99 {
+        findings.push(LintFinding {
+            rule: Rule::TooManyTracks,
+            line: None,
+            track: None,
+            message: format!("{} tracks, a disc holds at most 99", tracks.len()),
+            fix: None,
+        });
+    }
+
+    let numbers: Vec<Option<u32>> = tracks.iter().map(|track| track.no.parse().ok()).collect();
+    let mut previous: Option<u32> = None;
+    let mut highest = 0;
+    let mut seen = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        let no = numbers[i];
+        let line = line_of(i).map(|lines| lines.track);
+        let mut finding = |rule: Rule, message: String, fix: Option<Fix>| {
+            findings.push(LintFinding {
+                rule,
+                line,
+                track: no,
+                message,
+                fix,
+            });
+        };
+
+        match no {
+            Some(no) if seen.contains(&no) => {
+                // Renumbering is only unambiguous when the next number is free
+                let free = !numbers.contains(&Some(highest + 1));
+                let fix = if free {
+                    renumber(source, line, highest + 1, track)
+                } else {
+                    None
+                };
+                finding(
+                    Rule::DuplicateTrack,
+                    format!("TRACK {:02} already given", no),
+                    fix,
+                )
+            }
+            Some(no) if previous.is_some_and(|previous| no < previous) => finding(
+                Rule::TrackOrder,
+                format!("TRACK {:02} after TRACK {:02}", no, previous.unwrap()),
+                None,
+            ),
+            None => finding(
+                Rule::TrackOrder,
+                format!("TRACK number {:?} is not a number", track.no),
+                None,
+            ),
+            _ => {}
+        }
+        if no.is_some_and(|no| no > 99) {
+            finding(
+                Rule::TooManyTracks,
+                format!("TRACK {} is over 99", no.unwrap()),
+                None,
+            );
+        }
+        if let Some(no) = no {
+            seen.push(no);
+            highest = highest.max(no);
+            previous = Some(no);
+        }
+
+        let indices: Vec<(Option<u32>, u64)> = track
+            .previous_file_indices
+            .iter()
+            .chain(track.indices.iter())
+            .map(|(idx, time)| (idx.parse().ok(), duration_to_frames(time)))
+            .collect();
+        if !indices.iter().any(|(idx, _)| *idx == Some(1)) {
+            finding(Rule::MissingIndex01, "no INDEX 01".to_string(), None);
+        }
+        for pair in indices.windows(2) {
+            let ((idx_a, _), (idx_b, _)) = (pair[0], pair[1]);
+            if idx_a == Some(1) && idx_b == Some(0) {
+                finding(
+                    Rule::Index00After01,
+                    "INDEX 00 after INDEX 01".to_string(),
+                    None,
+                );
+            } else if idx_a.is_none() || idx_b.is_none() || idx_b <= idx_a {
+                finding(
+                    Rule::IndexOrder,
+                    format!(
+                        "INDEX {} after INDEX {}",
+                        idx_b.map_or("?".to_string(), |i| format!("{:02}", i)),
+                        idx_a.map_or("?".to_string(), |i| format!("{:02}", i))
+                    ),
+                    None,
+                );
+            }
+        }
+        // Times only go forward within one FILE
+        let times = |list: &[(String, Duration)]| -> Vec<u64> {
+            list.iter()
+                .map(|(_, time)| duration_to_frames(time))
+                .collect()
+        };
+        for list in [times(&track.previous_file_indices), times(&track.indices)].iter() {
+            if list.windows(2).any(|pair| pair[1] < pair[0]) {
+                finding(
+                    Rule::IndexOrder,
+                    "INDEX times go backwards".to_string(),
+                    None,
+                );
+            }
+        }
+
+        if track.pregap.is_some()
+            && (track.index(0).is_some()
+                || track
+                    .previous_file_indices
+                    .iter()
+                    .any(|(idx, _)| idx == "00"))
+        {
+            let fix = line_of(i).and_then(|lines| lines.pregap).map(|line| Fix {
+                line,
+                replacement: None,
+            });
+            finding(
+                Rule::PregapWithIndex00,
+                "PREGAP and INDEX 00 both give the pregap".to_string(),
+                fix,
+            );
+        }
+    }
+
+    // Index times of consecutive tracks in the same FILE
+    for file in &cue.files {
+        for pair in file.tracks.windows(2) {
+            let end = pair[0]
+                .indices
+                .last()
+                .map(|(_, time)| duration_to_frames(time));
+            let start = pair[1]
+                .indices
+                .first()
+                .map(|(_, time)| duration_to_frames(time));
+            if let (Some(end), Some(start)) = (end, start) {
+                if start < end {
+                    let i = tracks
+                        .iter()
+                        .position(|track| ptr::eq(*track, &pair[1]))
+                        .unwrap();
+                    findings.push(LintFinding {
+                        rule: Rule::IndexOrder,
+                        line: line_of(i).map(|lines| lines.track),
+                        track: pair[1].no.parse().ok(),
+                        message: "starts before the previous track's last INDEX".to_string(),
+                        fix: None,
+                    });
+                }
+            }
+        }
+    }
+
+    findings.retain(|finding| options.is_enabled(finding.rule));
+    findings.sort_by_key(|finding| finding.line.unwrap_or(0));
+    findings
+}
+
+/// Applies the suggested fixes of `findings` to `text`. When several fixes edit the same line,
+/// the first one is used. Line endings (LF or CRLF) are kept.
+///
+/// # Example
+///
+/// ```
+/// use rcue::lint::{apply_fixes, lint, Source};
+/// use rcue::parser::parse;
+/// use std::io::Cursor;
+///
+/// let text = "FILE \"a\\b.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:01:80\n";
+/// let cue = parse(&mut Cursor::new(text), false).unwrap();
+/// let fixed = apply_fixes(text, &lint(&cue, &Source::new(text)));
+/// assert_eq!(fixed, "FILE \"a/b.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:02:05\n");
+/// ```
+pub fn apply_fixes(text: &str, findings: &[LintFinding]) -> String {
+    let ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<Option<String>> = text.lines().map(|l| Some(l.to_string())).collect();
+    let mut fixed = Vec::new();
+
+    for fix in findings.iter().filter_map(|finding| finding.fix.as_ref()) {
+        if fixed.contains(&fix.line) || fix.line == 0 || fix.line > lines.len() {
+            continue;
+        }
+        fixed.push(fix.line);
+        lines[fix.line - 1] = fix.replacement.clone();
+    }
+
+    let mut out = lines.into_iter().flatten().collect::<Vec<_>>().join(ending);
+    if text.ends_with('\n') {
+        out.push_str(ending);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn lint_fixture(name: &str) -> Vec<LintFinding> {
+        let text = fs::read_to_string(format!("test/fixtures/{}.cue", name)).unwrap();
+        let cue = parse(&mut Cursor::new(&text), false).unwrap();
+        lint(&cue, &Source::new(&text))
+    }
+
+    fn lint_text(text: &str) -> Vec<LintFinding> {
+        let cue = parse(&mut Cursor::new(text), false).unwrap();
+        lint(&cue, &Source::new(text))
+    }
+
+    fn rules(findings: &[LintFinding]) -> Vec<Rule> {
+        findings.iter().map(|finding| finding.rule).collect()
+    }
+
+    #[test]
+    fn test_clean_fixtures() {
+        for name in &[
+            "good",
+            "gaps_appended",
+            "pregap",
+            "sessions",
+            "unicode",
+            "comments",
+        ] {
+            assert_eq!(lint_fixture(name), vec![], "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let findings = lint_fixture("duplicate_track");
+        assert_eq!(rules(&findings), vec![Rule::DuplicateTrack]);
+        assert_eq!(findings[0].line, Some(13));
+        assert_eq!(
+            findings[0].fix,
+            Some(Fix {
+                line: 13,
+                replacement: Some("  TRACK 02 AUDIO".to_string())
+            })
+        );
+
+        assert_eq!(
+            rules(&lint_fixture("bad_pregap_timestamp")),
+            vec![Rule::BadTimestamp]
+        );
+        assert_eq!(
+            rules(&lint_fixture("bad_index_timestamp")),
+            vec![Rule::MissingIndex01, Rule::BadTimestamp]
+        );
+
+        for name in &["orphan_index", "orphan_pregap", "orphan_postgap"] {
+            let findings = lint_fixture(name);
+            assert_eq!(rules(&findings), vec![Rule::Orphan], "{}", name);
+            assert_eq!(findings[0].fix.as_ref().unwrap().replacement, None);
+        }
+        // The orphan track's TITLE overwrites the disc's
+        assert_eq!(
+            rules(&lint_fixture("orphan_track")),
+            vec![Rule::Orphan, Rule::DuplicateTitle, Rule::Orphan]
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let text = "TITLE \"\"\n\
+                    FILE \"C:\\rip\\a.wav\" WAVE\n\
+                    \x20 TRACK 01 AUDIO\n\
+                    \x20   INDEX 01 00:00:00\n\
+                    \x20 TRACK 03 AUDIO\n\
+                    \x20   PREGAP 00:02:00\n\
+                    \x20   INDEX 00 00:59:70\n\
+                    \x20   INDEX 01 01:00:75\n\
+                    \x20 TRACK 02 AUDIO\n\
+                    \x20   INDEX 01 00:50:00\n\
+                    \x20   INDEX 00 00:52:00\n\
+                    \x20 TRACK 04 AUDIO\n\
+                    \x20   INDEX 00 03:00:00\n";
+        let findings = lint_text(text);
+        assert_eq!(
+            rules(&findings),
+            vec![
+                Rule::EmptyString,
+                Rule::BackslashPath,
+                Rule::PregapWithIndex00,
+                Rule::FramesOverflow,
+                Rule::TrackOrder,
+                Rule::Index00After01,
+                Rule::IndexOrder,
+                Rule::MissingIndex01,
+            ]
+        );
+        assert_eq!(
+            findings[3].to_string(),
+            "line 8: INDEX timestamp 01:00:75 overflows, meaning 01:01:00 [frames-overflow]"
+        );
+
+        let fixed = apply_fixes(text, &findings);
+        assert!(fixed.starts_with("FILE \"C:/rip/a.wav\" WAVE\n"));
+        assert!(!fixed.contains("PREGAP"));
+        assert!(fixed.contains("INDEX 01 01:01:00"));
+
+        let options = LintOptions {
+            disabled: vec![Rule::EmptyString, Rule::IndexOrder],
+        };
+        let cue = parse(&mut Cursor::new(text), false).unwrap();
+        let findings = lint_with(&cue, &Source::new(text), &options);
+        assert_eq!(findings.len(), 6);
+        assert!(findings.iter().all(|f| options.is_enabled(f.rule)));
+    }
+
+    #[test]
+    fn test_too_many_tracks() {
+        let mut text = "FILE \"a.wav\" WAVE\n".to_string();
+        for no in 1..=100 {
+            text.push_str(&format!(
+                "  TRACK {:02} AUDIO\n    INDEX 01 {:02}:00:00\n",
+                no, no
+            ));
+        }
+        let findings = lint_text(&text);
+        assert_eq!(
+            rules(&findings),
+            vec![Rule::TooManyTracks, Rule::TooManyTracks]
+        );
+        assert_eq!(findings[0].line, None);
+        assert_eq!(findings[1].track, Some(100));
+    }
+
+    #[test]
+    fn test_rule_ids() {
+        for rule in Rule::ALL.iter() {
+            assert_eq!(Rule::from_id(rule.id()), Some(*rule));
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use chapters::{chapters, clock_to_nanos, to_cue, ChapterMark, ImportOptions};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use util::{escape_xml, frames_to_nanos};
+use xml;
+
+/// Formats a number of nanoseconds as a Matroska chapter timestamp, `HH:MM:SS.nnnnnnnnn`.
+///
+/// # Example
+///
+/// ```
+/// use rcue::matroska::nanos_to_timestamp;
+///
+/// assert_eq!(nanos_to_timestamp(257_693_333_333), "00:04:17.693333333");
+/// ```
+pub fn nanos_to_timestamp(nanos: u64) -> String {
+    let seconds = nanos / 1_000_000_000;
+    format!(
+        "{:02}:{:02}:{:02}.{:09}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        nanos % 1_000_000_000
+    )
+}
+
+/// Exports a single-file `cue` as Matroska chapters XML, for an audio file `length` CD frames
+/// long.
+///
+/// The XML has one `EditionEntry` with a `ChapterAtom` per track (see
+/// [`chapters`](../chapters/fn.chapters.html) for the spans). `ChapterTimeStart` and
+/// `ChapterTimeEnd` are computed from exact CD frames and written with nanosecond precision.
+/// Each atom has a `ChapterDisplay` with the track TITLE, or `Track NN` if it has none, in the
+/// ISO 639-2 `language`.
+///
+/// # Example
+///
+/// ```
+/// use rcue::matroska::to_matroska_xml;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let xml = to_matroska_xml(&cue, 30000, "eng").unwrap();
+/// assert!(xml.contains("<ChapterTimeStart>00:04:17.693333333</ChapterTimeStart>"));
+/// assert!(xml.contains("<ChapterString>Loomer</ChapterString>"));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE.
+pub fn to_matroska_xml(cue: &Cue, length: u64, language: &str) -> Result<String, CueError> {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n\
+         <Chapters>\n  <EditionEntry>\n",
+    );
+
+    for (i, chapter) in chapters(cue, length)?.iter().enumerate() {
+        let title = match chapter.title {
+            Some(ref title) => title.clone(),
+            None => format!("Track {:02}", i + 1),
+        };
+        out.push_str("    <ChapterAtom>\n");
+        out.push_str(&format!(
+            "      <ChapterTimeStart>{}</ChapterTimeStart>\n",
+            nanos_to_timestamp(frames_to_nanos(chapter.start))
+        ));
+        out.push_str(&format!(
+            "      <ChapterTimeEnd>{}</ChapterTimeEnd>\n",
+            nanos_to_timestamp(frames_to_nanos(chapter.end))
+        ));
+        out.push_str("      <ChapterDisplay>\n");
+        out.push_str(&format!(
+            "        <ChapterString>{}</ChapterString>\n",
+            escape_xml(&title)
+        ));
+        out.push_str(&format!(
+            "        <ChapterLanguage>{}</ChapterLanguage>\n",
+            escape_xml(language)
+        ));
+        out.push_str("      </ChapterDisplay>\n    </ChapterAtom>\n");
+    }
+
+    out.push_str("  </EditionEntry>\n</Chapters>\n");
+    Ok(out)
+}
+
+/// Imports Matroska chapters XML as a single-file sheet for the media file `file`.
+///
+/// The default `EditionEntry` (or the first one) is used. Each top-level `ChapterAtom` that is
+/// enabled and not hidden becomes a track starting at its `ChapterTimeStart`, rounded to CD
+/// frames as described in [`chapters::to_cue`](../chapters/fn.to_cue.html). The track TITLE is
+/// the `ChapterString` in `language` if there is one, and the first otherwise. Nested chapters
+/// are ignored with a diagnostic.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ImportOptions;
+/// use rcue::matroska::{from_matroska_xml, to_matroska_xml};
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let xml = to_matroska_xml(&cue, 30000, "eng").unwrap();
+/// let (imported, diagnostics) =
+///     from_matroska_xml(&xml, "Loveless.mka", None, &ImportOptions::default()).unwrap();
+/// assert_eq!(imported.files[0].tracks[1].title, Some("Loomer".to_string()));
+/// assert!(diagnostics.is_empty());
+/// ```
+///
+/// # Failures
+///
+/// Fails if the XML is invalid, has no `EditionEntry`, a chapter has no valid
+/// `ChapterTimeStart`, or there are more than 99 chapters.
+pub fn from_matroska_xml(
+    text: &str,
+    file: &str,
+    language: Option<&str>,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let root = xml::parse(text)?;
+    if root.name != "Chapters" {
+        return Err(CueError::Parse(format!(
+            "expected <Chapters>, got <{}>",
+            root.name
+        )));
+    }
+
+    let edition = root
+        .children_named("EditionEntry")
+        .find(|edition| edition.child_text("EditionFlagDefault") == Some("1"))
+        .or_else(|| root.child("EditionEntry"))
+        .ok_or_else(|| CueError::Parse("no EditionEntry".to_string()))?;
+
+    let mut marks = Vec::new();
+    let mut nested = false;
+    for atom in edition.children_named("ChapterAtom") {
+        if atom.child_text("ChapterFlagHidden") == Some("1")
+            || atom.child_text("ChapterFlagEnabled") == Some("0")
+        {
+            continue;
+        }
+        nested |= atom.child("ChapterAtom").is_some();
+
+        let start = atom
+            .child_text("ChapterTimeStart")
+            .and_then(clock_to_nanos)
+            .ok_or_else(|| {
+                CueError::Parse(format!(
+                    "chapter {}: missing or bad ChapterTimeStart",
+                    marks.len() + 1
+                ))
+            })?;
+
+        let displays: Vec<&xml::Element> = atom.children_named("ChapterDisplay").collect();
+        let display = displays
+            .iter()
+            .find(|display| language.is_some() && display.child_text("ChapterLanguage") == language)
+            .or_else(|| displays.first());
+
+        marks.push(ChapterMark {
+            start,
+            title: display
+                .and_then(|display| display.child_text("ChapterString"))
+                .map(|title| title.to_string()),
+            performer: None,
+        });
+    }
+
+    let (cue, mut diagnostics) = to_cue(&marks, file, options)?;
+    if nested {
+        diagnostics.push(Diagnostic::new(None, "nested chapters ignored"));
+    }
+    Ok((cue, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_good_cue() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE Chapters SYSTEM "matroskachapters.dtd">
+<Chapters>
+  <EditionEntry>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+      <ChapterTimeEnd>00:04:17.693333333</ChapterTimeEnd>
+      <ChapterDisplay>
+        <ChapterString>Only Shallow</ChapterString>
+        <ChapterLanguage>eng</ChapterLanguage>
+      </ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:04:17.693333333</ChapterTimeStart>
+      <ChapterTimeEnd>00:06:40.000000000</ChapterTimeEnd>
+      <ChapterDisplay>
+        <ChapterString>Loomer</ChapterString>
+        <ChapterLanguage>eng</ChapterLanguage>
+      </ChapterDisplay>
+    </ChapterAtom>
+  </EditionEntry>
+</Chapters>
+"#;
+        assert_eq!(to_matroska_xml(&cue, 30000, "eng").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_untitled_and_escaped() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[0].title = Some("Rock & Roll".to_string());
+        cue.files[0].tracks[1].title = None;
+        let xml = to_matroska_xml(&cue, 30000, "jpn").unwrap();
+        assert!(xml.contains("<ChapterString>Rock &amp; Roll</ChapterString>"));
+        assert!(xml.contains("<ChapterString>Track 02</ChapterString>"));
+        assert!(xml.contains("<ChapterLanguage>jpn</ChapterLanguage>"));
+    }
+
+    #[test]
+    fn test_import_language_and_flags() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Chapters>
+  <EditionEntry>
+    <ChapterAtom>
+      <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+      <ChapterDisplay>
+        <ChapterString>Opening</ChapterString>
+        <ChapterLanguage>eng</ChapterLanguage>
+      </ChapterDisplay>
+      <ChapterDisplay>
+        <ChapterString>Ouverture</ChapterString>
+        <ChapterLanguage>fre</ChapterLanguage>
+      </ChapterDisplay>
+      <ChapterAtom>
+        <ChapterTimeStart>00:00:10</ChapterTimeStart>
+      </ChapterAtom>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterFlagHidden>1</ChapterFlagHidden>
+      <ChapterTimeStart>00:00:30</ChapterTimeStart>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterTimeStart>00:01:00.5</ChapterTimeStart>
+    </ChapterAtom>
+  </EditionEntry>
+</Chapters>
+"#;
+        let options = ImportOptions::default();
+        let (cue, diagnostics) = from_matroska_xml(xml, "a.mka", Some("fre"), &options).unwrap();
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].title, Some("Ouverture".to_string()));
+        assert_eq!(tracks[1].title, None);
+        assert_eq!(chapters(&cue, 10000).unwrap()[1].start, 4538);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::new(Some(2), "start rounded by 6666667ns to frame 4538"),
+                Diagnostic::new(None, "nested chapters ignored"),
+            ]
+        );
+
+        let (cue, _) = from_matroska_xml(xml, "a.mka", Some("jpn"), &options).unwrap();
+        assert_eq!(cue.files[0].tracks[0].title, Some("Opening".to_string()));
+    }
+
+    #[test]
+    fn test_import_invalid() {
+        let options = ImportOptions::default();
+        assert!(from_matroska_xml("<Tags/>", "a.mka", None, &options).is_err());
+        assert!(from_matroska_xml("<Chapters/>", "a.mka", None, &options).is_err());
+        let xml = "<Chapters><EditionEntry><ChapterAtom/></EditionEntry></Chapters>";
+        assert!(from_matroska_xml(xml, "a.mka", None, &options).is_err());
+    }
+
+    #[test]
+    fn test_long_timestamp() {
+        assert_eq!(
+            nanos_to_timestamp(frames_to_nanos(75 * 3661 + 1)),
+            "01:01:01.013333333"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use chapters::{chapters, clock_to_nanos, to_cue, ChapterMark, ImportOptions};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use util::FRAMES_PER_SECOND;
+
+/// Formats a number of CD frames as an OGM chapter timestamp, `HH:MM:SS.mmm`, rounded to the
+/// nearest millisecond.
+///
+/// # Example
+///
+/// ```
+/// use rcue::ogm::frames_to_ogm_timestamp;
+///
+/// assert_eq!(frames_to_ogm_timestamp(19327), "00:04:17.693");
+/// ```
+pub fn frames_to_ogm_timestamp(frames: u64) -> String {
+    let millis = (frames * 1000 * 2 + FRAMES_PER_SECOND) / (FRAMES_PER_SECOND * 2);
+    let seconds = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis % 1000
+    )
+}
+
+/// Exports a single-file `cue` as OGM simple chapters, with a `CHAPTERnn` start time and a
+/// `CHAPTERnnNAME` per track.
+///
+/// Start times are each track's INDEX 01 (see [`chapters`](../chapters/fn.chapters.html)), and
+/// names are the track TITLE, or `Track NN` if it has none. The format has no end times.
+///
+/// # Example
+///
+/// ```
+/// use rcue::ogm::to_ogm;
+/// use rcue::parser::parse_from_file;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let expected = "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Only Shallow\n\
+///                 CHAPTER02=00:04:17.693\nCHAPTER02NAME=Loomer\n";
+/// assert_eq!(to_ogm(&cue).unwrap(), expected);
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE.
+pub fn to_ogm(cue: &Cue) -> Result<String, CueError> {
+    let mut out = String::new();
+    for (i, chapter) in chapters(cue, u64::MAX)?.iter().enumerate() {
+        let no = i + 1;
+        let title = match chapter.title {
+            Some(ref title) => title.replace('\n', " "),
+            None => format!("Track {:02}", no),
+        };
+        out.push_str(&format!(
+            "CHAPTER{:02}={}\nCHAPTER{:02}NAME={}\n",
+            no,
+            frames_to_ogm_timestamp(chapter.start),
+            no,
+            title
+        ));
+    }
+    Ok(out)
+}
+
+/// Imports OGM simple chapters as a single-file sheet for the media file `file`.
+///
+/// Each `CHAPTERnn` time becomes a track starting there, rounded to CD frames as described in
+/// [`chapters::to_cue`](../chapters/fn.to_cue.html), titled by its `CHAPTERnnNAME`. Keys are
+/// matched case-insensitively and other lines are ignored.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ImportOptions;
+/// use rcue::ogm::from_ogm;
+///
+/// let text = "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Intro\nCHAPTER02=00:01:30.000\n";
+/// let (cue, _) = from_ogm(text, "book.mka", &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.files[0].tracks[0].title, Some("Intro".to_string()));
+/// assert_eq!(cue.files[0].tracks[1].start().unwrap().as_secs(), 90);
+/// ```
+///
+/// # Failures
+///
+/// Fails if a chapter has a name but no time, a time is invalid, or there are more than 99
+/// chapters.
+pub fn from_ogm(
+    text: &str,
+    file: &str,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let mut entries: BTreeMap<u32, (Option<u64>, Option<String>)> = BTreeMap::new();
+
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let (key, value) = match line.find('=') {
+            Some(eq) => (line[..eq].trim().to_ascii_uppercase(), &line[eq + 1..]),
+            None => continue,
+        };
+        let key = match key.strip_prefix("CHAPTER") {
+            Some(key) => key,
+            None => continue,
+        };
+        let (no, is_name) = match key.strip_suffix("NAME") {
+            Some(no) => (no, true),
+            None => (key, false),
+        };
+        let no: u32 = match no.parse() {
+            Ok(no) => no,
+            Err(_) => continue,
+        };
+
+        let entry = entries.entry(no).or_insert((None, None));
+        if is_name {
+            entry.1 = Some(value.trim_end_matches('\r').to_string());
+        } else {
+            let start = clock_to_nanos(value).ok_or_else(|| {
+                CueError::Parse(format!("CHAPTER{:02}: bad time {}", no, value.trim()))
+            })?;
+            entry.0 = Some(start);
+        }
+    }
+
+    let mut marks = Vec::new();
+    for (no, (start, title)) in entries {
+        let start =
+            start.ok_or_else(|| CueError::Parse(format!("CHAPTER{:02}: missing time", no)))?;
+        marks.push(ChapterMark {
+            start,
+            title,
+            performer: None,
+        });
+    }
+
+    to_cue(&marks, file, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_timestamp_rounding() {
+        assert_eq!(frames_to_ogm_timestamp(1), "00:00:00.013");
+        assert_eq!(frames_to_ogm_timestamp(2), "00:00:00.027");
+        assert_eq!(frames_to_ogm_timestamp(75 * 3600), "01:00:00.000");
+    }
+
+    #[test]
+    fn test_untitled_track() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[1].title = None;
+        assert!(to_ogm(&cue).unwrap().ends_with("CHAPTER02NAME=Track 02\n"));
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let (imported, diagnostics) =
+            from_ogm(&to_ogm(&cue).unwrap(), "a.mka", &ImportOptions::default()).unwrap();
+        assert_eq!(to_ogm(&imported).unwrap(), to_ogm(&cue).unwrap());
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_import_invalid() {
+        let options = ImportOptions::default();
+        assert!(from_ogm("CHAPTER01NAME=Intro\n", "a.mka", &options).is_err());
+        assert!(from_ogm("CHAPTER01=1:00\n", "a.mka", &options).is_err());
+        assert!(from_ogm("CHAPTER01=99999999999:00:00.000\n", "a.mka", &options).is_err());
+    }
+
+    #[test]
+    fn test_multi_file() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert!(to_ogm(&cue).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-use cue::{Command, Cue, CueFile, Track};
+use cue::{Command, Cue, CueFile, Session, Track};
 use errors::CueError;
 use util::{next_string, next_token, next_values, timestamp_to_duration};
 
@@ -74,6 +74,9 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
     }
 
     let mut cue = Cue::new();
+    // Set by REM SESSION until the session's first TRACK, while REM LEAD-IN and REM PREGAP
+    // describe the session
+    let mut session_started = false;
 
     fn last_file(cue: &mut Cue) -> Option<&mut CueFile> {
         cue.files.last_mut()
@@ -83,6 +86,25 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
         last_file(cue).and_then(|f| f.tracks.last_mut())
     }
 
+    // An INDEX directly after a FILE command continues the last TRACK of the previous FILE,
+    // whose earlier indices (usually an INDEX 00 pregap) are kept in `previous_file_indices`.
+    fn carry_over_track(cue: &mut Cue) {
+        let len = cue.files.len();
+        if len < 2 || !cue.files[len - 1].tracks.is_empty() {
+            return;
+        }
+
+        let continues = cue.files[len - 2]
+            .tracks
+            .last()
+            .is_some_and(|t| !t.indices.is_empty() && t.index(1).is_none());
+        if continues {
+            let mut track = cue.files[len - 2].tracks.pop().unwrap();
+            track.previous_file_indices = std::mem::take(&mut track.indices);
+            cue.files[len - 1].tracks.push(track);
+        }
+    }
+
     for (i, line) in buf_reader.lines().enumerate() {
         if let Ok(ref l) = line {
             let token = tokenize_line(l);
@@ -106,6 +128,60 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
                     }
                 }
                 Ok(Command::Rem(field, value)) => {
+                    match field.to_uppercase().as_ref() {
+                        "SESSION" => {
+                            let expected = cue.sessions.len() as u32 + 1;
+                            match value.parse::<u32>() {
+                                Ok(no) if no == expected || !strict => {
+                                    if no == 1 && last_track(&mut cue).is_some() {
+                                        fail_if_strict!(i, l, "REM SESSION 01 after tracks");
+                                    }
+                                    cue.sessions.push(Session::new(no));
+                                    session_started = true;
+                                    continue;
+                                }
+                                _ => fail_if_strict!(
+                                    i,
+                                    l,
+                                    &format!("REM SESSION out of order, expected {}", expected)
+                                ),
+                            }
+                        }
+                        "LEAD-OUT" => {
+                            let lead_out = timestamp_to_duration(&value);
+                            if let (Some(session), Ok(lead_out)) =
+                                (cue.sessions.last_mut(), lead_out)
+                            {
+                                if !session_started {
+                                    session.lead_out = Some(lead_out);
+                                    continue;
+                                }
+                            }
+                            fail_if_strict!(i, l, "REM LEAD-OUT outside a session");
+                        }
+                        "LEAD-IN" | "PREGAP" if session_started => {
+                            let session = cue.sessions.last_mut().unwrap();
+                            if let Ok(duration) = timestamp_to_duration(&value) {
+                                if field.eq_ignore_ascii_case("LEAD-IN") {
+                                    session.lead_in = Some(duration);
+                                } else {
+                                    session.pregap = Some(duration);
+                                }
+                                continue;
+                            }
+                            fail_if_strict!(i, l, "bad session gap timestamp");
+                        }
+                        "ORIGINAL"
+                            if value
+                                .get(..11)
+                                .is_some_and(|p| p.eq_ignore_ascii_case("MEDIA-TYPE:")) =>
+                        {
+                            cue.original_media_type = Some(value[11..].trim().to_string());
+                            continue;
+                        }
+                        _ => {}
+                    }
+
                     let comment = (field, value);
 
                     if last_track(&mut cue).is_some() {
@@ -120,8 +196,12 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
                     cue.files.push(CueFile::new(&file, &format));
                 }
                 Ok(Command::Track(idx, mode)) => {
+                    let session = cue.sessions.last().map(|session| session.no);
+                    session_started = false;
                     if let Some(file) = last_file(&mut cue) {
-                        file.tracks.push(Track::new(&idx, &mode));
+                        let mut track = Track::new(&idx, &mode);
+                        track.session = session;
+                        file.tracks.push(track);
                     } else {
                         fail_if_strict!(i, l, "TRACK assigned to no FILE");
                     }
@@ -149,6 +229,7 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
                     }
                 }
                 Ok(Command::Index(idx, time)) => {
+                    carry_over_track(&mut cue);
                     if let Some(track) = last_track(&mut cue) {
                         if let Ok(duration) = timestamp_to_duration(&time) {
                             track.indices.push((idx, duration));
@@ -203,11 +284,30 @@ pub fn parse(buf_reader: &mut dyn BufRead, strict: bool) -> Result<Cue, CueError
         }
     }
 
+    if strict {
+        cue.validate_sessions()?;
+    }
+
     Ok(cue)
 }
 
-#[allow(dead_code)]
-fn tokenize_line(line: &str) -> Result<Command, CueError> {
+/// Tokenizes one line of a CUE sheet into a [`Command`](../cue/enum.Command.html), without
+/// validating its values. Blank lines are [`Command::None`](../cue/enum.Command.html#variant.None).
+///
+/// # Example
+///
+/// ```
+/// use rcue::cue::Command;
+/// use rcue::parser::tokenize_line;
+///
+/// let command = tokenize_line("    INDEX 01 04:17:52").unwrap();
+/// assert_eq!(command, Command::Index("01".to_string(), "04:17:52".to_string()));
+/// ```
+///
+/// # Failures
+///
+/// Fails if a command is missing its value.
+pub fn tokenize_line(line: &str) -> Result<Command, CueError> {
     let mut chars = line.trim().chars();
 
     let command = next_token(&mut chars);
@@ -221,7 +321,16 @@ fn tokenize_line(line: &str) -> Result<Command, CueError> {
         Some(c) => match c.to_uppercase().as_ref() {
             "REM" => {
                 let key = next_token(&mut chars);
-                let val = next_string(&mut chars, "missing REM value")?;
+                let rest = chars.as_str().trim();
+                let media_type = rest
+                    .get(..11)
+                    .is_some_and(|p| p.eq_ignore_ascii_case("MEDIA-TYPE:"));
+                let val = if key.eq_ignore_ascii_case("ORIGINAL") && media_type {
+                    // REM ORIGINAL MEDIA-TYPE: CD
+                    rest.to_string()
+                } else {
+                    next_string(&mut chars, "missing REM value")?
+                };
                 Ok(Command::Rem(key, val))
             }
             "CATALOG" => {
@@ -411,6 +520,23 @@ mod tests {
         assert_eq!(cue.files[0], cue.files[1]);
     }
 
+    #[test]
+    fn test_gaps_appended() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        assert_eq!(cue.files.len(), 2);
+        assert_eq!(cue.files[0].tracks.len(), 1);
+        assert_eq!(cue.files[1].tracks.len(), 1);
+
+        let track = &cue.files[1].tracks[0];
+        assert_eq!(track.no, "02");
+        assert_eq!(track.title, Some("Loomer".to_string()));
+        assert_eq!(
+            track.previous_file_indices,
+            vec![("00".to_string(), Duration::new(255, 693333333))]
+        );
+        assert_eq!(track.indices, vec![("01".to_string(), Duration::new(0, 0))]);
+    }
+
     #[test]
     fn test_bad_index_lenient() {
         let cue = parse_from_file("test/fixtures/bad_index.cue", false).unwrap();
@@ -556,4 +682,77 @@ mod tests {
 
         assert!(parse(&mut io::Cursor::new(b"FILE"), true).is_err());
     }
+
+    #[test]
+    fn test_original_comments() {
+        use std::io;
+
+        let sheet = "REM ORIGINAL \"x\"\nREM ORIGINAL MED\u{131}A-TYPE: CD\n";
+        let cue = parse(&mut io::Cursor::new(sheet), true).unwrap();
+        assert_eq!(cue.original_media_type, None);
+        assert_eq!(
+            cue.comments,
+            vec![
+                ("ORIGINAL".to_string(), "x".to_string()),
+                ("ORIGINAL".to_string(), "MED\u{131}A-TYPE:".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sessions() {
+        let cue = parse_from_file("test/fixtures/sessions.cue", true).unwrap();
+        assert_eq!(cue.original_media_type, Some("CD".to_string()));
+        assert!(cue.comments.is_empty());
+        assert_eq!(cue.sessions.len(), 2);
+        assert_eq!(cue.sessions[0].lead_out, Some(Duration::new(90, 0)));
+        assert_eq!(cue.sessions[1].lead_in, Some(Duration::new(60, 0)));
+        assert_eq!(cue.sessions[1].pregap, Some(Duration::new(2, 0)));
+        assert!(cue.files.iter().all(|f| f.tracks[0].comments.is_empty()));
+
+        let groups = cue.session_tracks();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(groups[1].1[0].no, "03");
+
+        assert_eq!(cue.session_gap(1), 0);
+        assert_eq!(cue.session_gap(2), 11400);
+        assert_eq!(
+            cue.track_lbas(&[1000, 2000]).unwrap(),
+            vec![Some(0), Some(1150), Some(14400)]
+        );
+        assert!(cue.track_lbas(&[1000]).is_err());
+    }
+
+    #[test]
+    fn test_sessions_out_of_order() {
+        use std::io;
+
+        let sheet = |sessions: &str| {
+            let text = format!(
+                "REM SESSION {}\nFILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+                 REM SESSION {}\n  TRACK 02 MODE1/2352\n    INDEX 01 00:10:00\n",
+                &sessions[..2],
+                &sessions[2..]
+            );
+            io::Cursor::new(text.into_bytes())
+        };
+
+        assert!(parse(&mut sheet("0102"), true).is_ok());
+        assert!(parse(&mut sheet("0201"), true).is_err());
+        assert!(parse(&mut sheet("0103"), true).is_err());
+        assert!(parse(&mut sheet("0101"), true).is_err());
+
+        let cue = parse(&mut sheet("0103"), false).unwrap();
+        assert!(cue.validate_sessions().is_err());
+
+        let cue = parse(&mut sheet("0102"), true).unwrap();
+        assert_eq!(cue.files[0].tracks[1].session, Some(2));
+        assert!(cue.validate_sessions().is_ok());
+
+        let orphan = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+                      REM SESSION 01\n  TRACK 02 AUDIO\n    INDEX 01 00:10:00\n";
+        assert!(parse(&mut io::Cursor::new(orphan), true).is_err());
+        assert!(parse(&mut io::Cursor::new("REM LEAD-OUT 01:30:00\n"), true).is_err());
+    }
 }
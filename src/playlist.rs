@@ -0,0 +1,475 @@
+use std::path::{Component, Path, PathBuf};
+
+use cue::{Cue, PregapMode, TrackMode};
+use util::{escape_xml, FRAMES_PER_SECOND};
+
+/// Options for exporting playlists.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistOptions {
+    /// Directory of the CUE sheet, which FILE paths are relative to
+    pub cue_dir: PathBuf,
+    /// Directory the playlist is written to, which entry paths are made relative to
+    pub playlist_dir: PathBuf,
+}
+
+impl Default for PlaylistOptions {
+    fn default() -> Self {
+        Self {
+            cue_dir: PathBuf::from("."),
+            playlist_dir: PathBuf::from("."),
+        }
+    }
+}
+
+/// A track as a playlist entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistEntry {
+    /// Path of the track's file relative to the playlist directory, with `/` separators
+    pub path: String,
+    /// Track number
+    pub no: String,
+    /// Track title
+    pub title: Option<String>,
+    /// Track performer, or the disc performer if the track has none
+    pub performer: Option<String>,
+    /// First frame of the track in its file (75 frames = 1s)
+    pub start: u64,
+    /// Frame after the last frame of the track, if the file length is known
+    pub end: Option<u64>,
+    /// Whether the file holds other audio, so players need `start` and `end`
+    pub partial: bool,
+}
+
+impl PlaylistEntry {
+    /// Returns `Performer - Title`, the title alone, or `Track NN` if the track has no title.
+    pub fn display_title(&self) -> String {
+        let title = match (&self.performer, &self.title) {
+            (Some(performer), Some(title)) => format!("{} - {}", performer, title),
+            (None, Some(title)) => title.clone(),
+            _ => format!("Track {}", self.no),
+        };
+        title.replace(['\r', '\n'], " ")
+    }
+
+    /// Returns the length of the track in CD frames, if known.
+    pub fn duration(&self) -> Option<u64> {
+        self.end.map(|end| end.saturating_sub(self.start))
+    }
+}
+
+fn normalize<'a>(path: &'a Path) -> Vec<Component<'a>> {
+    let mut components: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {
+                    components.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+    components
+}
+
+// Lexically rewrites `path` relative to `base`. Falls back to `path` itself when that is not
+// possible without the working directory, e.g. when only one of them is absolute.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path_components = normalize(path);
+    let base_components = normalize(base);
+    let common = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if path.has_root() != base.has_root()
+        || (path.has_root() && common == 0)
+        || base_components[common..].contains(&Component::ParentDir)
+    {
+        return path_components.iter().collect();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Returns one playlist entry per AUDIO track of `cue`.
+///
+/// Tracks run from INDEX 01 to the next track's INDEX 01 in the same FILE, so pregaps belong to
+/// the previous track. `file_lengths` gives the length of each FILE in CD frames and is used to
+/// end the last track of each FILE; tracks of FILEs without a length have no end. Tracks without
+/// an INDEX are skipped.
+///
+/// FILE paths may use `\` separators and are resolved against
+/// [`cue_dir`](struct.PlaylistOptions.html#structfield.cue_dir), then made relative to
+/// [`playlist_dir`](struct.PlaylistOptions.html#structfield.playlist_dir).
+///
+/// # Example
+///
+/// ```
+/// use std::path::PathBuf;
+/// use rcue::parser::parse_from_file;
+/// use rcue::playlist::{entries, PlaylistOptions};
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let options = PlaylistOptions {
+///     cue_dir: PathBuf::from("music/Loveless"),
+///     playlist_dir: PathBuf::from("playlists"),
+/// };
+/// let entries = entries(&cue, &[30000], &options);
+/// assert_eq!(entries[1].path, "../music/Loveless/My Bloody Valentine - Loveless.wav");
+/// assert_eq!((entries[1].start, entries[1].end), (19327, Some(30000)));
+/// ```
+pub fn entries(cue: &Cue, file_lengths: &[u64], options: &PlaylistOptions) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+
+    for (i, file) in cue.files.iter().enumerate() {
+        let source = options.cue_dir.join(file.file.replace('\\', "/"));
+        let path = relative_to(&source, &options.playlist_dir)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let length = file_lengths.get(i).cloned();
+
+        for (track, span) in file.tracks.iter().zip(file.track_spans(PregapMode::Append)) {
+            let span = match span {
+                Some(span) => span,
+                None => continue,
+            };
+            if track.mode().is_some_and(|mode| mode != TrackMode::Audio) {
+                continue;
+            }
+
+            let end = span.end.or(length);
+            entries.push(PlaylistEntry {
+                path: path.clone(),
+                no: track.no.clone(),
+                title: track.title.clone(),
+                performer: track.performer.clone().or_else(|| cue.performer.clone()),
+                start: span.start,
+                end,
+                partial: file.tracks.len() > 1 || span.start > 0,
+            });
+        }
+    }
+
+    entries
+}
+
+// Formats frames as seconds with up to three decimals, e.g. `257.693` or `400`.
+fn seconds(frames: u64) -> String {
+    let millis = (frames * 1000 * 2 + FRAMES_PER_SECOND) / (FRAMES_PER_SECOND * 2);
+    if millis.is_multiple_of(1000) {
+        format!("{}", millis / 1000)
+    } else {
+        format!("{}.{:03}", millis / 1000, millis % 1000)
+            .trim_end_matches('0')
+            .to_string()
+    }
+}
+
+// Whole seconds, rounded, or -1 if unknown, as M3U and PLS expect.
+fn whole_seconds(frames: Option<u64>) -> String {
+    match frames {
+        Some(frames) => format!("{}", (frames + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND),
+        None => "-1".to_string(),
+    }
+}
+
+/// Exports `cue` as an extended M3U8 playlist with one entry per track.
+///
+/// Each entry has an `#EXTINF` line with the duration in whole seconds (`-1` if unknown) and
+/// `Performer - Title`. Tracks sharing a file, as in single-file rips, also get
+/// `#EXTVLCOPT:start-time` and `#EXTVLCOPT:stop-time` lines in seconds. See
+/// [`entries`](fn.entries.html) for how tracks and paths are resolved.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::playlist::{to_m3u8, PlaylistOptions};
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let m3u = to_m3u8(&cue, &[30000], &PlaylistOptions::default());
+/// assert!(m3u.ends_with(
+///     "#EXTINF:142,My Bloody Valentine - Loomer\n\
+///      #EXTVLCOPT:start-time=257.693\n\
+///      #EXTVLCOPT:stop-time=400\n\
+///      My Bloody Valentine - Loveless.wav\n"
+/// ));
+/// ```
+pub fn to_m3u8(cue: &Cue, file_lengths: &[u64], options: &PlaylistOptions) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries(cue, file_lengths, options) {
+        out.push_str(&format!(
+            "#EXTINF:{},{}\n",
+            whole_seconds(entry.duration()),
+            entry.display_title()
+        ));
+        if entry.partial {
+            out.push_str(&format!("#EXTVLCOPT:start-time={}\n", seconds(entry.start)));
+            if let Some(end) = entry.end {
+                out.push_str(&format!("#EXTVLCOPT:stop-time={}\n", seconds(end)));
+            }
+        }
+        out.push_str(&entry.path);
+        out.push('\n');
+    }
+    out
+}
+
+// Percent-encodes a path for an XSPF location, keeping `/`.
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    if path.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        encoded
+    }
+}
+
+/// Exports `cue` as an XSPF playlist with a `<track>` per track.
+///
+/// Locations are percent-encoded URIs, relative unless the path is absolute. Each track has its
+/// title, creator (performer), album (disc title), track number and duration in milliseconds.
+/// Tracks sharing a file also get VLC `start-time` and `stop-time` options in a VLC
+/// `<extension>`. See [`entries`](fn.entries.html) for how tracks and paths are resolved.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::playlist::{to_xspf, PlaylistOptions};
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let xspf = to_xspf(&cue, &[30000], &PlaylistOptions::default());
+/// assert!(xspf.contains("<location>My%20Bloody%20Valentine%20-%20Loveless.wav</location>"));
+/// assert!(xspf.contains("<vlc:option>start-time=257.693</vlc:option>"));
+/// ```
+pub fn to_xspf(cue: &Cue, file_lengths: &[u64], options: &PlaylistOptions) -> String {
+    fn element(out: &mut String, indent: &str, name: &str, value: &str) {
+        out.push_str(&format!(
+            "{}<{}>{}</{}>\n",
+            indent,
+            name,
+            escape_xml(value),
+            name
+        ));
+    }
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\" \
+         xmlns:vlc=\"http://www.videolan.org/vlc/playlist/ns/0/\">\n",
+    );
+    if let Some(ref title) = cue.title {
+        element(&mut out, "  ", "title", title);
+    }
+    if let Some(ref performer) = cue.performer {
+        element(&mut out, "  ", "creator", performer);
+    }
+
+    out.push_str("  <trackList>\n");
+    for entry in entries(cue, file_lengths, options) {
+        let indent = "      ";
+        out.push_str("    <track>\n");
+        element(&mut out, indent, "location", &uri_encode(&entry.path));
+        element(
+            &mut out,
+            indent,
+            "title",
+            &entry
+                .title
+                .clone()
+                .unwrap_or_else(|| format!("Track {}", entry.no)),
+        );
+        if let Some(ref performer) = entry.performer {
+            element(&mut out, indent, "creator", performer);
+        }
+        if let Some(ref album) = cue.title {
+            element(&mut out, indent, "album", album);
+        }
+        if let Ok(no) = entry.no.parse::<u32>() {
+            element(&mut out, indent, "trackNum", &no.to_string());
+        }
+        if let Some(duration) = entry.duration() {
+            let millis = (duration * 1000 * 2 + FRAMES_PER_SECOND) / (FRAMES_PER_SECOND * 2);
+            element(&mut out, indent, "duration", &millis.to_string());
+        }
+        if entry.partial {
+            out.push_str(
+                "      <extension application=\"http://www.videolan.org/vlc/playlist/0\">\n",
+            );
+            let indent = "        ";
+            element(
+                &mut out,
+                indent,
+                "vlc:option",
+                &format!("start-time={}", seconds(entry.start)),
+            );
+            if let Some(end) = entry.end {
+                element(
+                    &mut out,
+                    indent,
+                    "vlc:option",
+                    &format!("stop-time={}", seconds(end)),
+                );
+            }
+            out.push_str("      </extension>\n");
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+/// Exports `cue` as a PLS playlist with one entry per track.
+///
+/// Entries have a `File`, `Title` (`Performer - Title`) and `Length` in whole seconds (`-1` if
+/// unknown). PLS has no way to start playback inside a file, so tracks sharing a file all play
+/// it from the start. See [`entries`](fn.entries.html) for how tracks and paths are resolved.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::playlist::{to_pls, PlaylistOptions};
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let pls = to_pls(&cue, &[30000], &PlaylistOptions::default());
+/// assert!(pls.contains("Title2=My Bloody Valentine - Loomer\nLength2=142\n"));
+/// assert!(pls.ends_with("NumberOfEntries=2\nVersion=2\n"));
+/// ```
+pub fn to_pls(cue: &Cue, file_lengths: &[u64], options: &PlaylistOptions) -> String {
+    let entries = entries(cue, file_lengths, options);
+    let mut out = String::from("[playlist]\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry.path));
+        out.push_str(&format!("Title{}={}\n", n, entry.display_title()));
+        out.push_str(&format!(
+            "Length{}={}\n",
+            n,
+            whole_seconds(entry.duration())
+        ));
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+
+    fn options(cue_dir: &str, playlist_dir: &str) -> PlaylistOptions {
+        PlaylistOptions {
+            cue_dir: PathBuf::from(cue_dir),
+            playlist_dir: PathBuf::from(playlist_dir),
+        }
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let cases = [
+            ("a/b.wav", ".", "a/b.wav"),
+            ("a/b.wav", "a", "b.wav"),
+            ("a/./c/../b.wav", "x/y", "../../a/b.wav"),
+            ("/music/a/b.wav", "/music/lists", "../a/b.wav"),
+            ("/music/a/b.wav", "lists", "/music/a/b.wav"),
+            ("a/b.wav", "../lists", "a/b.wav"),
+        ];
+        for (path, base, expected) in cases.iter() {
+            assert_eq!(
+                relative_to(Path::new(path), Path::new(base)),
+                PathBuf::from(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_m3u8_single_file() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let expected = "#EXTM3U
+#EXTINF:258,My Bloody Valentine - Only Shallow
+#EXTVLCOPT:start-time=0
+#EXTVLCOPT:stop-time=257.693
+rips/My Bloody Valentine - Loveless.wav
+#EXTINF:-1,My Bloody Valentine - Loomer
+#EXTVLCOPT:start-time=257.693
+rips/My Bloody Valentine - Loveless.wav
+";
+        assert_eq!(to_m3u8(&cue, &[], &options("rips", ".")), expected);
+    }
+
+    #[test]
+    fn test_m3u8_multi_file() {
+        let mut cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        cue.files[1].file = "CD1\\02 - Loomer.wav".to_string();
+        let m3u = to_m3u8(&cue, &[19327, 10673], &options("/music", "/music"));
+        assert!(m3u.ends_with("#EXTINF:142,My Bloody Valentine - Loomer\nCD1/02 - Loomer.wav\n"));
+        assert!(!m3u.contains("#EXTVLCOPT"));
+    }
+
+    #[test]
+    fn test_xspf() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[1].title = Some("Loomer & <More>".to_string());
+        let xspf = to_xspf(&cue, &[30000], &options(".", "."));
+        let expected = "    <track>
+      <location>My%20Bloody%20Valentine%20-%20Loveless.wav</location>
+      <title>Loomer &amp; &lt;More&gt;</title>
+      <creator>My Bloody Valentine</creator>
+      <album>Loveless</album>
+      <trackNum>2</trackNum>
+      <duration>142307</duration>
+      <extension application=\"http://www.videolan.org/vlc/playlist/0\">
+        <vlc:option>start-time=257.693</vlc:option>
+        <vlc:option>stop-time=400</vlc:option>
+      </extension>
+    </track>
+";
+        assert!(xspf.contains(expected));
+        assert!(xspf.starts_with("<?xml"));
+        assert!(xspf.contains("  <title>Loveless</title>\n"));
+    }
+
+    #[test]
+    fn test_pls() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        let expected = "[playlist]
+File1=01 - Only Shallow.wav
+Title1=My Bloody Valentine - Only Shallow
+Length1=258
+File2=02 - Loomer.wav
+Title2=My Bloody Valentine - Loomer
+Length2=-1
+NumberOfEntries=2
+Version=2
+";
+        assert_eq!(to_pls(&cue, &[19327], &options(".", ".")), expected);
+    }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!(uri_encode("a b/ü.wav"), "a%20b/%C3%BC.wav");
+        assert_eq!(uri_encode("/music/a#1.wav"), "file:///music/a%231.wav");
+    }
+}
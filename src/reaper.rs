@@ -0,0 +1,281 @@
+use chapters::{chapters, seconds_to_nanos, to_cue, ChapterMark, ExportOptions, ImportOptions};
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use util::frames_to_nanos;
+
+// Formats milliseconds the way Reaper shows minutes:seconds, e.g. `4:17.693` or `1:02:03.456`.
+fn format_time(millis: u64) -> String {
+    let seconds = millis / 1000;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}.{:03}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            millis % 1000
+        )
+    } else {
+        format!("{}:{:02}.{:03}", seconds / 60, seconds % 60, millis % 1000)
+    }
+}
+
+/// Parses a Reaper region time in seconds (`257.693`), minutes:seconds (`4:17.693`) or
+/// hours:minutes:seconds (`0:04:17.693`) into nanoseconds.
+///
+/// Measures.beats times (`5.1.00`) depend on the project tempo and are not supported.
+///
+/// # Example
+///
+/// ```
+/// use rcue::reaper::parse_time;
+///
+/// assert_eq!(parse_time("4:17.693"), Some(257_693_000_000));
+/// assert_eq!(parse_time("1:00:00"), Some(3_600_000_000_000));
+/// assert_eq!(parse_time("5.1.00"), None);
+/// assert_eq!(parse_time("99999999999999:00"), None);
+/// ```
+pub fn parse_time(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (seconds, whole) = parts.split_last()?;
+    let mut nanos = seconds_to_nanos(seconds)?;
+    if !whole.is_empty() && nanos >= 60_000_000_000 {
+        return None;
+    }
+    for (unit, value) in whole.iter().rev().zip(&[60u64, 3600]) {
+        if unit.is_empty() || !unit.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let unit = unit
+            .parse::<u64>()
+            .ok()?
+            .checked_mul(value * 1_000_000_000)?;
+        nanos = nanos.checked_add(unit)?;
+    }
+    Some(nanos)
+}
+
+// Quotes a field if needed. Rows are read line by line, so line breaks become spaces.
+fn csv_field(s: &str) -> String {
+    let s = s.replace("\r\n", " ").replace(['\r', '\n'], " ");
+    if s.contains([',', '"']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+// Splits one CSV line into fields, resolving quotes.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Exports a single-file `cue` as a Reaper region list CSV, for an audio file `length` CD frames
+/// long.
+///
+/// Each track becomes a region row `R<n>,Name,Start,End,Length` with times as minutes:seconds
+/// rounded to milliseconds (see [`chapters`](../chapters/fn.chapters.html) for the spans).
+/// Names are built from TITLE and PERFORMER according to the
+/// [`labels`](../chapters/struct.ExportOptions.html#structfield.labels) option, with line breaks
+/// replaced by spaces.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ExportOptions;
+/// use rcue::parser::parse_from_file;
+/// use rcue::reaper::to_reaper_csv;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let csv = to_reaper_csv(&cue, 30000, &ExportOptions::default()).unwrap();
+/// assert!(csv.ends_with("R2,Loomer,4:17.693,6:40.000,2:22.307\n"));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet does not have exactly one FILE.
+pub fn to_reaper_csv(cue: &Cue, length: u64, options: &ExportOptions) -> Result<String, CueError> {
+    let millis = |frames: u64| {
+        options
+            .rounding
+            .div(u128::from(frames_to_nanos(frames)), 1_000_000) as u64
+    };
+
+    let mut out = String::from("#,Name,Start,End,Length\n");
+    for (i, chapter) in chapters(cue, length)?.iter().enumerate() {
+        let name = options
+            .labels
+            .join(chapter.title.as_deref(), chapter.performer.as_deref())
+            .unwrap_or_default();
+        let (start, end) = (millis(chapter.start), millis(chapter.end));
+        out.push_str(&format!(
+            "R{},{},{},{},{}\n",
+            i + 1,
+            csv_field(&name),
+            format_time(start),
+            format_time(end),
+            format_time(end - start)
+        ));
+    }
+    Ok(out)
+}
+
+/// Imports a Reaper region/marker list CSV as a single-file sheet for the audio file `file`.
+///
+/// Every region (`R` row) and marker (`M` row) starts a track at its start time, rounded to CD
+/// frames as described in [`chapters::to_cue`](../chapters/fn.to_cue.html). Region ends are
+/// ignored, as tracks run until the next one. Names map to TITLE and PERFORMER according to the
+/// [`labels`](../chapters/struct.ImportOptions.html#structfield.labels) option. The `Name` and
+/// `Start` columns are found from the header row, and are the second and third otherwise. See
+/// [`parse_time`](fn.parse_time.html) for the supported time formats.
+///
+/// # Example
+///
+/// ```
+/// use rcue::chapters::ImportOptions;
+/// use rcue::reaper::from_reaper_csv;
+///
+/// let csv = "#,Name,Start,End,Length\nR1,Intro,0:00.000,1:30.000,1:30.000\nM1,Verse,1:30.000,,\n";
+/// let (cue, _) = from_reaper_csv(csv, "mix.wav", &ImportOptions::default()).unwrap();
+/// assert_eq!(cue.files[0].tracks[1].title, Some("Verse".to_string()));
+/// assert_eq!(cue.files[0].tracks[1].start().unwrap().as_secs(), 90);
+/// ```
+///
+/// # Failures
+///
+/// Fails if a row has an unsupported or invalid start time, or there are more than 99 rows.
+pub fn from_reaper_csv(
+    text: &str,
+    file: &str,
+    options: &ImportOptions,
+) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let (mut name_column, mut start_column) = (1, 2);
+    let mut marks = Vec::new();
+
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let fields = csv_fields(line.trim_end_matches('\r'));
+        let id = fields[0].trim();
+
+        if id.starts_with('#') {
+            for (column, header) in fields.iter().enumerate() {
+                match header.trim().to_ascii_lowercase().as_str() {
+                    "name" => name_column = column,
+                    "start" => start_column = column,
+                    _ => (),
+                }
+            }
+            continue;
+        }
+        if !(id.starts_with('R') || id.starts_with('M')) {
+            continue;
+        }
+
+        let start = fields.get(start_column).map_or("", |s| s.as_str());
+        let start = parse_time(start).ok_or_else(|| {
+            CueError::Parse(format!("line {}: unsupported start time {}", i + 1, start))
+        })?;
+        let name = fields.get(name_column).map_or("", |s| s.as_str());
+
+        marks.push(ChapterMark {
+            start,
+            title: if name.is_empty() {
+                None
+            } else {
+                Some(name.to_string())
+            },
+            performer: None,
+        });
+    }
+
+    to_cue(&marks, file, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chapters::LabelMapping;
+    use parser::parse_from_file;
+
+    #[test]
+    fn test_round_trip() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[1].title = Some("Loomer, \"Live\"".to_string());
+        let options = ExportOptions {
+            labels: LabelMapping::PerformerTitle,
+            ..ExportOptions::default()
+        };
+        let csv = to_reaper_csv(&cue, 30000, &options).unwrap();
+        assert!(csv.contains("R2,\"My Bloody Valentine - Loomer, \"\"Live\"\"\",4:17.693"));
+
+        let options = ImportOptions {
+            labels: LabelMapping::PerformerTitle,
+            ..ImportOptions::default()
+        };
+        let (imported, diagnostics) = from_reaper_csv(&csv, "a.wav", &options).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            chapters(&imported, 30000).unwrap(),
+            chapters(&cue, 30000).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_line_breaks_in_names() {
+        let mut cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        cue.files[0].tracks[1].title = Some("Loomer\r\n(Live,\nDemo)".to_string());
+        let csv = to_reaper_csv(&cue, 30000, &ExportOptions::default()).unwrap();
+        assert!(csv.contains("R2,\"Loomer (Live, Demo)\",4:17.693"));
+
+        let (imported, _) = from_reaper_csv(&csv, "a.wav", &ImportOptions::default()).unwrap();
+        let tracks = &imported.files[0].tracks;
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].title, Some("Loomer (Live, Demo)".to_string()));
+    }
+
+    #[test]
+    fn test_columns_from_header() {
+        let csv = "#,Start,Name\nR1,0:01:00.000,Late\nR2,5,Early\n";
+        let (cue, _) = from_reaper_csv(csv, "a.wav", &ImportOptions::default()).unwrap();
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].title, Some("Early".to_string()));
+        assert_eq!(tracks[1].start().unwrap().as_secs(), 60);
+    }
+
+    #[test]
+    fn test_measures_beats() {
+        let csv = "#,Name,Start,End,Length\nR1,Intro,1.1.00,5.1.00,4.0.00\n";
+        assert!(from_reaper_csv(csv, "a.wav", &ImportOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_long_times() {
+        assert_eq!(format_time(3_723_456), "1:02:03.456");
+        assert_eq!(parse_time("1:02:03.456"), Some(3_723_456_000_000));
+        assert_eq!(parse_time("1:60"), None);
+
+        let csv = "#,Name,Start\nR1,Intro,99999999999999:00\n";
+        match from_reaper_csv(csv, "a.wav", &ImportOptions::default()) {
+            Err(CueError::Parse(message)) => {
+                assert_eq!(message, "line 2: unsupported start time 99999999999999:00")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
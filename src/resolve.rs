@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use cue::Cue;
+
+/// Options for [`resolve`](fn.resolve.html).
+#[derive(Clone, Debug)]
+pub struct ResolveOptions {
+    /// Match names that differ from the sheet only in letter case
+    pub case_insensitive: bool,
+    /// Extensions (without the dot) tried in order when the named file is missing, as sheets of
+    /// a WAVE rip are often kept next to the compressed files
+    pub extensions: Vec<String>,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            case_insensitive: true,
+            extensions: ["flac", "ape", "wv", "tak", "wav"]
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// How a FILE of a sheet was found on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// The file exists as named (backslashes read as separators)
+    Exact,
+    /// A file exists with the same name in another letter case
+    CaseInsensitive,
+    /// A file exists with another extension
+    Extension,
+    /// No file was found
+    Missing,
+}
+
+/// The result of resolving one FILE of a sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileResolution {
+    /// The FILE as written in the sheet
+    pub file: String,
+    /// How the file was found
+    pub resolution: Resolution,
+    /// Path of the file found, joined to the sheet's directory
+    pub path: Option<PathBuf>,
+}
+
+// Finds `name` in `dir`, exactly or ignoring case.
+fn find_entry(dir: &Path, name: &str, case_insensitive: bool) -> Option<(PathBuf, bool)> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some((exact, false));
+    }
+    if !case_insensitive {
+        return None;
+    }
+
+    let lower = name.to_lowercase();
+    let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().to_lowercase() == lower)
+        .map(|entry| entry.path())
+        .collect();
+    // Several names differing only in case: pick one deterministically
+    matches.sort();
+    matches.into_iter().next().map(|path| (path, true))
+}
+
+fn resolve_file(file: &str, cue_dir: &Path, options: &ResolveOptions) -> FileResolution {
+    let missing = FileResolution {
+        file: file.to_string(),
+        resolution: Resolution::Missing,
+        path: None,
+    };
+    let normalized = file.replace('\\', "/");
+    let relative = Path::new(&normalized);
+
+    // Directories, then the file name with each candidate extension
+    let mut dir = cue_dir.to_path_buf();
+    let mut case_changed = false;
+    let mut components = relative.components().peekable();
+    let mut name = None;
+    while let Some(component) = components.next() {
+        match component {
+            Component::Normal(part) if components.peek().is_none() => {
+                name = Some(part.to_string_lossy().into_owned());
+            }
+            Component::Normal(part) => {
+                match find_entry(&dir, &part.to_string_lossy(), options.case_insensitive) {
+                    Some((found, changed)) => {
+                        dir = found;
+                        case_changed |= changed;
+                    }
+                    None => return missing,
+                }
+            }
+            other => dir.push(other.as_os_str()),
+        }
+    }
+    let name = match name {
+        Some(name) => name,
+        None => return missing,
+    };
+
+    if let Some((path, changed)) = find_entry(&dir, &name, options.case_insensitive) {
+        let resolution = if case_changed || changed {
+            Resolution::CaseInsensitive
+        } else {
+            Resolution::Exact
+        };
+        return FileResolution {
+            resolution,
+            path: Some(path),
+            ..missing
+        };
+    }
+
+    let stem = Path::new(&name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or(name);
+    for extension in &options.extensions {
+        let candidate = format!("{}.{}", stem, extension);
+        if let Some((path, _)) = find_entry(&dir, &candidate, options.case_insensitive) {
+            return FileResolution {
+                resolution: Resolution::Extension,
+                path: Some(path),
+                ..missing
+            };
+        }
+    }
+
+    missing
+}
+
+/// Resolves every FILE of `cue` against `cue_dir`, the directory of the sheet, returning one
+/// [`FileResolution`](struct.FileResolution.html) per FILE in sheet order.
+///
+/// Backslashes are read as path separators. When the named file does not exist, each directory
+/// and the file name are matched ignoring case, then the extensions of `options` are tried in
+/// order.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::resolve::{resolve, Resolution, ResolveOptions};
+/// use std::path::Path;
+///
+/// let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+/// let resolved = resolve(&cue, Path::new("test/fixtures"), &ResolveOptions::default());
+/// assert_eq!(resolved[0].resolution, Resolution::Missing);
+/// ```
+pub fn resolve(cue: &Cue, cue_dir: &Path, options: &ResolveOptions) -> Vec<FileResolution> {
+    cue.files
+        .iter()
+        .map(|file| resolve_file(&file.file, cue_dir, options))
+        .collect()
+}
+
+/// Rewrites each FILE of `cue` found by [`resolve`](fn.resolve.html) (other than
+/// [`Exact`](enum.Resolution.html#variant.Exact) matches) to the path of the file on disk,
+/// relative to `cue_dir` and with `/` separators. FILE types are kept.
+///
+/// Returns the number of FILEs rewritten.
+pub fn rewrite(cue: &mut Cue, cue_dir: &Path, resolutions: &[FileResolution]) -> usize {
+    let mut rewritten = 0;
+    for (file, resolved) in cue.files.iter_mut().zip(resolutions) {
+        if resolved.file != file.file || resolved.resolution == Resolution::Exact {
+            continue;
+        }
+        if let Some(ref path) = resolved.path {
+            let relative = path.strip_prefix(cue_dir).unwrap_or(path);
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            if name != file.file {
+                file.file = name;
+                rewritten += 1;
+            }
+        }
+    }
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cue::CueFile;
+    use test_util::temp_dir;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = temp_dir(&format!("resolve_{}", name));
+        fs::create_dir_all(dir.join("CD1")).unwrap();
+        dir
+    }
+
+    fn sheet(files: &[&str]) -> Cue {
+        let mut cue = Cue::new();
+        for file in files {
+            cue.files.push(CueFile::new(file, "WAVE"));
+        }
+        cue
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = dir("resolve");
+        for name in &["exact.wav", "CD1/album.flac", "Other.WAV", "track.ape"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let cue = sheet(&[
+            "exact.wav",
+            "cd1\\Album.wav",
+            "other.wav",
+            "track.wav",
+            "gone.wav",
+        ]);
+
+        let resolved = resolve(&cue, &dir, &ResolveOptions::default());
+        let kinds: Vec<Resolution> = resolved.iter().map(|r| r.resolution).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Resolution::Exact,
+                Resolution::Extension,
+                Resolution::CaseInsensitive,
+                Resolution::Extension,
+                Resolution::Missing,
+            ]
+        );
+        assert_eq!(resolved[1].path, Some(dir.join("CD1").join("album.flac")));
+        assert_eq!(resolved[4].path, None);
+
+        let strict = ResolveOptions {
+            case_insensitive: false,
+            extensions: vec![],
+        };
+        let resolved = resolve(&cue, &dir, &strict);
+        assert_eq!(resolved[0].resolution, Resolution::Exact);
+        assert!(resolved[1..]
+            .iter()
+            .all(|r| r.resolution == Resolution::Missing));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let dir = dir("rewrite");
+        fs::write(dir.join("CD1/album.flac"), b"").unwrap();
+        fs::write(dir.join("b.wav"), b"").unwrap();
+        let mut cue = sheet(&["CD1\\Album.wav", "b.wav", "c.wav"]);
+
+        let resolved = resolve(&cue, &dir, &ResolveOptions::default());
+        assert_eq!(rewrite(&mut cue, &dir, &resolved), 1);
+        assert_eq!(cue.files[0].file, "CD1/album.flac");
+        assert_eq!(cue.files[0].format, "WAVE");
+        assert_eq!(cue.files[1].file, "b.wav");
+        assert_eq!(cue.files[2].file, "c.wav");
+    }
+}
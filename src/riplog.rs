@@ -0,0 +1,757 @@
+use std::time::Duration;
+
+use cue::Cue;
+use errors::{CueError, Diagnostic};
+use layout::{detect, Layout};
+use util::{duration_to_frames, frames_to_duration, timestamp_to_duration};
+
+/// Program that wrote a rip log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ripper {
+    /// Exact Audio Copy
+    Eac,
+    /// X Lossless Decoder
+    Xld,
+}
+
+/// How the ripper handled pregaps of a track rip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GapHandling {
+    /// Pregaps appended to the end of the previous track
+    AppendedToPrevious,
+    /// Pregaps prepended to the start of their own track
+    AppendedToNext,
+    /// Pregaps left out of the files
+    LeftOut,
+    /// Gaps were not detected (EAC then appends them to the previous track)
+    NotDetected,
+}
+
+impl GapHandling {
+    fn describe(self) -> &'static str {
+        match self {
+            GapHandling::AppendedToPrevious => "appended to the previous track",
+            GapHandling::AppendedToNext => "appended to the next track",
+            GapHandling::LeftOut => "left out",
+            GapHandling::NotDetected => "not detected",
+        }
+    }
+
+    // The sheet layout a track rip with this gap handling produces
+    fn layout(self) -> Option<Layout> {
+        match self {
+            GapHandling::AppendedToPrevious | GapHandling::NotDetected => {
+                Some(Layout::GapsAppended)
+            }
+            GapHandling::LeftOut => Some(Layout::GapsLeftOut),
+            GapHandling::AppendedToNext => None,
+        }
+    }
+}
+
+/// A row of the log's table of contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TocEntry {
+    /// Track number
+    pub track: u32,
+    /// LBA of the track's first sector (INDEX 01)
+    pub start: u64,
+    /// LBA of the track's last sector
+    pub end: u64,
+}
+
+/// AccurateRip result of a track.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccurateRip {
+    /// The track matches the database, with the confidence (summed over AccurateRip versions)
+    Accurate {
+        /// Number of matching submissions
+        confidence: u32,
+    },
+    /// The track is in the database but does not match
+    NotAccurate,
+    /// The track is not in the database
+    NotPresent,
+}
+
+/// Results of extracting a track or a range.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Extraction {
+    /// Peak level, 1.0 being full scale
+    pub peak: Option<f64>,
+    /// CRC-32 of the test pass
+    pub test_crc: Option<u32>,
+    /// CRC-32 of the copy pass
+    pub copy_crc: Option<u32>,
+    /// AccurateRip result
+    pub accuraterip: Option<AccurateRip>,
+}
+
+/// An extracted track of a rip log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogTrack {
+    /// Track number
+    pub number: u32,
+    /// Output file name
+    pub filename: Option<String>,
+    /// Length of the detected pregap
+    pub pregap: Option<Duration>,
+    /// Extraction results
+    pub extraction: Extraction,
+}
+
+/// A parsed EAC or XLD rip log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RipLog {
+    /// Program that wrote the log
+    pub ripper: Ripper,
+    /// Read offset correction in samples
+    pub read_offset: Option<i32>,
+    /// Gap handling of a track rip
+    pub gap_handling: Option<GapHandling>,
+    /// Table of contents of the disc
+    pub toc: Vec<TocEntry>,
+    /// Results for the whole range of an image rip (EAC), or all tracks (XLD)
+    pub range: Option<Extraction>,
+    /// Extracted tracks, in log order
+    pub tracks: Vec<LogTrack>,
+}
+
+impl RipLog {
+    /// Returns the extracted track numbered `number`.
+    pub fn track(&self, number: u32) -> Option<&LogTrack> {
+        self.tracks.iter().find(|track| track.number == number)
+    }
+}
+
+/// Decodes the bytes of a rip log. EAC writes UTF-16 logs with a byte order mark; other logs
+/// are read as UTF-8, replacing invalid sequences.
+///
+/// # Example
+///
+/// ```
+/// use rcue::riplog::decode_log;
+///
+/// assert_eq!(decode_log(&[0xFF, 0xFE, b'E', 0, b'A', 0, b'C', 0]), "EAC");
+/// ```
+pub fn decode_log(bytes: &[u8]) -> String {
+    let utf16 = |big_endian: bool| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .filter(|c| c.len() == 2)
+            .map(|c| {
+                if big_endian {
+                    u16::from_be_bytes([c[0], c[1]])
+                } else {
+                    u16::from_le_bytes([c[0], c[1]])
+                }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        utf16(false)
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        utf16(true)
+    } else {
+        let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+// Returns the value of `key` in `line`, written as `key value` (EAC) or `key : value` (XLD).
+fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let head = line.get(..key.len())?;
+    let rest = &line[key.len()..];
+    if !head.eq_ignore_ascii_case(key) || !(rest.is_empty() || rest.starts_with([' ', '\t', ':'])) {
+        return None;
+    }
+    Some(rest.trim_start().trim_start_matches(':').trim())
+}
+
+fn parse_crc(value: &str) -> Result<u32, CueError> {
+    u32::from_str_radix(value.trim(), 16)
+        .map_err(|_| CueError::Parse(format!("bad CRC {} in log", value)))
+}
+
+// EAC writes `98.8 %`, XLD a fraction of full scale
+fn parse_peak(value: &str) -> Result<f64, CueError> {
+    let invalid = || CueError::Parse(format!("bad peak level {} in log", value));
+    match value.strip_suffix('%') {
+        Some(percent) => Ok(percent.trim().parse::<f64>().map_err(|_| invalid())? / 100.0),
+        None => value.trim().parse().map_err(|_| invalid()),
+    }
+}
+
+// EAC writes `h:mm:ss.ff`, XLD `mm:ss:ff`
+fn parse_pregap(value: &str) -> Result<Duration, CueError> {
+    let (clock, frames) = match value.rfind('.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => return timestamp_to_duration(value),
+    };
+    let mut seconds: u64 = 0;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.trim().parse::<u64>()?;
+    }
+    Ok(frames_to_duration(seconds * 75 + frames.parse::<u64>()?))
+}
+
+fn parse_accuraterip(text: &str) -> Option<AccurateRip> {
+    let lower = text.trim().trim_start_matches("->").to_lowercase();
+    let confidence = || {
+        let rest = &lower[lower.find("confidence")? + "confidence".len()..];
+        let end = rest.find([')', ']', ',', '/']).unwrap_or(rest.len());
+        rest[..end]
+            .split('+')
+            .map(|n| n.trim().parse::<u32>().ok())
+            .sum::<Option<u32>>()
+    };
+
+    if lower.contains("accurately ripped") || lower.starts_with("ok") {
+        Some(AccurateRip::Accurate {
+            confidence: confidence().unwrap_or(0),
+        })
+    } else if lower.contains("not present") || lower.starts_with("not found") {
+        Some(AccurateRip::NotPresent)
+    } else if lower.contains("cannot be verified")
+        || lower.contains("may not be accurate")
+        || lower.starts_with("ng")
+    {
+        Some(AccurateRip::NotAccurate)
+    } else {
+        None
+    }
+}
+
+// Parses `Track  1` (EAC) or `Track 01` (XLD), returning the number and the rest of the line.
+fn track_line(line: &str) -> Option<(u32, &str)> {
+    let rest = line.strip_prefix("Track")?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let number = rest[..digits].parse().ok()?;
+    Some((
+        number,
+        rest[digits..].trim_start().trim_start_matches(':').trim(),
+    ))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    Toc,
+    Range,
+    Summary,
+    Track,
+    Done,
+}
+
+/// Parses an EAC or XLD rip log, such as one decoded with [`decode_log`](fn.decode_log.html).
+///
+/// The read offset, gap handling, table of contents, range (or all tracks) results and each
+/// track's file name, pregap, peak level, test and copy CRCs and AccurateRip result are read.
+/// Unknown lines are ignored.
+///
+/// # Example
+///
+/// ```
+/// use rcue::riplog::{parse_log, AccurateRip, Ripper};
+///
+/// let log = std::fs::read_to_string("test/fixtures/eac.log").unwrap();
+/// let log = parse_log(&log).unwrap();
+/// assert_eq!(log.ripper, Ripper::Eac);
+/// assert_eq!(log.read_offset, Some(30));
+/// assert_eq!(log.toc.len(), 3);
+/// let accurate = AccurateRip::Accurate { confidence: 12 };
+/// assert_eq!(log.tracks[0].extraction.accuraterip, Some(accurate));
+/// ```
+///
+/// # Failures
+///
+/// Fails if the text is not an EAC or XLD log, or a CRC, peak level, pregap or read offset can
+/// not be parsed.
+pub fn parse_log(text: &str) -> Result<RipLog, CueError> {
+    let text = text.trim_start_matches('\u{feff}');
+    let first = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("");
+    let ripper = if first.starts_with("Exact Audio Copy")
+        || first.starts_with("EAC extraction logfile")
+    {
+        Ripper::Eac
+    } else if first.starts_with("X Lossless Decoder") || first.starts_with("XLD extraction logfile")
+    {
+        Ripper::Xld
+    } else {
+        return Err(CueError::Parse("not an EAC or XLD log".to_string()));
+    };
+
+    let mut log = RipLog {
+        ripper,
+        read_offset: None,
+        gap_handling: None,
+        toc: Vec::new(),
+        range: None,
+        tracks: Vec::new(),
+    };
+    let mut section = Section::Header;
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("TOC of the extracted CD") {
+            section = Section::Toc;
+            continue;
+        }
+        if line.starts_with("Range status and errors") || line == "All Tracks" {
+            section = Section::Range;
+            log.range = Some(Extraction::default());
+            continue;
+        }
+        if line.to_lowercase().starts_with("accuraterip summary") {
+            section = Section::Summary;
+            continue;
+        }
+        if line.starts_with("End of status report") {
+            section = Section::Done;
+            continue;
+        }
+
+        if let Some((number, rest)) = track_line(line) {
+            match section {
+                Section::Summary | Section::Range if !rest.is_empty() => {
+                    // AccurateRip results of an image rip or XLD's summary
+                    let status = parse_accuraterip(rest);
+                    if log.track(number).is_none() {
+                        log.tracks.push(LogTrack {
+                            number,
+                            filename: None,
+                            pregap: None,
+                            extraction: Extraction::default(),
+                        });
+                    }
+                    if let Some(track) = log.tracks.iter_mut().find(|t| t.number == number) {
+                        track.extraction.accuraterip = status.or(track.extraction.accuraterip);
+                    }
+                    continue;
+                }
+                _ if rest.is_empty() => {
+                    section = Section::Track;
+                    if let Some(i) = log.tracks.iter().position(|t| t.number == number) {
+                        // Moves an entry created by a summary to the end
+                        let track = log.tracks.remove(i);
+                        log.tracks.push(track);
+                    } else {
+                        log.tracks.push(LogTrack {
+                            number,
+                            filename: None,
+                            pregap: None,
+                            extraction: Extraction::default(),
+                        });
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        match section {
+            Section::Header => {
+                if let Some(offset) = field(line, "Read offset correction") {
+                    log.read_offset = Some(offset.parse().map_err(|_| {
+                        CueError::Parse(format!("bad read offset {} in log", offset))
+                    })?);
+                } else if let Some(gaps) =
+                    field(line, "Gap handling").or_else(|| field(line, "Gap status"))
+                {
+                    let gaps = gaps.to_lowercase();
+                    log.gap_handling = Some(
+                        if gaps.contains("not detected") || gaps.contains("not analyzed") {
+                            GapHandling::NotDetected
+                        } else if gaps.contains("next track") {
+                            GapHandling::AppendedToNext
+                        } else if gaps.contains("left out") {
+                            GapHandling::LeftOut
+                        } else {
+                            GapHandling::AppendedToPrevious
+                        },
+                    );
+                }
+            }
+            Section::Toc => {
+                let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+                if let [track, _, _, start, end] = columns[..] {
+                    if let (Ok(track), Ok(start), Ok(end)) =
+                        (track.parse(), start.parse(), end.parse())
+                    {
+                        log.toc.push(TocEntry { track, start, end });
+                    }
+                }
+            }
+            Section::Range | Section::Track => {
+                let extraction = if section == Section::Range {
+                    log.range.as_mut()
+                } else {
+                    log.tracks.last_mut().map(|track| &mut track.extraction)
+                };
+                let extraction = match extraction {
+                    Some(extraction) => extraction,
+                    None => continue,
+                };
+
+                if field(line, "CRC32 hash (skip zero)").is_some() {
+                    continue;
+                } else if let Some(crc) =
+                    field(line, "Test CRC").or_else(|| field(line, "CRC32 hash (test run)"))
+                {
+                    extraction.test_crc = Some(parse_crc(crc)?);
+                } else if let Some(crc) =
+                    field(line, "Copy CRC").or_else(|| field(line, "CRC32 hash"))
+                {
+                    extraction.copy_crc = Some(parse_crc(crc)?);
+                } else if let Some(peak) = field(line, "Peak level").or_else(|| field(line, "Peak"))
+                {
+                    extraction.peak = Some(parse_peak(peak)?);
+                } else if let Some(status) = parse_accuraterip(line) {
+                    extraction.accuraterip = Some(status);
+                } else if section == Section::Track {
+                    let track = log.tracks.last_mut().unwrap();
+                    if let Some(filename) = field(line, "Filename") {
+                        track.filename = Some(filename.to_string());
+                    } else if let Some(pregap) = field(line, "Pre-gap length") {
+                        track.pregap = Some(parse_pregap(pregap)?);
+                    }
+                }
+            }
+            Section::Summary | Section::Done => {}
+        }
+    }
+
+    Ok(log)
+}
+
+/// Cross-checks a rip log against the sheet ripped with it.
+///
+/// Reported as diagnostics:
+///
+/// * tracks in the log's TOC or extracted in the log but missing from the sheet, and sheet
+///   tracks missing from the log
+/// * tracks whose INDEX 01, relative to the first track's, is not where the log's TOC puts
+///   it, using [`Cue::track_lbas`](../cue/struct.Cue.html#method.track_lbas) with
+///   `file_lengths` (only needed for multi-file sheets)
+/// * pregaps (INDEX 00 to INDEX 01 in the same FILE, or PREGAP) of a different length than the
+///   log's
+/// * a multi-file sheet whose [layout](../layout/fn.detect.html) does not match the log's gap
+///   handling
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::riplog::{cross_check, parse_log};
+///
+/// let log = parse_log(&std::fs::read_to_string("test/fixtures/eac.log").unwrap()).unwrap();
+/// let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+/// let diagnostics = cross_check(&log, &cue, &[19327]);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].to_string(), "track 03: in the log TOC but not in the sheet");
+/// ```
+pub fn cross_check(log: &RipLog, cue: &Cue, file_lengths: &[u64]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let tracks: Vec<_> = cue
+        .files
+        .iter()
+        .flat_map(|file| file.tracks.iter())
+        .collect();
+    let numbers: Vec<Option<u32>> = tracks.iter().map(|t| t.no.parse().ok()).collect();
+    let in_sheet = |number: u32| numbers.contains(&Some(number));
+
+    for entry in &log.toc {
+        if !in_sheet(entry.track) {
+            diagnostics.push(Diagnostic::new(
+                Some(entry.track),
+                "in the log TOC but not in the sheet",
+            ));
+        }
+    }
+    for track in &log.tracks {
+        if !in_sheet(track.number) && !log.toc.iter().any(|e| e.track == track.number) {
+            diagnostics.push(Diagnostic::new(
+                Some(track.number),
+                "extracted in the log but not in the sheet",
+            ));
+        }
+    }
+    // Only track sections name a file; an image rip's AccurateRip summary lists tracks too
+    let track_rip = log.tracks.iter().any(|track| track.filename.is_some());
+    for number in numbers.iter().flatten() {
+        if !log.toc.is_empty() && !log.toc.iter().any(|e| e.track == *number) {
+            diagnostics.push(Diagnostic::new(
+                Some(*number),
+                "in the sheet but not in the log TOC",
+            ));
+        } else if track_rip && log.track(*number).is_none() {
+            diagnostics.push(Diagnostic::new(Some(*number), "not extracted in the log"));
+        }
+    }
+
+    match cue.track_lbas(file_lengths) {
+        Ok(lbas) => {
+            let placed: Vec<(u32, u64, u64)> = numbers
+                .iter()
+                .zip(&lbas)
+                .filter_map(|(number, lba)| {
+                    let number = (*number)?;
+                    let entry = log.toc.iter().find(|e| e.track == number)?;
+                    Some((number, (*lba)?, entry.start))
+                })
+                .collect();
+            if let Some(&(_, first_lba, first_start)) = placed.first() {
+                // Compared as offsets from the first track, which may be out of order
+                for &(number, lba, start) in &placed {
+                    let lba = lba as i64 - first_lba as i64 + first_start as i64;
+                    if lba != start as i64 {
+                        diagnostics.push(Diagnostic::new(
+                            Some(number),
+                            &format!("INDEX 01 at LBA {}, log TOC says {}", lba, start),
+                        ));
+                    }
+                }
+            }
+        }
+        Err(err) => diagnostics.push(Diagnostic::new(
+            None,
+            &format!("track positions not compared: {}", err),
+        )),
+    }
+
+    for (track, number) in tracks.iter().zip(&numbers) {
+        let logged = match number.and_then(|n| log.track(n)).and_then(|t| t.pregap) {
+            Some(pregap) => duration_to_frames(&pregap),
+            None => continue,
+        };
+        let pregap = match (track.index(0), track.index(1), track.pregap) {
+            (Some(start), Some(end), _) if track.previous_file_indices.is_empty() => {
+                duration_to_frames(&end).saturating_sub(duration_to_frames(&start))
+            }
+            (None, _, Some(pregap)) if track.previous_file_indices.is_empty() => {
+                duration_to_frames(&pregap)
+            }
+            _ => continue,
+        };
+        if pregap != logged {
+            diagnostics.push(Diagnostic::new(
+                *number,
+                &format!("pregap of {} frames, log says {}", pregap, logged),
+            ));
+        }
+    }
+
+    if let Some(handling) = log.gap_handling {
+        match detect(cue) {
+            Some(Layout::SingleFile) | None => {}
+            Some(layout) if Some(layout) != handling.layout() => {
+                diagnostics.push(Diagnostic::new(
+                    None,
+                    &format!(
+                        "sheet layout is {:?}, but the log says gaps were {}",
+                        layout,
+                        handling.describe()
+                    ),
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{parse, parse_from_file};
+    use std::fs;
+    use std::io::Cursor;
+
+    fn eac() -> RipLog {
+        parse_log(&fs::read_to_string("test/fixtures/eac.log").unwrap()).unwrap()
+    }
+
+    fn xld() -> RipLog {
+        parse_log(&fs::read_to_string("test/fixtures/xld.log").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_eac() {
+        let log = eac();
+        assert_eq!(log.gap_handling, Some(GapHandling::AppendedToPrevious));
+        assert_eq!(
+            log.toc[1],
+            TocEntry {
+                track: 2,
+                start: 19327,
+                end: 31166
+            }
+        );
+        assert_eq!(log.range, None);
+        assert_eq!(log.tracks.len(), 3);
+
+        let one = &log.tracks[0];
+        assert_eq!(
+            one.filename,
+            Some("C:\\Rips\\01 - Only Shallow.wav".to_string())
+        );
+        assert_eq!(one.pregap, None);
+        assert_eq!(one.extraction.peak, Some(0.988));
+        assert_eq!(one.extraction.test_crc, Some(0x4F8E_7A3C));
+
+        let two = &log.tracks[1];
+        assert_eq!(two.pregap, Some(Duration::new(2, 0)));
+        assert_eq!(two.extraction.copy_crc, Some(0x0A1B_2C3E));
+        assert_eq!(two.extraction.accuraterip, Some(AccurateRip::NotAccurate));
+
+        let three = &log.tracks[2];
+        assert_eq!(three.pregap.map(|p| duration_to_frames(&p)), Some(105));
+        assert_eq!(three.extraction.test_crc, None);
+        assert_eq!(three.extraction.accuraterip, Some(AccurateRip::NotPresent));
+    }
+
+    #[test]
+    fn test_xld() {
+        let log = xld();
+        assert_eq!(log.ripper, Ripper::Xld);
+        assert_eq!(log.read_offset, Some(667));
+        assert_eq!(log.gap_handling, Some(GapHandling::AppendedToPrevious));
+        assert_eq!(log.toc.len(), 2);
+
+        let range = log.range.unwrap();
+        assert_eq!(range.peak, Some(0.998932));
+        assert_eq!(range.copy_crc, Some(0x9ABC_DEF0));
+
+        assert_eq!(
+            log.tracks.iter().map(|t| t.number).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            log.tracks[0].extraction.accuraterip,
+            Some(AccurateRip::Accurate { confidence: 5 })
+        );
+        assert_eq!(log.tracks[0].extraction.test_crc, Some(0x4F8E_7A3C));
+        assert_eq!(log.tracks[1].pregap, Some(Duration::new(2, 0)));
+        assert_eq!(log.tracks[1].extraction.peak, Some(1.0));
+        assert_eq!(
+            log.tracks[1].extraction.accuraterip,
+            Some(AccurateRip::NotAccurate)
+        );
+    }
+
+    #[test]
+    fn test_image_rip_summary() {
+        let text = "Exact Audio Copy V1.0 beta 3\n\nRange status and errors\n\nSelected range\n\n\
+                    Peak level 100.0 %\nTest CRC 11111111\nCopy CRC 22222222\nCopy OK\n\n\
+                    AccurateRip summary\n\n\
+                    Track  1  accurately ripped (confidence 7)  [AAAAAAAA]  (AR v2)\n\
+                    Track  2  not present in database\n";
+        let log = parse_log(text).unwrap();
+        assert_eq!(
+            log.range,
+            Some(Extraction {
+                peak: Some(1.0),
+                test_crc: Some(0x1111_1111),
+                copy_crc: Some(0x2222_2222),
+                accuraterip: None,
+            })
+        );
+        assert_eq!(
+            log.tracks[0].extraction.accuraterip,
+            Some(AccurateRip::Accurate { confidence: 7 })
+        );
+        assert_eq!(
+            log.tracks[1].extraction.accuraterip,
+            Some(AccurateRip::NotPresent)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse_log("REM GENRE Rock\n").is_err());
+        assert!(parse_log("Exact Audio Copy\nRead offset correction : lots\n").is_err());
+        assert!(parse_log("Exact Audio Copy\nTrack  1\n  Copy CRC XYZ\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let mut utf16 = vec![0xFE, 0xFF];
+        for unit in "Track  1".encode_utf16() {
+            utf16.extend(&unit.to_be_bytes());
+        }
+        assert_eq!(decode_log(&utf16), "Track  1");
+        assert_eq!(decode_log(b"\xEF\xBB\xBFXLD"), "XLD");
+    }
+
+    #[test]
+    fn test_cross_check_single_file() {
+        let sheet = "FILE \"a.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+                     TRACK 02 AUDIO\n    INDEX 00 04:15:52\n    INDEX 01 04:17:52\n\
+                     TRACK 03 AUDIO\n    INDEX 00 06:54:50\n    INDEX 01 06:55:42\n";
+        let cue = parse(&mut Cursor::new(sheet), true).unwrap();
+        assert_eq!(
+            cross_check(&eac(), &cue, &[]),
+            vec![Diagnostic::new(
+                Some(3),
+                "pregap of 67 frames, log says 105"
+            )]
+        );
+
+        let moved = sheet.replace("06:55:42", "06:55:45");
+        let cue = parse(&mut Cursor::new(moved), true).unwrap();
+        assert_eq!(
+            cross_check(&eac(), &cue, &[])[0],
+            Diagnostic::new(Some(3), "INDEX 01 at LBA 31170, log TOC says 31167")
+        );
+
+        let early = sheet
+            .replace("04:17:52", "00:00:00")
+            .replacen("00:00:00", "04:17:52", 1);
+        let cue = parse(&mut Cursor::new(early), false).unwrap();
+        assert_eq!(
+            cross_check(&eac(), &cue, &[])[0],
+            Diagnostic::new(Some(2), "INDEX 01 at LBA -19327, log TOC says 19327")
+        );
+    }
+
+    #[test]
+    fn test_cross_check_layout_and_missing() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        let mut log = xld();
+        assert!(cross_check(&log, &cue, &[19327]).is_empty());
+
+        log.gap_handling = Some(GapHandling::LeftOut);
+        log.tracks.pop();
+        let diagnostics = cross_check(&log, &cue, &[]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::new(Some(2), "not extracted in the log"),
+                Diagnostic::new(
+                    None,
+                    "track positions not compared: \
+                     Parse error: missing length of FILE 01 - Only Shallow.wav"
+                ),
+                Diagnostic::new(
+                    None,
+                    "sheet layout is GapsAppended, but the log says gaps were left out"
+                ),
+            ]
+        );
+    }
+}
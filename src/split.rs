@@ -0,0 +1,332 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use cue::{Cue, CueFile, PregapMode, Span, Track};
+use errors::CueError;
+use util::fill_template;
+use wav::{copy_pcm, write_header, PcmSource};
+
+/// Options for [`split`](fn.split.html).
+#[derive(Clone, Debug)]
+pub struct SplitOptions {
+    /// Which track the audio between INDEX 00 and INDEX 01 is written to
+    pub pregap: PregapMode,
+    /// Output filename template, see [`render_template`](fn.render_template.html)
+    pub template: String,
+    /// Directory the split files are written to
+    pub output_dir: PathBuf,
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        Self {
+            pregap: PregapMode::Append,
+            template: "{no} - {title}.wav".to_string(),
+            output_dir: PathBuf::from("."),
+        }
+    }
+}
+
+/// Renders an output filename for `track` in `file`.
+///
+/// The following placeholders are replaced:
+///
+/// * `{no}`: track number as written in the sheet (eg. `01`)
+/// * `{title}`: track title, or `Track {no}` if the track has no title
+/// * `{performer}`: track performer, or an empty string
+/// * `{file}`: name of the source file without its directory and extension
+///
+/// Path separators and characters reserved on Windows are replaced with `_` in the
+/// substituted values, see [`fill_template`](../util/fn.fill_template.html).
+///
+/// # Example
+///
+/// ```
+/// use rcue::cue::{CueFile, Track};
+/// use rcue::split::render_template;
+///
+/// let file = CueFile::new("rip/album.wav", "WAVE");
+/// let mut track = Track::new("03", "AUDIO");
+/// track.title = Some("AC/DC".to_string());
+/// assert_eq!(render_template("{file} {no} {title}.wav", &track, &file), "album 03 AC_DC.wav");
+/// ```
+pub fn render_template(template: &str, track: &Track, file: &CueFile) -> String {
+    let stem = Path::new(&file.file.replace('\\', "/"))
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let title = track
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Track {}", track.no));
+
+    fill_template(
+        template,
+        &[
+            ("no", &track.no),
+            ("title", &title),
+            (
+                "performer",
+                track.performer.as_ref().map_or("", |s| s.as_str()),
+            ),
+            ("file", &stem),
+        ],
+    )
+}
+
+/// Splits every file referenced by `cue` into one WAVE file per AUDIO track.
+///
+/// FILE paths are resolved relative to `cue_dir`. Supported FILE types are `WAVE` (PCM),
+/// `BINARY` and `MOTOROLA`; raw big endian audio is converted to little endian. Cut points
+/// are computed from the INDEX timestamps and are exact to the sample. Non-AUDIO tracks are
+/// skipped.
+///
+/// Returns the paths of the written files in track order.
+///
+/// # Failures
+///
+/// Fails before writing anything if two tracks would be written to the same path, or a track
+/// over one of the referenced files. Fails if a referenced file can not be read, has an
+/// unsupported type, or if an output file can not be written.
+pub fn split(cue: &Cue, cue_dir: &Path, options: &SplitOptions) -> Result<Vec<PathBuf>, CueError> {
+    let mut sources = Vec::new();
+    for file in &cue.files {
+        sources.push(fs::canonicalize(
+            cue_dir.join(file.file.replace('\\', "/")),
+        )?);
+    }
+    let mut all = Vec::new();
+    for (i, file) in cue.files.iter().enumerate() {
+        all.extend(outputs(file, cue.files.get(i + 1), options));
+    }
+    for (track, _, path) in &all {
+        if path.exists() && sources.contains(&fs::canonicalize(path)?) {
+            return Err(CueError::Parse(format!(
+                "track {} would overwrite the source file {}",
+                track.no,
+                path.display()
+            )));
+        }
+    }
+    check_outputs(&all)?;
+
+    let mut written = Vec::new();
+
+    for (i, file) in cue.files.iter().enumerate() {
+        let mut source = File::open(cue_dir.join(file.file.replace('\\', "/")))?;
+        written.extend(split_file(
+            file,
+            cue.files.get(i + 1),
+            &mut source,
+            options,
+        )?);
+    }
+
+    Ok(written)
+}
+
+/// Splits a single [`CueFile`](../cue/struct.CueFile.html) whose audio is read from `source`.
+/// `next` is the FILE after `file` in the sheet, whose first track's pregap may be at the end of
+/// `file`.
+///
+/// See [`split`](fn.split.html).
+///
+/// # Failures
+///
+/// Fails if `source` has an unsupported type, if two tracks would be written to the same path,
+/// or if an output file can not be written.
+pub fn split_file<R: Read + Seek>(
+    file: &CueFile,
+    next: Option<&CueFile>,
+    source: &mut R,
+    options: &SplitOptions,
+) -> Result<Vec<PathBuf>, CueError> {
+    let pcm = PcmSource::open(source, &file.format)?;
+    let outputs = outputs(file, next, options);
+    check_outputs(&outputs)?;
+    let mut written = Vec::new();
+
+    for (_, span, path) in outputs {
+        let start = pcm.format.frames_to_bytes(span.start).min(pcm.data_len);
+        let end = span
+            .end
+            .map_or(pcm.data_len, |end| pcm.format.frames_to_bytes(end))
+            .min(pcm.data_len);
+        let len = end.saturating_sub(start);
+
+        let mut out = BufWriter::new(File::create(&path)?);
+        let format = pcm.format;
+        write_header(&mut out, &format, len)?;
+        source.seek(SeekFrom::Start(pcm.data_offset + start))?;
+        copy_pcm(source, &mut out, len, &format)?;
+        out.flush()?;
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+// The AUDIO tracks of `file` with their spans and output paths.
+fn outputs<'a>(
+    file: &'a CueFile,
+    next: Option<&CueFile>,
+    options: &SplitOptions,
+) -> Vec<(&'a Track, Span, PathBuf)> {
+    file.tracks
+        .iter()
+        .zip(file.track_spans_before(options.pregap, next))
+        .filter_map(|(track, span)| match span {
+            Some(span) if track.format.eq_ignore_ascii_case("AUDIO") => {
+                let name = render_template(&options.template, track, file);
+                Some((track, span, options.output_dir.join(name)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Fails if two tracks would be written to the same path.
+fn check_outputs(outputs: &[(&Track, Span, PathBuf)]) -> Result<(), CueError> {
+    for (i, (track, _, path)) in outputs.iter().enumerate() {
+        if outputs[..i].iter().any(|(_, _, earlier)| earlier == path) {
+            return Err(CueError::Parse(format!(
+                "track {} would overwrite {}, written for an earlier track",
+                track.no,
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse;
+    use std::fs;
+    use std::io::Cursor;
+    use test_util::temp_dir;
+    use wav::{header, read_header, PcmFormat};
+
+    fn output_dir(name: &str) -> PathBuf {
+        temp_dir(&format!("split_{}", name))
+    }
+
+    fn sheet(indices: &str) -> Cue {
+        let text = format!(
+            "FILE \"image.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"One\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n{}",
+            indices
+        );
+        parse(&mut Cursor::new(text), true).unwrap()
+    }
+
+    fn image(frames: u64) -> Cursor<Vec<u8>> {
+        let len = PcmFormat::cd().frames_to_bytes(frames);
+        let mut wav = header(&PcmFormat::cd(), len);
+        wav.extend((0..len).map(|i| (i / 2352) as u8));
+        Cursor::new(wav)
+    }
+
+    fn data_len(path: &Path) -> u64 {
+        read_header(&mut File::open(path).unwrap())
+            .unwrap()
+            .data_len
+    }
+
+    #[test]
+    fn test_split_pregap_modes() {
+        let cue = sheet("    INDEX 00 00:00:10\n    INDEX 01 00:00:12\n");
+        let expected = vec![
+            (PregapMode::Append, 12, 8),
+            (PregapMode::Prepend, 10, 10),
+            (PregapMode::Discard, 10, 8),
+        ];
+
+        for (mode, first, second) in expected {
+            let options = SplitOptions {
+                pregap: mode,
+                output_dir: output_dir(&format!("{:?}", mode)),
+                ..SplitOptions::default()
+            };
+            let paths = split_file(&cue.files[0], None, &mut image(20), &options).unwrap();
+            assert_eq!(paths.len(), 2);
+            assert_eq!(paths[0].file_name().unwrap(), "01 - One.wav");
+            assert_eq!(paths[1].file_name().unwrap(), "02 - Track 02.wav");
+            assert_eq!(data_len(&paths[0]), first * 2352);
+            assert_eq!(data_len(&paths[1]), second * 2352);
+        }
+    }
+
+    #[test]
+    fn test_split_sample_accurate() {
+        let cue = sheet("    INDEX 01 00:00:03\n");
+        let options = SplitOptions {
+            output_dir: output_dir("accurate"),
+            ..SplitOptions::default()
+        };
+        let paths = split_file(&cue.files[0], None, &mut image(5), &options).unwrap();
+        let second = fs::read(&paths[1]).unwrap();
+        assert_eq!(second.len(), 44 + 2 * 2352);
+        assert_eq!(second[44], 3);
+        assert_eq!(second[second.len() - 1], 4);
+    }
+
+    #[test]
+    fn test_split_from_sheet_dir() {
+        let dir = output_dir("sheet_dir");
+        fs::write(dir.join("image.wav"), image(4).into_inner()).unwrap();
+        let cue = sheet("    INDEX 01 00:00:01\n");
+        let options = SplitOptions {
+            template: "{file}-{no}.wav".to_string(),
+            output_dir: dir.clone(),
+            ..SplitOptions::default()
+        };
+        let paths = split(&cue, &dir, &options).unwrap();
+        assert_eq!(
+            paths,
+            vec![dir.join("image-01.wav"), dir.join("image-02.wav")]
+        );
+    }
+
+    #[test]
+    fn test_split_overwrites() {
+        let dir = output_dir("overwrites");
+        fs::write(dir.join("image.wav"), image(4).into_inner()).unwrap();
+        let cue = sheet("    TITLE \"One\"\n    INDEX 01 00:00:01\n");
+        let options = SplitOptions {
+            template: "{title}.wav".to_string(),
+            output_dir: dir.clone(),
+            ..SplitOptions::default()
+        };
+        assert!(split_file(&cue.files[0], None, &mut image(4), &options).is_err());
+        assert!(split(&cue, &dir, &options).is_err());
+        assert!(!dir.join("One.wav").exists());
+
+        let options = SplitOptions {
+            template: "{file}.wav".to_string(),
+            ..options
+        };
+        let err = split(&sheet("    INDEX 01 00:00:01\n"), &dir, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Parse error: track 01 would overwrite the source file {}",
+                dir.join("image.wav").display()
+            )
+        );
+        assert_eq!(data_len(&dir.join("image.wav")), 4 * 2352);
+    }
+
+    #[test]
+    fn test_split_unsupported_type() {
+        let mut cue = sheet("    INDEX 01 00:00:01\n");
+        cue.files[0].format = "MP3".to_string();
+        let options = SplitOptions {
+            output_dir: output_dir("unsupported"),
+            ..SplitOptions::default()
+        };
+        assert!(split_file(&cue.files[0], None, &mut image(4), &options).is_err());
+    }
+}
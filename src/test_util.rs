@@ -0,0 +1,12 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// Creates an empty directory for the test `name`, unique to the running test process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rcue_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,1135 @@
+use std::collections::HashMap;
+
+use cue::{Cue, CueFile, Track, TrackMode};
+use errors::{CueError, Diagnostic};
+use util::{duration_to_frames, frames_to_duration, frames_to_timestamp};
+
+/// CD-TEXT items that are kept in `REM` comments when reading a TOC, and written back as
+/// CD-TEXT items.
+const CD_TEXT_REMS: &[&str] = &["ARRANGER", "COMPOSER", "DISC_ID", "MESSAGE"];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, CueError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Comma),
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(d @ '0'..='7') => {
+                                let mut code = d.to_digit(8).unwrap();
+                                for _ in 0..2 {
+                                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                                        Some(digit) => {
+                                            code = code * 8 + digit;
+                                            chars.next();
+                                        }
+                                        None => break,
+                                    }
+                                }
+                                s.push(std::char::from_u32(code).unwrap_or('?'));
+                            }
+                            Some('n') => s.push('\n'),
+                            Some(c) => s.push(c),
+                            None => return Err(CueError::Parse("unterminated string".to_string())),
+                        },
+                        Some(c) => s.push(c),
+                        None => return Err(CueError::Parse("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == ',' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parses an `MM:SS:FF` position into CD frames.
+fn parse_msf(s: &str) -> Option<u64> {
+    let parts: Vec<u64> = s
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    match parts[..] {
+        [minutes, seconds, frames] if seconds < 60 && frames < 75 => {
+            Some((minutes * 60 + seconds) * 75 + frames)
+        }
+        _ => None,
+    }
+}
+
+fn parse_toc_mode(mode: &str) -> Option<TrackMode> {
+    match mode {
+        "AUDIO" => Some(TrackMode::Audio),
+        "MODE1" => Some(TrackMode::Mode1_2048),
+        "MODE1_RAW" => Some(TrackMode::Mode1_2352),
+        "MODE2" | "MODE2_FORM_MIX" => Some(TrackMode::Mode2_2336),
+        "MODE2_FORM1" => Some(TrackMode::Mode2_2048),
+        "MODE2_FORM2" => Some(TrackMode::Mode2_2324),
+        "MODE2_RAW" => Some(TrackMode::Mode2_2352),
+        _ => None,
+    }
+}
+
+fn toc_mode(mode: TrackMode) -> Option<&'static str> {
+    match mode {
+        TrackMode::Audio => Some("AUDIO"),
+        TrackMode::Mode1_2048 => Some("MODE1"),
+        TrackMode::Mode1_2352 => Some("MODE1_RAW"),
+        TrackMode::Mode2_2048 => Some("MODE2_FORM1"),
+        TrackMode::Mode2_2324 => Some("MODE2_FORM2"),
+        TrackMode::Mode2_2336 => Some("MODE2"),
+        TrackMode::Mode2_2352 => Some("MODE2_RAW"),
+        TrackMode::Cdg | TrackMode::Cdi2336 | TrackMode::Cdi2352 => None,
+    }
+}
+
+#[derive(Debug)]
+enum Segment {
+    // A FILE/AUDIOFILE or DATAFILE segment placed in its file, in frames
+    Data {
+        file: String,
+        start: u64,
+        length: Option<u64>,
+    },
+    // SILENCE or ZERO
+    Silence(u64),
+}
+
+#[derive(Debug, Default)]
+struct TocTrack {
+    mode: Option<TrackMode>,
+    flags: Vec<String>,
+    isrc: Option<String>,
+    cd_text: Vec<(String, String)>,
+    segments: Vec<Segment>,
+    // Position of INDEX 01 from the start of the track's data
+    start: Option<u64>,
+    // Positions of INDEX 02 and later, relative to INDEX 01
+    indices: Vec<u64>,
+}
+
+impl TocTrack {
+    fn length(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| match *segment {
+                Segment::Data { length, .. } => length.unwrap_or(0),
+                Segment::Silence(length) => length,
+            })
+            .sum()
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    diagnostics: Vec<Diagnostic>,
+    // Frame, byte offset and sector size of each segment read from each file, and of the end of
+    // the last one
+    origins: HashMap<String, Vec<(u64, u64, u64)>>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, CueError> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            other => Err(CueError::Parse(format!(
+                "expected {}, got {:?}",
+                what, other
+            ))),
+        }
+    }
+
+    fn string(&mut self, what: &str) -> Result<String, CueError> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s),
+            other => Err(CueError::Parse(format!(
+                "expected {}, got {:?}",
+                what, other
+            ))),
+        }
+    }
+
+    fn open(&mut self) -> Result<(), CueError> {
+        match self.next() {
+            Some(Token::Open) => Ok(()),
+            other => Err(CueError::Parse(format!("expected {{, got {:?}", other))),
+        }
+    }
+
+    fn diagnose(&mut self, track: Option<u32>, message: &str) {
+        self.diagnostics.push(Diagnostic::new(track, message));
+    }
+
+    // Reads a length or position: `MM:SS:FF`, or a sample count at 44.1 kHz.
+    fn length(&mut self, track: Option<u32>) -> Result<u64, CueError> {
+        let word = self.word("a length")?;
+        if let Some(frames) = parse_msf(&word) {
+            return Ok(frames);
+        }
+        let samples: u64 = word
+            .parse()
+            .map_err(|_| CueError::Parse(format!("invalid length {}", word)))?;
+        if !samples.is_multiple_of(588) {
+            self.diagnose(
+                track,
+                &format!("{} samples is not a whole CD frame, truncated", samples),
+            );
+        }
+        Ok(samples / 588)
+    }
+
+    fn is_length(&self) -> bool {
+        self.peek_word().is_some_and(|word| {
+            parse_msf(word).is_some() || word.chars().all(|c| c.is_ascii_digit())
+        })
+    }
+
+    // Reads `#offset`, a byte offset into a file, if present.
+    fn byte_offset(&mut self) -> Result<Option<u64>, CueError> {
+        match self.peek_word() {
+            Some(word) if word.starts_with('#') => {
+                let offset = word[1..]
+                    .parse()
+                    .map_err(|_| CueError::Parse(format!("invalid offset {}", word)))?;
+                self.pos += 1;
+                Ok(Some(offset))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Skips a `{ ... }` binary CD-TEXT value.
+    fn skip_block(&mut self) -> Result<(), CueError> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some(Token::Open) => depth += 1,
+                Some(Token::Close) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Some(_) => (),
+                None => return Err(CueError::Parse("unterminated block".to_string())),
+            }
+        }
+    }
+
+    // Reads a CD_TEXT block, returning the string items of the first language.
+    fn cd_text(&mut self, track: Option<u32>) -> Result<Vec<(String, String)>, CueError> {
+        self.open()?;
+        let mut items = Vec::new();
+        let mut first_language: Option<String> = None;
+
+        loop {
+            match self.next() {
+                Some(Token::Close) => return Ok(items),
+                Some(Token::Word(ref word)) if word == "LANGUAGE_MAP" => self.skip_block_after()?,
+                Some(Token::Word(ref word)) if word == "LANGUAGE" => {
+                    let language = self.word("a language number")?;
+                    let keep = match first_language {
+                        None => {
+                            first_language = Some(language.clone());
+                            true
+                        }
+                        Some(ref first) => *first == language,
+                    };
+                    if !keep {
+                        self.diagnose(track, &format!("CD_TEXT language {} dropped", language));
+                        self.skip_block()?;
+                        continue;
+                    }
+
+                    self.open()?;
+                    loop {
+                        match self.next() {
+                            Some(Token::Close) => break,
+                            Some(Token::Word(item)) => match self.peek() {
+                                Some(Token::Str(_)) => {
+                                    let value = self.string("a CD_TEXT value")?;
+                                    items.push((item, value));
+                                }
+                                Some(Token::Open) => {
+                                    self.skip_block()?;
+                                    self.diagnose(
+                                        track,
+                                        &format!("binary CD_TEXT item {} dropped", item),
+                                    );
+                                }
+                                other => {
+                                    return Err(CueError::Parse(format!(
+                                        "bad CD_TEXT value for {}: {:?}",
+                                        item, other
+                                    )))
+                                }
+                            },
+                            other => {
+                                return Err(CueError::Parse(format!(
+                                    "bad CD_TEXT item: {:?}",
+                                    other
+                                )))
+                            }
+                        }
+                    }
+                }
+                other => return Err(CueError::Parse(format!("bad CD_TEXT block: {:?}", other))),
+            }
+        }
+    }
+
+    fn skip_block_after(&mut self) -> Result<(), CueError> {
+        match self.peek() {
+            Some(Token::Open) => self.skip_block(),
+            other => Err(CueError::Parse(format!("expected {{, got {:?}", other))),
+        }
+    }
+
+    // Returns the frame at `byte_offset` in `file`, or after the last segment of the file if no
+    // offset is given.
+    fn place(&mut self, track: u32, file: &str, byte_offset: Option<u64>, sector_size: u64) -> u64 {
+        let origins = self.origins.get(file).cloned().unwrap_or_default();
+        let (frame, byte, size) = match byte_offset {
+            Some(offset) => origins
+                .iter()
+                .rev()
+                .find(|(_, byte, _)| *byte <= offset)
+                .cloned()
+                .unwrap_or((0, 0, sector_size)),
+            None => return origins.last().map_or(0, |(frame, _, _)| *frame),
+        };
+        let offset = byte_offset.unwrap_or(0);
+        if !(offset - byte).is_multiple_of(size) {
+            self.diagnose(
+                Some(track),
+                &format!(
+                    "byte offset {} of {} is not on a sector boundary",
+                    offset, file
+                ),
+            );
+        }
+        frame + (offset - byte) / size
+    }
+
+    // Records a segment of `length` frames of `sector_size` bytes at `frame` in `file`.
+    fn advance(&mut self, file: &str, frame: u64, length: Option<u64>, sector_size: u64) {
+        let origins = self.origins.entry(file.to_string()).or_default();
+        let byte = match origins.iter().rev().find(|(f, _, _)| *f <= frame) {
+            Some(&(f, byte, size)) => byte + (frame - f) * size,
+            None => frame * sector_size,
+        };
+        // The previous end marker is superseded by this segment
+        origins.retain(|(f, _, _)| *f < frame);
+        origins.push((frame, byte, sector_size));
+        if let Some(length) = length {
+            origins.push((frame + length, byte + length * sector_size, sector_size));
+        }
+    }
+
+    fn track(&mut self, no: u32) -> Result<TocTrack, CueError> {
+        let mut track = TocTrack::default();
+        let mode = self.word("a track mode")?;
+        track.mode = parse_toc_mode(&mode);
+        if track.mode.is_none() {
+            return Err(CueError::Parse(format!("unknown track mode {}", mode)));
+        }
+        if let Some(sub) = self.peek_word() {
+            if sub == "RW" || sub == "RW_RAW" {
+                let sub = sub.to_string();
+                self.pos += 1;
+                self.diagnose(Some(no), &format!("sub-channel mode {} dropped", sub));
+            }
+        }
+        let sector_size = track.mode.map_or(2352, TrackMode::sector_size);
+
+        loop {
+            let word = match self.peek_word() {
+                Some(word) if word != "TRACK" => word.to_string(),
+                _ => break,
+            };
+            self.pos += 1;
+
+            match word.as_str() {
+                "NO" => {
+                    self.word("a flag")?;
+                }
+                "COPY" => track.flags.push("DCP".to_string()),
+                "PRE_EMPHASIS" => track.flags.push("PRE".to_string()),
+                "FOUR_CHANNEL_AUDIO" => track.flags.push("4CH".to_string()),
+                "TWO_CHANNEL_AUDIO" => (),
+                "ISRC" => track.isrc = Some(self.string("an ISRC")?),
+                "CD_TEXT" => track.cd_text = self.cd_text(Some(no))?,
+                "PREGAP" => {
+                    let length = self.length(Some(no))?;
+                    let before = track.length();
+                    track.segments.push(Segment::Silence(length));
+                    track.start = Some(before + length);
+                }
+                "SILENCE" => {
+                    let length = self.length(Some(no))?;
+                    track.segments.push(Segment::Silence(length));
+                }
+                "ZERO" => {
+                    while self
+                        .peek_word()
+                        .is_some_and(|w| parse_toc_mode(w).is_some())
+                        || self.peek_word() == Some("RW")
+                        || self.peek_word() == Some("RW_RAW")
+                    {
+                        self.pos += 1;
+                    }
+                    let length = self.length(Some(no))?;
+                    track.segments.push(Segment::Silence(length));
+                }
+                "FILE" | "AUDIOFILE" => {
+                    let file = self.string("a file name")?;
+                    let byte_offset = self.byte_offset()?;
+                    let start = self.length(Some(no))?;
+                    let length = if self.is_length() {
+                        Some(self.length(Some(no))?)
+                    } else {
+                        None
+                    };
+                    let base = match byte_offset {
+                        Some(_) => self.place(no, &file, byte_offset, 2352),
+                        None => 0,
+                    };
+                    self.advance(&file, base + start, length, 2352);
+                    track.segments.push(Segment::Data {
+                        file,
+                        start: base + start,
+                        length,
+                    });
+                }
+                "DATAFILE" => {
+                    let file = self.string("a file name")?;
+                    let byte_offset = self.byte_offset()?;
+                    let length = if self.is_length() {
+                        Some(self.length(Some(no))?)
+                    } else {
+                        None
+                    };
+                    let start = self.place(no, &file, byte_offset, sector_size);
+                    self.advance(&file, start, length, sector_size);
+                    track.segments.push(Segment::Data {
+                        file,
+                        start,
+                        length,
+                    });
+                }
+                "START" => {
+                    track.start = Some(if self.is_length() {
+                        self.length(Some(no))?
+                    } else {
+                        track.length()
+                    });
+                }
+                "INDEX" => {
+                    let index = self.length(Some(no))?;
+                    track.indices.push(index);
+                }
+                "FIFO" => {
+                    self.string("a FIFO path")?;
+                    self.length(Some(no))?;
+                    self.diagnose(Some(no), "FIFO data dropped");
+                }
+                _ => {
+                    return Err(CueError::Parse(format!(
+                        "unknown TOC command {} in track {}",
+                        word, no
+                    )))
+                }
+            }
+        }
+
+        Ok(track)
+    }
+}
+
+fn file_format(file: &str, has_data: bool) -> &'static str {
+    if file.to_ascii_lowercase().ends_with(".wav") {
+        "WAVE"
+    } else if has_data {
+        "BINARY"
+    } else {
+        // cdrdao reads raw audio as big-endian samples
+        "MOTOROLA"
+    }
+}
+
+fn apply_cd_text(
+    items: Vec<(String, String)>,
+    title: &mut Option<String>,
+    performer: &mut Option<String>,
+    songwriter: &mut Option<String>,
+    comments: &mut Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut rest = Vec::new();
+    for (item, value) in items {
+        match item.as_str() {
+            "TITLE" => *title = Some(value),
+            "PERFORMER" => *performer = Some(value),
+            "SONGWRITER" => *songwriter = Some(value),
+            _ if CD_TEXT_REMS.contains(&item.as_str()) => comments.push((item, value)),
+            _ => rest.push((item, value)),
+        }
+    }
+    rest
+}
+
+/// Parses a cdrdao TOC file into a [`Cue`](../cue/struct.Cue.html).
+///
+/// Consecutive tracks reading the same data file share a FILE; WAVE files are detected by
+/// extension, files holding data tracks are BINARY, and other audio files MOTOROLA, as cdrdao
+/// reads raw audio as big-endian. A PREGAP (or silence before START) becomes a CUE PREGAP,
+/// file data before START becomes INDEX 00, INDEX commands become INDEX 02 and up, and trailing
+/// silence becomes a POSTGAP. COPY, PRE_EMPHASIS and FOUR_CHANNEL_AUDIO become the DCP, PRE and
+/// 4CH flags.
+///
+/// CD_TEXT TITLE, PERFORMER and SONGWRITER map to the CUE commands, UPC_EAN to CATALOG (unless
+/// one is set) and ISRC to the track ISRC (likewise). ARRANGER, COMPOSER, DISC_ID and MESSAGE
+/// are kept as `REM` comments. Only the first CD_TEXT language is read.
+///
+/// Everything that cannot be represented, such as other languages, binary CD-TEXT items,
+/// silence inside a track or segments from another file, is reported as a diagnostic.
+///
+/// # Example
+///
+/// ```
+/// use rcue::toc::from_toc;
+///
+/// let toc = "CD_DA\nTRACK AUDIO\nFILE \"disc.wav\" 0 04:17:52\nTRACK AUDIO\nFILE \"disc.wav\" 04:17:52\n";
+/// let (cue, diagnostics) = from_toc(toc).unwrap();
+/// assert_eq!(cue.files[0].file, "disc.wav");
+/// assert_eq!(cue.files[0].tracks[1].start().unwrap().as_secs(), 257);
+/// assert!(diagnostics.is_empty());
+/// ```
+///
+/// # Failures
+///
+/// Fails on syntax errors, unknown commands or track modes, or tracks without data.
+pub fn from_toc(text: &str) -> Result<(Cue, Vec<Diagnostic>), CueError> {
+    let mut parser = Parser {
+        tokens: tokenize(text.trim_start_matches('\u{feff}'))?,
+        pos: 0,
+        diagnostics: Vec::new(),
+        origins: HashMap::new(),
+    };
+    let mut cue = Cue::new();
+    let mut disc_text = Vec::new();
+    let mut tracks = Vec::new();
+
+    while let Some(token) = parser.next() {
+        let word = match token {
+            Token::Word(word) => word,
+            other => {
+                return Err(CueError::Parse(format!(
+                    "unexpected {:?} in TOC header",
+                    other
+                )))
+            }
+        };
+        match word.as_str() {
+            "CD_DA" | "CD_ROM" | "CD_ROM_XA" | "CD_I" => (),
+            "CATALOG" => cue.catalog = Some(parser.string("a catalog number")?),
+            "CD_TEXT" => disc_text = parser.cd_text(None)?,
+            "TRACK" => {
+                let no = tracks.len() as u32 + 1;
+                tracks.push(parser.track(no)?);
+            }
+            _ => return Err(CueError::Parse(format!("unknown TOC command {}", word))),
+        }
+    }
+
+    let mut comments = Vec::new();
+    for (item, value) in apply_cd_text(
+        disc_text,
+        &mut cue.title,
+        &mut cue.performer,
+        &mut cue.songwriter,
+        &mut comments,
+    ) {
+        match item.as_str() {
+            "UPC_EAN" if cue.catalog.is_none() => cue.catalog = Some(value),
+            "UPC_EAN" => (),
+            _ => parser.diagnose(None, &format!("CD_TEXT item {} dropped", item)),
+        }
+    }
+    cue.comments = comments;
+
+    let data_files: Vec<String> = tracks
+        .iter()
+        .filter(|track| track.mode != Some(TrackMode::Audio))
+        .flat_map(|track| track.segments.iter())
+        .filter_map(|segment| match segment {
+            Segment::Data { file, .. } => Some(file.clone()),
+            Segment::Silence(_) => None,
+        })
+        .collect();
+
+    for (i, toc_track) in tracks.into_iter().enumerate() {
+        let no = i as u32 + 1;
+        let mode = toc_track.mode.unwrap_or(TrackMode::Audio);
+        let mut track = Track::new(&format!("{:02}", no), mode.as_str());
+        track.flags = toc_track.flags;
+        track.isrc = toc_track.isrc;
+
+        let mut comments = Vec::new();
+        for (item, value) in apply_cd_text(
+            toc_track.cd_text,
+            &mut track.title,
+            &mut track.performer,
+            &mut track.songwriter,
+            &mut comments,
+        ) {
+            match item.as_str() {
+                "ISRC" if track.isrc.is_none() => track.isrc = Some(value),
+                "ISRC" => (),
+                _ => parser.diagnose(Some(no), &format!("CD_TEXT item {} dropped", item)),
+            }
+        }
+        track.comments = comments;
+
+        // Silence before the first data segment, and the data segment itself
+        let mut lead = 0;
+        let mut data: Option<(String, u64, Option<u64>)> = None;
+        let mut postgap = 0;
+        for segment in &toc_track.segments {
+            match (segment, &data) {
+                (Segment::Silence(length), None) => lead += length,
+                (Segment::Silence(length), Some(_)) => postgap += length,
+                (
+                    Segment::Data {
+                        file,
+                        start,
+                        length,
+                    },
+                    None,
+                ) => data = Some((file.clone(), *start, *length)),
+                (Segment::Data { file, start, .. }, Some((first, first_start, first_length))) => {
+                    let contiguous = file == first
+                        && postgap == 0
+                        && first_length.is_some_and(|length| first_start + length == *start);
+                    if !contiguous {
+                        parser.diagnose(Some(no), &format!("data segment from {} dropped", file));
+                    }
+                    if postgap > 0 {
+                        parser.diagnose(Some(no), "silence inside the track dropped");
+                        postgap = 0;
+                    }
+                }
+            }
+        }
+        let (file, file_start, _) = match data {
+            Some(data) => data,
+            None => {
+                return Err(CueError::Parse(format!(
+                    "track {} has no FILE or DATAFILE",
+                    no
+                )))
+            }
+        };
+
+        let start = toc_track.start.unwrap_or(0);
+        let index_01 = if start > lead {
+            if lead > 0 {
+                track.pregap = Some(frames_to_duration(lead));
+            }
+            track
+                .indices
+                .push(("00".to_string(), frames_to_duration(file_start)));
+            file_start + start - lead
+        } else {
+            if start > 0 {
+                track.pregap = Some(frames_to_duration(start));
+            }
+            if start < lead {
+                parser.diagnose(Some(no), "silence after START dropped");
+            }
+            file_start
+        };
+        track
+            .indices
+            .push(("01".to_string(), frames_to_duration(index_01)));
+        for (i, index) in toc_track.indices.iter().enumerate() {
+            track.indices.push((
+                format!("{:02}", i + 2),
+                frames_to_duration(index_01 + index),
+            ));
+        }
+        if postgap > 0 {
+            track.postgap = Some(frames_to_duration(postgap));
+        }
+
+        if cue.files.last().map(|f| &f.file) != Some(&file) {
+            let format = file_format(&file, data_files.contains(&file));
+            cue.files.push(CueFile::new(&file, format));
+        }
+        cue.files.last_mut().unwrap().tracks.push(track);
+    }
+
+    Ok((cue, parser.diagnostics))
+}
+
+fn escape(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn cd_text_block(out: &mut String, disc: bool, items: &[(String, String)]) {
+    out.push_str("CD_TEXT {\n");
+    if disc {
+        out.push_str("  LANGUAGE_MAP {\n    0 : EN\n  }\n");
+    }
+    out.push_str("  LANGUAGE 0 {\n");
+    for (item, value) in items {
+        out.push_str(&format!("    {} {}\n", item, escape(value)));
+    }
+    out.push_str("  }\n}\n");
+}
+
+// Collects the CD_TEXT items of a disc or track, reporting comments that have no equivalent.
+fn cd_text_items(
+    fields: &[(&str, &Option<String>)],
+    comments: &[(String, String)],
+    track: Option<u32>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<(String, String)> {
+    let mut items: Vec<(String, String)> = fields
+        .iter()
+        .filter_map(|(item, value)| value.as_ref().map(|v| (item.to_string(), v.clone())))
+        .collect();
+    for (key, value) in comments {
+        let key = key.to_ascii_uppercase();
+        if CD_TEXT_REMS.contains(&key.as_str()) {
+            items.push((key, value.clone()));
+        } else {
+            diagnostics.push(Diagnostic::new(
+                track,
+                &format!("REM {} has no TOC equivalent, dropped", key),
+            ));
+        }
+    }
+    items
+}
+
+/// Writes a [`Cue`](../cue/struct.Cue.html) as a cdrdao TOC file.
+///
+/// The disc type is CD_DA for audio-only sheets, CD_ROM_XA if any track is MODE2 and CD_ROM
+/// otherwise. Each track reads its FILE from INDEX 00 (or INDEX 01) up to the next track of the
+/// same FILE, with START marking INDEX 01 and INDEX commands for later indices; audio tracks use
+/// FILE and data tracks DATAFILE with a byte offset. PREGAP and POSTGAP become PREGAP and
+/// SILENCE (or ZERO). TITLE, PERFORMER, SONGWRITER, and `REM` ARRANGER, COMPOSER, DISC_ID and
+/// MESSAGE comments are written as English CD_TEXT.
+///
+/// Everything that cannot be represented, such as other `REM` comments, the SCMS flag, CD-G or
+/// CD-I tracks, pregaps in a previous FILE, or little-endian BINARY audio, is reported as a
+/// diagnostic. Only the first session of a multi-session sheet is written; tracks of later
+/// sessions are dropped with a diagnostic.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse_from_file;
+/// use rcue::toc::to_toc;
+///
+/// let cue = parse_from_file("test/fixtures/pregap.cue", true).unwrap();
+/// let (toc, _) = to_toc(&cue);
+/// assert!(toc.contains("TRACK AUDIO\nNO COPY\n"));
+/// assert!(toc.contains("PREGAP 00:01:00\nFILE \"My Bloody Valentine - Loveless.wav\" 00:00:00 04:17:52\nSILENCE 00:02:00\n"));
+/// ```
+pub fn to_toc(cue: &Cue) -> (String, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    // A TOC file describes a single session
+    let first_session = cue.sessions.first().map(|session| session.no);
+    let in_first_session =
+        |track: &Track| track.session.is_none() || track.session == first_session;
+    let modes: Vec<TrackMode> = cue
+        .files
+        .iter()
+        .flat_map(|file| file.tracks.iter())
+        .filter(|track| in_first_session(track))
+        .map(|track| track.mode().unwrap_or(TrackMode::Audio))
+        .collect();
+
+    let disc_type = if modes.iter().all(|mode| *mode == TrackMode::Audio) {
+        "CD_DA"
+    } else if modes.iter().any(|mode| mode.as_str().starts_with("MODE2")) {
+        "CD_ROM_XA"
+    } else {
+        "CD_ROM"
+    };
+    let mut out = format!("{}\n", disc_type);
+
+    if let Some(ref catalog) = cue.catalog {
+        out.push_str(&format!("CATALOG {}\n", escape(catalog)));
+    }
+
+    let disc_items = cd_text_items(
+        &[
+            ("TITLE", &cue.title),
+            ("PERFORMER", &cue.performer),
+            ("SONGWRITER", &cue.songwriter),
+        ],
+        &cue.comments,
+        None,
+        &mut diagnostics,
+    );
+    let has_cd_text = !disc_items.is_empty()
+        || cue
+            .files
+            .iter()
+            .flat_map(|f| f.tracks.iter())
+            .any(|t| t.title.is_some() || t.performer.is_some() || t.songwriter.is_some());
+    if has_cd_text {
+        cd_text_block(&mut out, true, &disc_items);
+    }
+    if cue.cd_text_file.is_some() {
+        diagnostics.push(Diagnostic::new(None, "CDTEXTFILE dropped"));
+    }
+
+    let mut no = 0;
+    for file in &cue.files {
+        let starts: Vec<Option<u64>> = file
+            .tracks
+            .iter()
+            .map(|track| track.pregap_start().map(|start| duration_to_frames(&start)))
+            .collect();
+
+        for (i, track) in file.tracks.iter().enumerate() {
+            no += 1;
+            if !in_first_session(track) {
+                diagnostics.push(Diagnostic::new(
+                    Some(no),
+                    &format!(
+                        "track in session {} dropped, a TOC file holds a single session",
+                        track.session.unwrap_or(1)
+                    ),
+                ));
+                continue;
+            }
+            let mode = track.mode().unwrap_or(TrackMode::Audio);
+            let name = match toc_mode(mode) {
+                Some(name) => name,
+                None => {
+                    diagnostics.push(Diagnostic::new(
+                        Some(no),
+                        &format!(
+                            "{} tracks are not supported, written as AUDIO",
+                            mode.as_str()
+                        ),
+                    ));
+                    "AUDIO"
+                }
+            };
+            out.push_str(&format!("\n// Track {}\nTRACK {}\n", no, name));
+
+            let flag = |f: &str| track.flags.iter().any(|flag| flag.eq_ignore_ascii_case(f));
+            out.push_str(if flag("DCP") { "COPY\n" } else { "NO COPY\n" });
+            if mode == TrackMode::Audio {
+                out.push_str(if flag("PRE") {
+                    "PRE_EMPHASIS\n"
+                } else {
+                    "NO PRE_EMPHASIS\n"
+                });
+                out.push_str(if flag("4CH") {
+                    "FOUR_CHANNEL_AUDIO\n"
+                } else {
+                    "TWO_CHANNEL_AUDIO\n"
+                });
+            }
+            if flag("SCMS") {
+                diagnostics.push(Diagnostic::new(Some(no), "SCMS flag dropped"));
+            }
+            if let Some(ref isrc) = track.isrc {
+                out.push_str(&format!("ISRC {}\n", escape(isrc)));
+            }
+
+            let items = cd_text_items(
+                &[
+                    ("TITLE", &track.title),
+                    ("PERFORMER", &track.performer),
+                    ("SONGWRITER", &track.songwriter),
+                ],
+                &track.comments,
+                Some(no),
+                &mut diagnostics,
+            );
+            if !items.is_empty() {
+                cd_text_block(&mut out, false, &items);
+            }
+            if !track.previous_file_indices.is_empty() {
+                diagnostics.push(Diagnostic::new(
+                    Some(no),
+                    "pregap in the previous FILE dropped",
+                ));
+            }
+
+            if let Some(ref pregap) = track.pregap {
+                out.push_str(&format!(
+                    "PREGAP {}\n",
+                    frames_to_timestamp(duration_to_frames(pregap))
+                ));
+            }
+
+            let (start, index_01) = match (starts[i], track.start()) {
+                (Some(start), Some(index_01)) => (start, duration_to_frames(&index_01)),
+                _ => {
+                    diagnostics.push(Diagnostic::new(Some(no), "track without INDEX dropped"));
+                    continue;
+                }
+            };
+            let length = starts[i + 1..]
+                .iter()
+                .find_map(|s| *s)
+                .map(|next| next.saturating_sub(start));
+
+            if mode == TrackMode::Audio && file.format.eq_ignore_ascii_case("WAVE") {
+                out.push_str(&format!(
+                    "FILE {} {}",
+                    escape(&file.file),
+                    frames_to_timestamp(start)
+                ));
+            } else if mode == TrackMode::Audio {
+                if file.format.eq_ignore_ascii_case("BINARY") {
+                    diagnostics.push(Diagnostic::new(
+                        Some(no),
+                        "BINARY audio is little-endian, cdrdao needs --swap to read it",
+                    ));
+                }
+                out.push_str(&format!(
+                    "FILE {} #{} 0",
+                    escape(&file.file),
+                    file.sector_offset(start)
+                ));
+            } else {
+                out.push_str(&format!(
+                    "DATAFILE {} #{}",
+                    escape(&file.file),
+                    file.sector_offset(start)
+                ));
+            }
+            match length {
+                Some(length) => out.push_str(&format!(" {}\n", frames_to_timestamp(length))),
+                None => out.push('\n'),
+            }
+
+            if index_01 > start {
+                let pregap = track.pregap.as_ref().map_or(0, duration_to_frames);
+                out.push_str(&format!(
+                    "START {}\n",
+                    frames_to_timestamp(pregap + index_01 - start)
+                ));
+            }
+            for (index, time) in &track.indices {
+                if index.parse::<u32>().is_ok_and(|index| index > 1) {
+                    let frames = duration_to_frames(time).saturating_sub(index_01);
+                    out.push_str(&format!("INDEX {}\n", frames_to_timestamp(frames)));
+                }
+            }
+            if let Some(ref postgap) = track.postgap {
+                out.push_str(&format!(
+                    "{} {}\n",
+                    if mode == TrackMode::Audio {
+                        "SILENCE"
+                    } else {
+                        "ZERO"
+                    },
+                    frames_to_timestamp(duration_to_frames(postgap))
+                ));
+            }
+        }
+    }
+
+    (out, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+    use std::fs;
+
+    fn frames(time: &Option<std::time::Duration>) -> Option<u64> {
+        time.as_ref().map(duration_to_frames)
+    }
+
+    #[test]
+    fn test_mixed_toc() {
+        let toc = fs::read_to_string("test/fixtures/mixed.toc").unwrap();
+        let (cue, diagnostics) = from_toc(&toc).unwrap();
+
+        assert_eq!(cue.catalog, Some("0724384260910".to_string()));
+        assert_eq!(cue.title, Some("Loveless".to_string()));
+        assert_eq!(
+            cue.comments,
+            vec![("COMPOSER".to_string(), "Kevin Shields".to_string())]
+        );
+        assert_eq!(cue.files.len(), 1);
+        assert_eq!(cue.files[0].format, "BINARY");
+
+        let tracks = &cue.files[0].tracks;
+        assert_eq!(tracks[0].format, "MODE1/2352");
+        assert_eq!(frames(&tracks[0].start()), Some(0));
+
+        assert_eq!(tracks[1].title, Some("Only \"Shallow\"".to_string()));
+        assert_eq!(tracks[1].flags, vec!["DCP".to_string()]);
+        assert_eq!(tracks[1].isrc, Some("USRC17609839".to_string()));
+        assert_eq!(frames(&tracks[1].pregap), Some(150));
+        assert_eq!(frames(&tracks[1].start()), Some(750));
+        assert_eq!(frames(&tracks[1].index(2)), Some(5250));
+
+        assert_eq!(frames(&tracks[2].index(0)), Some(20077));
+        assert_eq!(frames(&tracks[2].start()), Some(20152));
+        assert_eq!(frames(&tracks[2].postgap), Some(30));
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::new(None, "binary CD_TEXT item GENRE dropped"),
+                Diagnostic::new(None, "CD_TEXT language 1 dropped"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let toc = fs::read_to_string("test/fixtures/mixed.toc").unwrap();
+        let (cue, _) = from_toc(&toc).unwrap();
+        let (written, diagnostics) = to_toc(&cue);
+        assert!(written.starts_with("CD_ROM\nCATALOG \"0724384260910\"\n"));
+        assert!(written.contains("DATAFILE \"image.bin\" #0 00:10:00\n"));
+        assert!(written.contains("FILE \"image.bin\" #1764000 0 04:17:52\nINDEX 01:00:00\n"));
+        assert_eq!(diagnostics.len(), 2);
+
+        let (reread, _) = from_toc(&written).unwrap();
+        assert_eq!(reread, cue);
+    }
+
+    #[test]
+    fn test_cue_round_trip() {
+        for path in &["test/fixtures/good.cue", "test/fixtures/pregap.cue"] {
+            let mut cue = parse_from_file(path, true).unwrap();
+            let (toc, diagnostics) = to_toc(&cue);
+            let (mut reread, _) = from_toc(&toc).unwrap();
+
+            assert!(diagnostics.contains(&Diagnostic::new(
+                None,
+                "REM DATE has no TOC equivalent, dropped"
+            )));
+            cue.comments.clear();
+            cue.cd_text_file = None;
+            for track in cue.files[0].tracks.iter_mut() {
+                track.flags.retain(|flag| flag != "SCMS" && flag != "DATA");
+                track.flags.sort();
+            }
+            for track in reread.files[0].tracks.iter_mut() {
+                track.flags.sort();
+            }
+            assert_eq!(reread, cue);
+        }
+    }
+
+    #[test]
+    fn test_start_from_file() {
+        let toc = "CD_DA
+TRACK AUDIO
+FILE \"a.wav\" 0 00:10:00
+TRACK AUDIO
+FILE \"a.wav\" 00:10:00 11337
+START 00:02:00
+TRACK AUDIO
+FILE \"b.raw\" 0
+";
+        let (cue, diagnostics) = from_toc(toc).unwrap();
+        assert_eq!(cue.files.len(), 2);
+        assert_eq!(cue.files[1].format, "MOTOROLA");
+        let track = &cue.files[0].tracks[1];
+        assert_eq!(frames(&track.index(0)), Some(750));
+        assert_eq!(frames(&track.start()), Some(900));
+        assert_eq!(track.pregap, None);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                Some(2),
+                "11337 samples is not a whole CD frame, truncated"
+            )]
+        );
+
+        let (written, _) = to_toc(&cue);
+        assert!(written.contains("FILE \"a.wav\" 00:10:00\nSTART 00:02:00\n"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(from_toc("CD_DA\nTRACK AUDIO\n").is_err());
+        assert!(from_toc("CD_DA\nTRACK BOGUS\nFILE \"a.wav\" 0\n").is_err());
+        assert!(from_toc("CD_DA\nTRACK AUDIO\nFILE \"a.wav\" 0\nBOGUS\n").is_err());
+        assert!(from_toc("CD_DA\nCATALOG \"123\n").is_err());
+    }
+
+    #[test]
+    fn test_sessions() {
+        let cue = parse_from_file("test/fixtures/sessions.cue", true).unwrap();
+        let (toc, diagnostics) = to_toc(&cue);
+        assert!(toc.starts_with("CD_DA\n"));
+        assert!(!toc.contains("Track 03"));
+        assert_eq!(
+            diagnostics.last(),
+            Some(&Diagnostic::new(
+                Some(3),
+                "track in session 2 dropped, a TOC file holds a single session"
+            ))
+        );
+    }
+}
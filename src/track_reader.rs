@@ -0,0 +1,369 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use cue::{CueFile, PregapMode};
+use errors::CueError;
+use wav::{header, swap_endianness, PcmFormat, PcmSource};
+
+/// Options for [`TrackReader`](struct.TrackReader.html).
+#[derive(Clone, Copy, Debug)]
+pub struct TrackReaderOptions {
+    /// Which track the audio between INDEX 00 and INDEX 01 belongs to
+    pub pregap: PregapMode,
+    /// Prefix the track's data with a synthesized WAVE header
+    pub wav_header: bool,
+}
+
+impl Default for TrackReaderOptions {
+    fn default() -> Self {
+        Self {
+            pregap: PregapMode::Append,
+            wav_header: false,
+        }
+    }
+}
+
+/// A `Read + Seek` view of a single track in a single-file image.
+///
+/// For `WAVE` files the view covers the track's PCM samples. For `BINARY` and `MOTOROLA`
+/// files it covers the track's sectors, sized according to each track's
+/// [`TrackMode`](../cue/enum.TrackMode.html). Big endian (`MOTOROLA`) audio is converted to
+/// little endian when a WAVE header is synthesized.
+///
+/// # Example
+///
+/// ```
+/// use rcue::parser::parse;
+/// use rcue::track_reader::{TrackReader, TrackReaderOptions};
+/// use std::io::{Cursor, Read};
+///
+/// let sheet = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:01\n";
+/// let cue = parse(&mut Cursor::new(sheet), true).unwrap();
+/// let image = Cursor::new(vec![0u8; 3 * 2352]);
+///
+/// let options = TrackReaderOptions::default();
+/// let mut reader = TrackReader::new(image, &cue.files[0], None, 1, options).unwrap();
+/// let mut track = Vec::new();
+/// reader.read_to_end(&mut track).unwrap();
+/// assert_eq!(track.len(), 2 * 2352);
+/// ```
+#[derive(Debug)]
+pub struct TrackReader<R> {
+    inner: R,
+    header: Vec<u8>,
+    start: u64,
+    len: u64,
+    pos: u64,
+    swap: Option<PcmFormat>,
+}
+
+impl<R: Read + Seek> TrackReader<R> {
+    /// Creates a view of the track at position `track` in `file.tracks`, whose data is read from
+    /// `inner`. `next` is the FILE after `file` in the sheet, whose first track's pregap may be
+    /// at the end of `file` (see
+    /// [`CueFile::track_spans_before`](../cue/struct.CueFile.html#method.track_spans_before)).
+    ///
+    /// # Failures
+    ///
+    /// Fails if the track does not exist or has no INDEX, if the FILE type is not supported,
+    /// or if the WAVE header of `inner` is invalid.
+    pub fn new(
+        mut inner: R,
+        file: &CueFile,
+        next: Option<&CueFile>,
+        track: usize,
+        options: TrackReaderOptions,
+    ) -> Result<Self, CueError> {
+        let span = file
+            .track_spans_before(options.pregap, next)
+            .get(track)
+            .cloned()
+            .ok_or_else(|| CueError::Parse(format!("no track at position {}", track)))?
+            .ok_or_else(|| CueError::Parse(format!("track at position {} has no INDEX", track)))?;
+        let pcm = PcmSource::open(&mut inner, &file.format)?;
+
+        let to_bytes = |frame: u64| {
+            if file.format.eq_ignore_ascii_case("WAVE") {
+                pcm.format.frames_to_bytes(frame)
+            } else {
+                file.sector_offset(frame)
+            }
+        };
+        let start = to_bytes(span.start).min(pcm.data_len);
+        let end = span
+            .end
+            .map_or(pcm.data_len, to_bytes)
+            .min(pcm.data_len)
+            .max(start);
+
+        let header = if options.wav_header {
+            header(&pcm.format, end - start)
+        } else {
+            Vec::new()
+        };
+        let swap = if options.wav_header && pcm.format.big_endian {
+            Some(pcm.format)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            inner,
+            header,
+            start: pcm.data_offset + start,
+            len: end - start,
+            pos: 0,
+            swap,
+        })
+    }
+
+    /// Returns the total length of the view in bytes, including any synthesized header.
+    pub fn len(&self) -> u64 {
+        self.header.len() as u64 + self.len
+    }
+
+    /// Returns true if the view contains no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Unwraps this `TrackReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_data(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let want = (buf.len() as u64).min(self.len - offset) as usize;
+
+        let format = match self.swap {
+            Some(format) => format,
+            None => {
+                self.inner.seek(SeekFrom::Start(self.start + offset))?;
+                return self.inner.read(&mut buf[..want]);
+            }
+        };
+
+        // Swapping needs whole samples, so read the sample-aligned window around the request.
+        let width = u64::from(format.bits_per_sample.div_ceil(8));
+        let aligned = offset - offset % width;
+        let mut window = vec![0u8; (offset - aligned) as usize + want + width as usize];
+        let window_len = (window.len() as u64).min(self.len - aligned) as usize;
+        self.inner.seek(SeekFrom::Start(self.start + aligned))?;
+        let mut read = 0;
+        while read < window_len {
+            match self.inner.read(&mut window[read..window_len])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        swap_endianness(&mut window[..read - read % width as usize], &format);
+
+        let skip = (offset - aligned) as usize;
+        let copied = read.saturating_sub(skip).min(want);
+        buf[..copied].copy_from_slice(&window[skip..skip + copied]);
+        Ok(copied)
+    }
+}
+
+impl<R: Read + Seek> Read for TrackReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let header_len = self.header.len() as u64;
+
+        let read = if self.pos < header_len {
+            let from = self.pos as usize;
+            let n = buf.len().min(self.header.len() - from);
+            buf[..n].copy_from_slice(&self.header[from..from + n]);
+            n
+        } else if self.pos < self.len() {
+            let offset = self.pos - header_len;
+            self.read_data(offset, buf)?
+        } else {
+            0
+        };
+
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for TrackReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+
+        match target {
+            Some(target) => {
+                self.pos = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cue::Span;
+    use parser::parse;
+    use std::io::Cursor;
+    use wav::read_header;
+
+    fn sheet(format: &str, modes: (&str, &str), second: &str) -> CueFile {
+        let text = format!(
+            "FILE \"image\" {}\n  TRACK 01 {}\n    INDEX 01 00:00:00\n  TRACK 02 {}\n{}",
+            format, modes.0, modes.1, second
+        );
+        parse(&mut Cursor::new(text), true).unwrap().files.remove(0)
+    }
+
+    fn sectors(sizes: &[usize]) -> Cursor<Vec<u8>> {
+        let mut image = Vec::new();
+        for (i, size) in sizes.iter().enumerate() {
+            image.extend(vec![i as u8; *size]);
+        }
+        Cursor::new(image)
+    }
+
+    #[test]
+    fn test_pregap_modes() {
+        let file = sheet(
+            "BINARY",
+            ("AUDIO", "AUDIO"),
+            "    INDEX 00 00:00:02\n    INDEX 01 00:00:03\n",
+        );
+
+        for (mode, len, first) in [
+            (PregapMode::Append, 2, 3),
+            (PregapMode::Prepend, 3, 2),
+            (PregapMode::Discard, 2, 3),
+        ] {
+            let options = TrackReaderOptions {
+                pregap: mode,
+                ..TrackReaderOptions::default()
+            };
+            let mut reader =
+                TrackReader::new(sectors(&[2352; 5]), &file, None, 1, options).unwrap();
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            assert_eq!(data.len(), len * 2352);
+            assert_eq!(data[0], first);
+        }
+    }
+
+    #[test]
+    fn test_pregap_in_previous_file() {
+        let text =
+            "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n\
+                    INDEX 00 00:00:03\nFILE \"b.bin\" BINARY\n    INDEX 01 00:00:00\n";
+        let cue = parse(&mut Cursor::new(text), true).unwrap();
+        let (file, next) = (&cue.files[0], Some(&cue.files[1]));
+
+        assert_eq!(
+            file.track_spans_before(PregapMode::Discard, next),
+            vec![Some(Span {
+                start: 0,
+                end: Some(3)
+            })]
+        );
+        assert_eq!(file.track_spans(PregapMode::Discard)[0].unwrap().end, None);
+
+        for (mode, len) in [
+            (PregapMode::Append, 5),
+            (PregapMode::Prepend, 3),
+            (PregapMode::Discard, 3),
+        ] {
+            let options = TrackReaderOptions {
+                pregap: mode,
+                ..TrackReaderOptions::default()
+            };
+            let reader = TrackReader::new(sectors(&[2352; 5]), file, next, 0, options).unwrap();
+            assert_eq!(reader.len(), len * 2352);
+        }
+    }
+
+    #[test]
+    fn test_mixed_sector_sizes() {
+        let file = sheet("BINARY", ("MODE1/2048", "AUDIO"), "    INDEX 01 00:00:02\n");
+        let image = sectors(&[2048, 2048, 2352]);
+        let mut reader =
+            TrackReader::new(image, &file, None, 1, TrackReaderOptions::default()).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![2u8; 2352]);
+    }
+
+    #[test]
+    fn test_sector_offset_after_leading_frames() {
+        let text = "FILE \"image\" BINARY\n  TRACK 01 MODE1/2048\n    INDEX 01 00:00:10\n";
+        let file = parse(&mut Cursor::new(text), true).unwrap().files.remove(0);
+        let offsets: Vec<u64> = (9..12).map(|frame| file.sector_offset(frame)).collect();
+        assert_eq!(offsets, vec![9 * 2352, 10 * 2352, 10 * 2352 + 2048]);
+    }
+
+    #[test]
+    fn test_wav_header_and_seek() {
+        let file = sheet("BINARY", ("AUDIO", "AUDIO"), "    INDEX 01 00:00:01\n");
+        let options = TrackReaderOptions {
+            wav_header: true,
+            ..TrackReaderOptions::default()
+        };
+        let mut reader = TrackReader::new(sectors(&[2352; 3]), &file, None, 1, options).unwrap();
+        assert_eq!(reader.len(), 44 + 2 * 2352);
+
+        let source = read_header(&mut reader).unwrap();
+        assert_eq!(source.data_offset, 44);
+        assert_eq!(source.data_len, 2 * 2352);
+
+        reader.seek(SeekFrom::End(-1)).unwrap();
+        let mut byte = [0u8; 1];
+        assert_eq!(reader.read(&mut byte).unwrap(), 1);
+        assert_eq!(byte[0], 2);
+        assert_eq!(reader.read(&mut byte).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-10_000)).is_err());
+    }
+
+    #[test]
+    fn test_motorola_swapped_at_odd_offset() {
+        let file = sheet("MOTOROLA", ("AUDIO", "AUDIO"), "    INDEX 01 00:00:01\n");
+        let image: Vec<u8> = (0..2 * 2352).map(|i| (i % 2) as u8 + 1).collect();
+        let options = TrackReaderOptions {
+            wav_header: true,
+            ..TrackReaderOptions::default()
+        };
+        let mut reader = TrackReader::new(Cursor::new(image), &file, None, 0, options).unwrap();
+        reader.seek(SeekFrom::Start(45)).unwrap();
+        let mut bytes = [0u8; 3];
+        reader.read_exact(&mut bytes).unwrap();
+        assert_eq!(bytes, [1, 2, 1]);
+    }
+
+    #[test]
+    fn test_wave_source() {
+        let file = sheet("WAVE", ("AUDIO", "AUDIO"), "    INDEX 01 00:00:01\n");
+        let mut wav = header(&PcmFormat::cd(), 2 * 2352);
+        wav.extend(vec![7u8; 2 * 2352]);
+        let reader = TrackReader::new(
+            Cursor::new(wav),
+            &file,
+            None,
+            0,
+            TrackReaderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(reader.len(), 2352);
+        assert!(TrackReader::new(
+            Cursor::new(Vec::new()),
+            &file,
+            None,
+            5,
+            TrackReaderOptions::default()
+        )
+        .is_err());
+    }
+}
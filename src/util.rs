@@ -1,3 +1,4 @@
+use std::iter;
 use std::str::Chars;
 use std::time::Duration;
 
@@ -69,9 +70,181 @@ pub fn timestamp_to_duration(s: &str) -> Result<Duration, CueError> {
     Ok(Duration::new(seconds, nanos))
 }
 
+/// Number of CD frames (sectors) in one second.
+pub const FRAMES_PER_SECOND: u64 = 75;
+
+/// Converts a [Duration](https://doc.rust-lang.org/nightly/std/time/duration/struct.Duration.html)
+/// to a number of CD frames, rounding to the nearest frame.
+///
+/// Durations produced by [`timestamp_to_duration`](fn.timestamp_to_duration.html) are truncated
+/// to whole nanoseconds, so rounding recovers the exact frame count.
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::{duration_to_frames, timestamp_to_duration};
+///
+/// let duration = timestamp_to_duration("04:17:52").unwrap();
+/// assert_eq!(duration_to_frames(&duration), (4 * 60 + 17) * 75 + 52);
+/// ```
+pub fn duration_to_frames(duration: &Duration) -> u64 {
+    duration.as_secs() * FRAMES_PER_SECOND
+        + (u64::from(duration.subsec_nanos()) * FRAMES_PER_SECOND + 500_000_000) / 1_000_000_000
+}
+
+/// Converts a number of CD frames to a
+/// [Duration](https://doc.rust-lang.org/nightly/std/time/duration/struct.Duration.html).
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use rcue::util::frames_to_duration;
+///
+/// assert_eq!(frames_to_duration(76), Duration::new(1, 13333333));
+/// ```
+pub fn frames_to_duration(frames: u64) -> Duration {
+    let nanos = (frames % FRAMES_PER_SECOND) * 1_000_000_000 / FRAMES_PER_SECOND;
+    Duration::new(frames / FRAMES_PER_SECOND, nanos as u32)
+}
+
+/// Formats a number of CD frames as a CUE timestamp (MM:SS:FF).
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::frames_to_timestamp;
+///
+/// assert_eq!(frames_to_timestamp(19327), "04:17:52");
+/// ```
+pub fn frames_to_timestamp(frames: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / FRAMES_PER_SECOND / 60,
+        frames / FRAMES_PER_SECOND % 60,
+        frames % FRAMES_PER_SECOND
+    )
+}
+
+/// Converts a number of CD frames to a number of samples (per channel) at `sample_rate`.
+///
+/// Sample rates that are not a multiple of 75 Hz are rounded down to the previous sample.
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::frames_to_samples;
+///
+/// assert_eq!(frames_to_samples(1, 44100), 588);
+/// assert_eq!(frames_to_samples(75, 48000), 48000);
+/// ```
+pub fn frames_to_samples(frames: u64, sample_rate: u32) -> u64 {
+    frames * u64::from(sample_rate) / FRAMES_PER_SECOND
+}
+
+/// Converts a number of CD frames to nanoseconds, rounded to the nearest nanosecond.
+///
+/// Unlike [`frames_to_duration`](fn.frames_to_duration.html), which truncates, this is exact for
+/// every whole second and off by at most half a nanosecond otherwise.
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::frames_to_nanos;
+///
+/// assert_eq!(frames_to_nanos(1), 13_333_333);
+/// assert_eq!(frames_to_nanos(2), 26_666_667);
+/// ```
+pub fn frames_to_nanos(frames: u64) -> u64 {
+    (frames * 1_000_000_000 * 2 + FRAMES_PER_SECOND) / (FRAMES_PER_SECOND * 2)
+}
+
+/// Escapes `&`, `<`, `>`, `"` and `'` for use in XML text and attribute values.
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::escape_xml;
+///
+/// assert_eq!(escape_xml("Rock & <Roll>"), "Rock &amp; &lt;Roll&gt;");
+/// ```
+pub fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replaces each `{key}` placeholder in `template` with its value from `fields`.
+///
+/// The template is scanned once, so placeholders inside a value are kept as they are. Path
+/// separators, control characters and characters reserved in Windows filenames are replaced
+/// with `_` in the values, as are the dots of a `.` or `..` value, so the result can be used as
+/// a filename.
+///
+/// # Example
+///
+/// ```
+/// use rcue::util::fill_template;
+///
+/// let name = fill_template("{no} - {title}.wav", &[("no", "01"), ("title", "AC/DC")]);
+/// assert_eq!(name, "01 - AC_DC.wav");
+/// ```
+pub fn fill_template(template: &str, fields: &[(&str, &str)]) -> String {
+    fn sanitize(s: &str) -> String {
+        if s == "." || s == ".." {
+            return s.replace('.', "_");
+        }
+        s.chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect()
+    }
+
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let placeholder = &rest[open..];
+        let value = placeholder.find('}').and_then(|close| {
+            let key = &placeholder[1..close];
+            fields
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| (close, value))
+        });
+        match value {
+            Some((close, value)) => {
+                filled.push_str(&sanitize(value));
+                rest = &placeholder[close + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
 /// Returns the next token from a [`Chars`](https://doc.rust-lang.org/std/str/struct.Chars.html).
 /// This does *not* ignore leading whitespace.
 ///
+/// In a quoted string, backslashes are literal except before a quotation mark, where each pair
+/// stands for one backslash and a remaining one escapes the quotation mark.
+///
 /// # Example
 ///
 /// ```
@@ -111,29 +284,33 @@ pub fn next_string(chars: &mut Chars, error: &str) -> Result<String, CueError> {
         .ok_or_else(|| CueError::Parse(error.to_string()))?;
 
     if first == '"' {
-        let mut escaped = false;
-        let string = chars
-            .take_while(|c| {
-                if !escaped && *c == '\\' {
-                    escaped = true;
-                    return true;
+        // Backslashes are literal, except that before a quotation mark each pair stands for one
+        // backslash and a remaining one escapes the quotation mark
+        let mut string = String::new();
+        let mut backslashes = 0;
+        for c in chars.by_ref() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    string.extend(iter::repeat_n('\\', backslashes / 2));
+                    if backslashes % 2 == 0 {
+                        backslashes = 0;
+                        break;
+                    }
+                    string.push('"');
+                    backslashes = 0;
                 }
-
-                if escaped {
-                    escaped = false;
-                    return true;
+                c => {
+                    string.extend(iter::repeat_n('\\', backslashes));
+                    string.push(c);
+                    backslashes = 0;
                 }
+            }
+        }
+        string.extend(iter::repeat_n('\\', backslashes));
+        let _next_space = chars.next();
 
-                *c != '"'
-            })
-            .collect::<String>();
-        let _next_space = chars.next().ok_or_else(|| {
-            CueError::Parse(
-                "Unexpected error: could not consume next space. This is likely a bug.".to_string(),
-            )
-        });
-
-        Ok(unescape_quotes(&string))
+        Ok(string)
     } else {
         let string = first.to_string() + &next_token(chars);
 
@@ -218,6 +395,32 @@ mod tests {
         assert!(timestamp_to_duration("P0003-06-04T12:30:05").is_err());
     }
 
+    #[test]
+    fn test_duration_frame_round_trip() {
+        for frames in &[0, 1, 74, 75, 76, 19327, 449_999] {
+            let timestamp = frames_to_timestamp(*frames);
+            let duration = timestamp_to_duration(&timestamp).unwrap();
+            assert_eq!(duration_to_frames(&duration), *frames);
+            assert_eq!(duration_to_frames(&frames_to_duration(*frames)), *frames);
+        }
+    }
+
+    #[test]
+    fn test_frames_to_timestamp() {
+        assert_eq!(frames_to_timestamp(0), "00:00:00");
+        assert_eq!(frames_to_timestamp(75 * 60 * 100), "100:00:00");
+    }
+
+    #[test]
+    fn test_fill_template() {
+        let fields = [("performer", "AC/DC"), ("title", "{performer}")];
+        let name = fill_template("{performer}/{title} {x}.wav", &fields);
+        assert_eq!(name, "AC_DC/{performer} {x}.wav");
+
+        let name = fill_template("{album}/{title}", &[("album", ".."), ("title", "...")]);
+        assert_eq!(name, "__/...");
+    }
+
     #[test]
     fn test_next_string_quotation_marks() {
         let quotes = r#""quotation \"\" marks""#.to_string();
@@ -0,0 +1,342 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use errors::CueError;
+use util::frames_to_samples;
+
+/// Layout of PCM samples in an audio file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcmFormat {
+    /// Number of interleaved channels
+    pub channels: u16,
+    /// Samples per second, per channel
+    pub sample_rate: u32,
+    /// Bits per sample, per channel
+    pub bits_per_sample: u16,
+    /// Whether samples are stored big endian (MOTOROLA) instead of little endian
+    pub big_endian: bool,
+}
+
+impl PcmFormat {
+    /// Red Book CD audio: 44.1KHz, 16bit, stereo, little endian.
+    pub fn cd() -> Self {
+        Self {
+            channels: 2,
+            sample_rate: 44_100,
+            bits_per_sample: 16,
+            big_endian: false,
+        }
+    }
+
+    /// Size in bytes of one sample across all channels.
+    pub fn block_align(&self) -> u64 {
+        u64::from(self.channels) * u64::from(self.bits_per_sample).div_ceil(8)
+    }
+
+    /// Converts a number of CD frames (75 frames = 1s) to a byte length, rounded down
+    /// to a whole sample.
+    pub fn frames_to_bytes(&self, frames: u64) -> u64 {
+        frames_to_samples(frames, self.sample_rate) * self.block_align()
+    }
+}
+
+/// Location and layout of the PCM data in an audio file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcmSource {
+    /// Sample layout
+    pub format: PcmFormat,
+    /// Byte offset of the first sample
+    pub data_offset: u64,
+    /// Length of the sample data in bytes
+    pub data_len: u64,
+}
+
+impl PcmSource {
+    /// Locates the PCM data of a file referenced by a FILE command of type `file_type`.
+    ///
+    /// `WAVE` files have their header read. `BINARY` and `MOTOROLA` files are raw CD
+    /// audio (little and big endian respectively) spanning the whole file.
+    ///
+    /// # Failures
+    ///
+    /// Fails if the file type is not supported, or if the WAVE header is invalid.
+    pub fn open<R: Read + Seek>(reader: &mut R, file_type: &str) -> Result<Self, CueError> {
+        match file_type.to_uppercase().as_ref() {
+            "WAVE" => read_header(reader),
+            "BINARY" | "MOTOROLA" => {
+                let data_len = reader.seek(SeekFrom::End(0))?;
+                Ok(Self {
+                    format: PcmFormat {
+                        big_endian: file_type.eq_ignore_ascii_case("MOTOROLA"),
+                        ..PcmFormat::cd()
+                    },
+                    data_offset: 0,
+                    data_len,
+                })
+            }
+            _ => Err(CueError::Parse(format!(
+                "unsupported FILE type for PCM access: {}",
+                file_type
+            ))),
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from(read_u16(bytes)) | u32::from(read_u16(&bytes[2..])) << 16
+}
+
+/// Reads a RIFF WAVE header and locates its `data` chunk.
+///
+/// Only integer PCM (including `WAVE_FORMAT_EXTENSIBLE` PCM) is supported. A `data` chunk
+/// length that runs past the end of the file (as written by some streaming encoders) is
+/// clamped to the file length.
+///
+/// # Failures
+///
+/// Fails if the header is not a PCM RIFF WAVE header, or has no channels, a sample rate of 0 or
+/// 0 bits per sample.
+pub fn read_header<R: Read + Seek>(reader: &mut R) -> Result<PcmSource, CueError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut riff = [0u8; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(CueError::Parse("not a RIFF WAVE file".to_string()));
+    }
+
+    let mut format = None;
+    let mut offset = 12u64;
+
+    loop {
+        let mut chunk = [0u8; 8];
+        if reader.read_exact(&mut chunk).is_err() {
+            return Err(CueError::Parse("missing WAVE data chunk".to_string()));
+        }
+        let len = u64::from(read_u32(&chunk[4..8]));
+        offset += 8;
+
+        match &chunk[0..4] {
+            b"fmt " => {
+                if len < 16 {
+                    return Err(CueError::Parse("WAVE fmt chunk too short".to_string()));
+                }
+                // WAVE_FORMAT_EXTENSIBLE needs 40 bytes; the rest is skipped with the chunk
+                let mut fmt = vec![0u8; len.min(40) as usize];
+                reader.read_exact(&mut fmt)?;
+                let tag = read_u16(&fmt[0..2]);
+                let is_pcm = tag == 1 || (tag == 0xFFFE && fmt.len() >= 26 && fmt[24] == 1);
+                if !is_pcm {
+                    return Err(CueError::Parse(format!(
+                        "unsupported WAVE format tag: {:#06x}",
+                        tag
+                    )));
+                }
+                let pcm = PcmFormat {
+                    channels: read_u16(&fmt[2..4]),
+                    sample_rate: read_u32(&fmt[4..8]),
+                    bits_per_sample: read_u16(&fmt[14..16]),
+                    big_endian: false,
+                };
+                if pcm.channels == 0 || pcm.sample_rate == 0 || pcm.bits_per_sample == 0 {
+                    return Err(CueError::Parse(format!(
+                        "invalid WAVE format: {} channels, {} Hz, {} bits",
+                        pcm.channels, pcm.sample_rate, pcm.bits_per_sample
+                    )));
+                }
+                format = Some(pcm);
+            }
+            b"data" => {
+                let format = format.ok_or_else(|| {
+                    CueError::Parse("WAVE data chunk before fmt chunk".to_string())
+                })?;
+                return Ok(PcmSource {
+                    format,
+                    data_offset: offset,
+                    data_len: len.min(file_len.saturating_sub(offset)),
+                });
+            }
+            _ => {}
+        }
+
+        offset += len + (len & 1);
+        reader.seek(SeekFrom::Start(offset))?;
+    }
+}
+
+/// Returns a canonical 44-byte RIFF WAVE header for `data_len` bytes of PCM in `format`.
+///
+/// The header always describes little endian samples; big endian data must be byte-swapped
+/// (see [`swap_endianness`](fn.swap_endianness.html)) before it is written after the header.
+pub fn header(format: &PcmFormat, data_len: u64) -> Vec<u8> {
+    let data_len = data_len.min(u64::from(u32::MAX - 36)) as u32;
+    let block_align = format.block_align() as u16;
+    let byte_rate = format.sample_rate * u32::from(block_align);
+
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&format.channels.to_le_bytes());
+    header.extend_from_slice(&format.sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+/// Writes a canonical RIFF WAVE header. See [`header`](fn.header.html).
+///
+/// # Failures
+///
+/// Fails if the header could not be written.
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    format: &PcmFormat,
+    data_len: u64,
+) -> Result<(), CueError> {
+    writer.write_all(&header(format, data_len))?;
+    Ok(())
+}
+
+/// Reverses the byte order of every sample in `buf` in place.
+pub fn swap_endianness(buf: &mut [u8], format: &PcmFormat) {
+    let width = usize::from(format.bits_per_sample.div_ceil(8));
+    if width > 1 {
+        for sample in buf.chunks_mut(width) {
+            sample.reverse();
+        }
+    }
+}
+
+/// Copies `len` bytes of PCM from `reader` to `writer`, converting big endian samples to
+/// little endian if `format` requires it.
+///
+/// Returns the number of bytes copied, which is less than `len` if `reader` ran out of data.
+///
+/// # Failures
+///
+/// Fails on IO errors.
+pub fn copy_pcm<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    len: u64,
+    format: &PcmFormat,
+) -> Result<u64, CueError> {
+    let mut buf = vec![0u8; 64 * 1024 - (64 * 1024) % format.block_align().max(1) as usize];
+    let mut copied = 0u64;
+
+    while copied < len {
+        let want = buf.len().min((len - copied) as usize);
+        let mut read = 0;
+        while read < want {
+            match reader.read(&mut buf[read..want])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        if read == 0 {
+            break;
+        }
+        if format.big_endian {
+            swap_endianness(&mut buf[..read], format);
+        }
+        writer.write_all(&buf[..read])?;
+        copied += read as u64;
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_header_round_trip() {
+        let mut wav = header(&PcmFormat::cd(), 2352);
+        wav.extend(vec![0u8; 2352]);
+        let source = read_header(&mut Cursor::new(wav)).unwrap();
+        assert_eq!(source.format, PcmFormat::cd());
+        assert_eq!(source.data_offset, 44);
+        assert_eq!(source.data_len, 2352);
+    }
+
+    #[test]
+    fn test_skips_unknown_chunks() {
+        let mut wav = b"RIFF\0\0\0\0WAVELIST\x03\0\0\0abc\0".to_vec();
+        wav.extend_from_slice(&header(&PcmFormat::cd(), 4)[12..]);
+        wav.extend_from_slice(&[1, 2, 3, 4]);
+        let source = read_header(&mut Cursor::new(wav)).unwrap();
+        assert_eq!(source.data_offset, 56);
+        assert_eq!(source.data_len, 4);
+    }
+
+    #[test]
+    fn test_truncated_data_chunk() {
+        let mut wav = header(&PcmFormat::cd(), 9000);
+        wav.extend(vec![0u8; 100]);
+        let source = read_header(&mut Cursor::new(wav)).unwrap();
+        assert_eq!(source.data_len, 100);
+    }
+
+    #[test]
+    fn test_invalid_fmt() {
+        // Zero channels, sample rate or bit depth
+        for (offset, value) in [(22, 0u8), (24, 0), (34, 0)] {
+            let mut wav = header(&PcmFormat::cd(), 4);
+            wav[offset] = value;
+            wav[offset + 1] = value;
+            if offset == 24 {
+                wav[26] = 0;
+                wav[27] = 0;
+            }
+            wav.extend_from_slice(&[1, 2, 3, 4]);
+            assert!(read_header(&mut Cursor::new(wav)).is_err());
+        }
+
+        // A fmt chunk claiming 4 GiB is not allocated, and fails on the short read
+        let mut wav = header(&PcmFormat::cd(), 4);
+        wav[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_header(&mut Cursor::new(wav)).is_err());
+
+        // Extra fmt bytes are skipped
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt \x40\0\0\0".to_vec();
+        wav.extend_from_slice(&header(&PcmFormat::cd(), 4)[20..36]);
+        wav.extend(vec![0u8; 48]);
+        wav.extend_from_slice(b"data\x04\0\0\0\x01\x02\x03\x04");
+        let source = read_header(&mut Cursor::new(wav)).unwrap();
+        assert_eq!(source.format, PcmFormat::cd());
+        assert_eq!(source.data_offset, 92);
+    }
+
+    #[test]
+    fn test_not_wave() {
+        assert!(read_header(&mut Cursor::new(b"fLaC\0\0\0\0\0\0\0\0".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_motorola_swap() {
+        let mut source = Cursor::new(vec![0x12, 0x34, 0x56, 0x78]);
+        let pcm = PcmSource::open(&mut source, "MOTOROLA").unwrap();
+        assert!(pcm.format.big_endian);
+        source.set_position(0);
+        let mut out = Vec::new();
+        copy_pcm(&mut source, &mut out, 4, &pcm.format).unwrap();
+        assert_eq!(out, vec![0x34, 0x12, 0x78, 0x56]);
+    }
+
+    #[test]
+    fn test_frames_to_bytes() {
+        assert_eq!(PcmFormat::cd().frames_to_bytes(1), 2352);
+    }
+}
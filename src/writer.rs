@@ -0,0 +1,656 @@
+use std::io::{Cursor, Write};
+use std::iter;
+use std::time::Duration;
+
+use cue::{Cue, Track};
+use errors::CueError;
+use parser::parse;
+use util::{duration_to_frames, frames_to_timestamp};
+
+// Escapes quotation marks as `\"`, and doubles the backslashes before them or at the end of
+// the string so they are not read as escapes. Other backslashes, as in Windows paths, are kept.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let mut backslashes = 0;
+    for c in s.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                escaped.extend(iter::repeat_n('\\', backslashes * 2 + 1));
+                escaped.push('"');
+                backslashes = 0;
+            }
+            c => {
+                escaped.extend(iter::repeat_n('\\', backslashes));
+                escaped.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    escaped.extend(iter::repeat_n('\\', backslashes * 2));
+    escaped
+}
+
+/// Quotes a string for a CUE field if it is empty or contains whitespace or quotation marks.
+///
+/// Quotation marks inside the string are escaped as `\"`, and backslashes before them or at the
+/// end of the string are doubled.
+///
+/// # Example
+///
+/// ```
+/// use rcue::writer::quote;
+///
+/// assert_eq!(quote("1991"), "1991");
+/// assert_eq!(quote("My \"Cute\" Song"), r#""My \"Cute\" Song""#);
+/// assert_eq!(quote(r"C:\My Music\"), r#""C:\My Music\\""#);
+/// ```
+pub fn quote(s: &str) -> String {
+    if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"') {
+        always_quote(s)
+    } else {
+        s.to_string()
+    }
+}
+
+/// Quotes a string for a CUE field unconditionally, escaping it as [`quote`](fn.quote.html)
+/// does.
+///
+/// # Example
+///
+/// ```
+/// use rcue::writer::always_quote;
+///
+/// assert_eq!(always_quote("Loveless"), "\"Loveless\"");
+/// ```
+pub fn always_quote(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+/// Indentation of the commands in a FILE (one level) and in a TRACK (two levels).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    /// The given number of spaces per level
+    Spaces(usize),
+    /// One tab per level
+    Tab,
+}
+
+/// Which values are written in quotation marks. Values that need quoting (empty, or containing
+/// whitespace or quotation marks) are always quoted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quoting {
+    /// TITLE, PERFORMER, SONGWRITER, FILE and CDTEXTFILE are always quoted, other values only
+    /// when needed
+    Standard,
+    /// REM values are quoted as well
+    Always,
+    /// Every value is only quoted when needed
+    WhenNeeded,
+}
+
+/// Line endings of a written sheet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`, as written by most Windows rippers
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Layout of a written sheet. The default is the layout of [`to_lines`](fn.to_lines.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Indentation per level (default two spaces)
+    pub indent: Indent,
+    /// Quoting of values (default `Standard`)
+    pub quoting: Quoting,
+    /// Uppercase REM names, FILE types, TRACK modes and FLAGS, and write TRACK and INDEX
+    /// numbers with two digits (default false)
+    pub normalize_keywords: bool,
+    /// Sort REM comments by name, keeping the order of comments with the same name (default
+    /// false)
+    pub sort_rems: bool,
+    /// Line endings (default `Lf`)
+    pub line_ending: LineEnding,
+    /// Start the sheet with a UTF-8 byte order mark (default false)
+    pub bom: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: Indent::Spaces(2),
+            quoting: Quoting::Standard,
+            normalize_keywords: false,
+            sort_rems: false,
+            line_ending: LineEnding::Lf,
+            bom: false,
+        }
+    }
+}
+
+// Writes the values of commands according to `FormatOptions`.
+struct Style<'a> {
+    options: &'a FormatOptions,
+}
+
+impl<'a> Style<'a> {
+    fn indent(&self, level: usize) -> String {
+        match self.options.indent {
+            Indent::Spaces(n) => " ".repeat(n * level),
+            Indent::Tab => "\t".repeat(level),
+        }
+    }
+
+    // TITLE, PERFORMER, SONGWRITER, FILE and CDTEXTFILE values
+    fn name(&self, s: &str) -> String {
+        match self.options.quoting {
+            Quoting::WhenNeeded => quote(s),
+            _ => always_quote(s),
+        }
+    }
+
+    fn rem_value(&self, s: &str) -> String {
+        match self.options.quoting {
+            Quoting::Always => always_quote(s),
+            _ => quote(s),
+        }
+    }
+
+    fn keyword(&self, s: &str) -> String {
+        if self.options.normalize_keywords {
+            s.to_uppercase()
+        } else {
+            s.to_string()
+        }
+    }
+
+    fn number(&self, s: &str) -> String {
+        match s.parse::<u32>() {
+            Ok(n) if self.options.normalize_keywords => format!("{:02}", n),
+            _ => s.to_string(),
+        }
+    }
+
+    fn rems(&self, lines: &mut Vec<String>, comments: &[(String, String)], level: usize) {
+        let mut comments: Vec<&(String, String)> = comments.iter().collect();
+        if self.options.sort_rems {
+            comments.sort_by_key(|(key, _)| key.to_uppercase());
+        }
+        for (key, value) in comments {
+            lines.push(format!(
+                "{}REM {} {}",
+                self.indent(level),
+                self.keyword(key),
+                self.rem_value(value)
+            ));
+        }
+    }
+}
+
+fn duration_to_timestamp(duration: &Duration) -> String {
+    frames_to_timestamp(duration_to_frames(duration))
+}
+
+fn track_header(lines: &mut Vec<String>, style: &Style, track: &Track) {
+    let (one, two) = (style.indent(1), style.indent(2));
+    lines.push(format!(
+        "{}TRACK {} {}",
+        one,
+        style.number(&track.no),
+        style.keyword(&track.format)
+    ));
+    if let Some(ref title) = track.title {
+        lines.push(format!("{}TITLE {}", two, style.name(title)));
+    }
+    if let Some(ref performer) = track.performer {
+        lines.push(format!("{}PERFORMER {}", two, style.name(performer)));
+    }
+    if let Some(ref songwriter) = track.songwriter {
+        lines.push(format!("{}SONGWRITER {}", two, style.name(songwriter)));
+    }
+    if let Some(ref isrc) = track.isrc {
+        lines.push(format!("{}ISRC {}", two, isrc));
+    }
+    if !track.flags.is_empty() {
+        let flags: Vec<String> = track.flags.iter().map(|f| style.keyword(f)).collect();
+        lines.push(format!("{}FLAGS {}", two, flags.join(" ")));
+    }
+    style.rems(lines, &track.comments, 2);
+    for line in &track.unknown {
+        lines.push(format!("{}{}", two, line.trim()));
+    }
+    if let Some(ref pregap) = track.pregap {
+        lines.push(format!("{}PREGAP {}", two, duration_to_timestamp(pregap)));
+    }
+}
+
+fn track_indices(lines: &mut Vec<String>, style: &Style, indices: &[(String, Duration)]) {
+    for (idx, time) in indices {
+        lines.push(format!(
+            "{}INDEX {} {}",
+            style.indent(2),
+            style.number(idx),
+            duration_to_timestamp(time)
+        ));
+    }
+}
+
+// Starts the session of `track` if it differs from `current`, ending the previous session with
+// its lead-out.
+fn session_start(
+    lines: &mut Vec<String>,
+    cue: &Cue,
+    track: &Track,
+    current: &mut Option<u32>,
+    indent: &str,
+) {
+    if track.session.is_none() || track.session == *current {
+        return;
+    }
+    if let Some(lead_out) = session_lead_out(cue, *current) {
+        lines.push(format!(
+            "{}REM LEAD-OUT {}",
+            indent,
+            duration_to_timestamp(&lead_out)
+        ));
+    }
+    *current = track.session;
+
+    lines.push(format!(
+        "{}REM SESSION {:02}",
+        indent,
+        track.session.unwrap_or(1)
+    ));
+    if let Some(session) = cue
+        .sessions
+        .iter()
+        .find(|session| Some(session.no) == *current)
+    {
+        if let Some(ref lead_in) = session.lead_in {
+            lines.push(format!(
+                "{}REM LEAD-IN {}",
+                indent,
+                duration_to_timestamp(lead_in)
+            ));
+        }
+        if let Some(ref pregap) = session.pregap {
+            lines.push(format!(
+                "{}REM PREGAP {}",
+                indent,
+                duration_to_timestamp(pregap)
+            ));
+        }
+    }
+}
+
+fn session_lead_out(cue: &Cue, session: Option<u32>) -> Option<Duration> {
+    cue.sessions
+        .iter()
+        .find(|s| Some(s.no) == session)
+        .and_then(|s| s.lead_out)
+}
+
+/// Serializes a [`Cue`](../cue/struct.Cue.html) into the lines of a CUE sheet.
+///
+/// Commands are written in a fixed order with two spaces of indentation per level. TITLE,
+/// PERFORMER, SONGWRITER, FILE and CDTEXTFILE values are always quoted; other values are only
+/// quoted when needed. Tracks with
+/// [`previous_file_indices`](../cue/struct.Track.html#structfield.previous_file_indices) are
+/// started before their FILE command. On multi-session sheets, `REM SESSION` (followed by
+/// `REM LEAD-IN` and `REM PREGAP`) is written before the first track of each session, before its
+/// FILE if the track starts one, and `REM LEAD-OUT` at the end of each session.
+pub fn to_lines(cue: &Cue) -> Vec<String> {
+    to_lines_with(cue, &FormatOptions::default())
+}
+
+/// Serializes a [`Cue`](../cue/struct.Cue.html) into the lines of a CUE sheet laid out
+/// according to `options`. Lines never end in whitespace.
+///
+/// See [`to_lines`](fn.to_lines.html) for the order of commands.
+pub fn to_lines_with(cue: &Cue, options: &FormatOptions) -> Vec<String> {
+    let style = Style { options };
+    let one = style.indent(1);
+    let mut lines = Vec::new();
+
+    style.rems(&mut lines, &cue.comments, 0);
+    if let Some(ref media_type) = cue.original_media_type {
+        lines.push(format!("REM ORIGINAL MEDIA-TYPE: {}", media_type));
+    }
+    if let Some(ref catalog) = cue.catalog {
+        lines.push(format!("CATALOG {}", quote(catalog)));
+    }
+    if let Some(ref path) = cue.cd_text_file {
+        lines.push(format!("CDTEXTFILE {}", style.name(path)));
+    }
+    if let Some(ref performer) = cue.performer {
+        lines.push(format!("PERFORMER {}", style.name(performer)));
+    }
+    if let Some(ref songwriter) = cue.songwriter {
+        lines.push(format!("SONGWRITER {}", style.name(songwriter)));
+    }
+    if let Some(ref title) = cue.title {
+        lines.push(format!("TITLE {}", style.name(title)));
+    }
+    for line in &cue.unknown {
+        lines.push(line.trim().to_string());
+    }
+
+    let mut session = None;
+    for file in &cue.files {
+        let mut tracks = file.tracks.iter().peekable();
+
+        if let Some(track) = tracks.peek() {
+            session_start(&mut lines, cue, track, &mut session, "");
+            if !track.previous_file_indices.is_empty() {
+                track_header(&mut lines, &style, track);
+                track_indices(&mut lines, &style, &track.previous_file_indices);
+            }
+        }
+
+        lines.push(format!(
+            "FILE {} {}",
+            style.name(&file.file),
+            style.keyword(&file.format)
+        ));
+        style.rems(&mut lines, &file.comments, 1);
+
+        let mut first = true;
+        for track in tracks {
+            if !first {
+                session_start(&mut lines, cue, track, &mut session, &one);
+            }
+            if !first || track.previous_file_indices.is_empty() {
+                track_header(&mut lines, &style, track);
+            }
+            first = false;
+            track_indices(&mut lines, &style, &track.indices);
+            if let Some(ref postgap) = track.postgap {
+                lines.push(format!(
+                    "{}POSTGAP {}",
+                    style.indent(2),
+                    duration_to_timestamp(postgap)
+                ));
+            }
+        }
+    }
+    if let Some(lead_out) = session_lead_out(cue, session) {
+        lines.push(format!("REM LEAD-OUT {}", duration_to_timestamp(&lead_out)));
+    }
+
+    for line in &mut lines {
+        let len = line.trim_end().len();
+        line.truncate(len);
+    }
+    lines
+}
+
+/// Serializes a [`Cue`](../cue/struct.Cue.html) into a CUE sheet string with `\n` line endings.
+///
+/// See [`to_lines`](fn.to_lines.html) for the layout.
+///
+/// # Example
+///
+/// ```
+/// use rcue::cue::{Cue, CueFile, Track};
+/// use rcue::util::timestamp_to_duration;
+/// use rcue::writer::to_string;
+///
+/// let mut track = Track::new("01", "AUDIO");
+/// track.indices.push(("01".to_string(), timestamp_to_duration("00:00:00").unwrap()));
+/// let mut file = CueFile::new("disc.wav", "WAVE");
+/// file.tracks.push(track);
+/// let mut cue = Cue::new();
+/// cue.title = Some("Disc".to_string());
+/// cue.files.push(file);
+///
+/// let expected = "TITLE \"Disc\"\nFILE \"disc.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+/// assert_eq!(to_string(&cue), expected);
+/// ```
+pub fn to_string(cue: &Cue) -> String {
+    to_string_with(cue, &FormatOptions::default())
+}
+
+/// Serializes a [`Cue`](../cue/struct.Cue.html) into a CUE sheet string laid out according to
+/// `options`, ending every line (including the last) with its line ending.
+pub fn to_string_with(cue: &Cue, options: &FormatOptions) -> String {
+    let ending = options.line_ending.as_str();
+    let mut out = String::new();
+    if options.bom {
+        out.push('\u{feff}');
+    }
+    for line in to_lines_with(cue, options) {
+        out.push_str(&line);
+        out.push_str(ending);
+    }
+    out
+}
+
+/// Formats the CUE sheet `text` canonically: it is parsed in strict mode and written with
+/// [`to_string_with`](fn.to_string_with.html). Case of commands, indentation, quoting, blank
+/// lines, trailing whitespace and line endings of the input do not matter, so formatting an
+/// already formatted sheet returns it unchanged.
+///
+/// # Example
+///
+/// ```
+/// use rcue::writer::{format, FormatOptions, Indent};
+///
+/// let options = FormatOptions {
+///     indent: Indent::Tab,
+///     normalize_keywords: true,
+///     ..FormatOptions::default()
+/// };
+/// let text = "title \"A\"\r\n\r\nfile a.wav wave\r\n track 1 audio\r\n  index 1 00:00:00 \r\n";
+/// let formatted = format(text, &options).unwrap();
+/// let expected = "TITLE \"A\"\nFILE \"a.wav\" WAVE\n\tTRACK 01 AUDIO\n\t\tINDEX 01 00:00:00\n";
+/// assert_eq!(formatted, expected);
+/// assert_eq!(format(&formatted, &options).unwrap(), formatted);
+/// ```
+///
+/// # Failures
+///
+/// Fails if the sheet can not be parsed in strict mode, as lines dropped by lenient parsing
+/// would be lost.
+pub fn format(text: &str, options: &FormatOptions) -> Result<String, CueError> {
+    // Strict parsing rejects blank lines, which carry nothing
+    let text: Vec<&str> = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let cue = parse(&mut Cursor::new(text.join("\n")), true)?;
+    Ok(to_string_with(&cue, options))
+}
+
+/// Writes a [`Cue`](../cue/struct.Cue.html) as a CUE sheet to a
+/// [`Write`](https://doc.rust-lang.org/std/io/trait.Write.html).
+///
+/// See [`to_lines`](fn.to_lines.html) for the layout.
+///
+/// # Failures
+///
+/// Fails if the sheet could not be written.
+pub fn write(cue: &Cue, writer: &mut dyn Write) -> Result<(), CueError> {
+    writer.write_all(to_string(cue).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_from_file;
+    use std::fs;
+
+    fn round_trip(path: &str) {
+        let cue = parse_from_file(path, true).unwrap();
+        let written = to_string(&cue);
+        let reparsed = parse(&mut Cursor::new(written), true).unwrap();
+        assert_eq!(reparsed, cue);
+    }
+
+    #[test]
+    fn test_round_trip_fixtures() {
+        round_trip("test/fixtures/good.cue");
+        round_trip("test/fixtures/unicode.cue");
+        round_trip("test/fixtures/pregap.cue");
+        round_trip("test/fixtures/comments.cue");
+        round_trip("test/fixtures/catalog.cue");
+        round_trip("test/fixtures/gaps_appended.cue");
+        round_trip("test/fixtures/sessions.cue");
+    }
+
+    #[test]
+    fn test_good_cue_output() {
+        let cue = parse_from_file("test/fixtures/good.cue", true).unwrap();
+        let lines = to_lines(&cue);
+        assert_eq!(lines[3], "REM COMMENT \"ExactAudioCopy v0.95b4\"");
+        assert_eq!(lines[8], "FILE \"My Bloody Valentine - Loveless.wav\" WAVE");
+        assert_eq!(lines[9], "  TRACK 01 AUDIO");
+        assert_eq!(lines[lines.len() - 1], "    INDEX 01 04:17:52");
+    }
+
+    #[test]
+    fn test_gaps_appended_output() {
+        let cue = parse_from_file("test/fixtures/gaps_appended.cue", true).unwrap();
+        let lines = to_lines(&cue);
+        let tail: Vec<&str> = lines[lines.len() - 6..]
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(
+            tail,
+            vec![
+                "  TRACK 02 AUDIO",
+                "    TITLE \"Loomer\"",
+                "    PERFORMER \"My Bloody Valentine\"",
+                "    INDEX 00 04:15:52",
+                "FILE \"02 - Loomer.wav\" WAVE",
+                "    INDEX 01 00:00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let mut cue = Cue::new();
+        cue.title = Some("My \"Cute\" Song".to_string());
+        let reparsed = parse(&mut Cursor::new(to_string(&cue)), true).unwrap();
+        assert_eq!(reparsed.title, cue.title);
+    }
+
+    #[test]
+    fn test_escaped_backslashes() {
+        let values = [
+            r"C:\dir\",
+            r"\\server\share\a.wav",
+            r#"say \"hi\""#,
+            r"ends in two \\",
+            r"\",
+        ];
+        for value in &values {
+            let mut cue = Cue::new();
+            cue.title = Some(value.to_string());
+            cue.comments
+                .push(("COMMENT".to_string(), value.to_string()));
+            let written = to_string(&cue);
+            let reparsed = parse(&mut Cursor::new(written), true).unwrap();
+            assert_eq!(reparsed.title, cue.title);
+            assert_eq!(reparsed.comments, cue.comments);
+
+            let formatted = format(&to_string(&cue), &FormatOptions::default()).unwrap();
+            assert_eq!(formatted, to_string(&cue));
+        }
+
+        let cue = parse(&mut Cursor::new(r#"FILE "C:\dir\a.wav" WAVE"#), true).unwrap();
+        assert_eq!(cue.files[0].file, r"C:\dir\a.wav");
+        assert_eq!(to_lines(&cue)[0], r#"FILE "C:\dir\a.wav" WAVE"#);
+    }
+
+    #[test]
+    fn test_format_options() {
+        let text = fs::read_to_string("test/fixtures/case_sensitivity.cue").unwrap();
+        let options = FormatOptions {
+            indent: Indent::Spaces(4),
+            quoting: Quoting::Always,
+            sort_rems: true,
+            line_ending: LineEnding::CrLf,
+            bom: true,
+            ..FormatOptions::default()
+        };
+        let formatted = format(&text, &options).unwrap();
+        assert!(formatted.starts_with(
+            "\u{feff}REM COMMENT \"ExactAudioCopy v0.95b4\"\r\nREM DATE \"1991\"\r\n"
+        ));
+        assert!(formatted.contains("\r\nPERFORMER \"My Bloody Valentine\"\r\n"));
+        assert!(formatted.contains("\r\n    TRACK 01 AUDIO\r\n        TITLE \"Only Shallow\"\r\n"));
+
+        let bare = FormatOptions {
+            quoting: Quoting::WhenNeeded,
+            ..FormatOptions::default()
+        };
+        let formatted = format(&text, &bare).unwrap();
+        assert!(formatted.contains("\nTITLE Loveless\n"));
+        assert!(formatted.contains("\nPERFORMER \"My Bloody Valentine\"\n"));
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let styles = [
+            FormatOptions::default(),
+            FormatOptions {
+                indent: Indent::Tab,
+                quoting: Quoting::Always,
+                normalize_keywords: true,
+                sort_rems: true,
+                line_ending: LineEnding::CrLf,
+                bom: true,
+            },
+            FormatOptions {
+                indent: Indent::Spaces(4),
+                quoting: Quoting::WhenNeeded,
+                ..FormatOptions::default()
+            },
+        ];
+        for name in &[
+            "good",
+            "case_sensitivity",
+            "empty_lines",
+            "comments",
+            "gaps_appended",
+            "pregap",
+            "sessions",
+            "unicode",
+        ] {
+            let text = fs::read_to_string(format!("test/fixtures/{}.cue", name)).unwrap();
+            for options in styles.iter() {
+                let once = format(&text, options).unwrap();
+                assert_eq!(
+                    format(&once, options).unwrap(),
+                    once,
+                    "{} {:?}",
+                    name,
+                    options
+                );
+                assert!(once.lines().all(|line| line.trim_end() == line));
+            }
+        }
+
+        let empty_lines = fs::read_to_string("test/fixtures/empty_lines.cue").unwrap();
+        let options = FormatOptions::default();
+        let formatted = format(&empty_lines, &options).unwrap();
+        assert!(formatted.starts_with("REM GENRE Alternative\nREM DATE 1991\n"));
+        assert!(!formatted.contains("\n\n"));
+        assert!(format("FILE", &options).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use errors::CueError;
+
+/// An XML element with its attributes, child elements and direct text content.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Element {
+    /// Element name
+    pub name: String,
+    /// Attributes in document order, with entities resolved
+    pub attributes: Vec<(String, String)>,
+    /// Child elements in document order
+    pub children: Vec<Element>,
+    /// Concatenated text and CDATA directly inside the element, with entities resolved
+    pub text: String,
+}
+
+impl Element {
+    /// Returns the first child element named `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the child elements named `name`.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// Returns the value of the attribute named `name`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the trimmed text of the first child element named `name`.
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.trim())
+    }
+}
+
+fn invalid(reason: &str) -> CueError {
+    CueError::Parse(format!("invalid XML: {}", reason))
+}
+
+/// Resolves the predefined entities and character references in XML text.
+pub fn unescape(s: &str) -> Result<String, CueError> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let end = rest[amp..]
+            .find(';')
+            .ok_or_else(|| invalid("unterminated entity"))?;
+        let entity = &rest[amp + 1..amp + end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if entity.starts_with("#x") || entity.starts_with("#X") {
+                    u32::from_str_radix(&entity[2..], 16).ok()
+                } else if let Some(decimal) = entity.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(std::char::from_u32)
+                    .ok_or_else(|| invalid(&format!("unknown entity &{};", entity)))?
+            }
+        };
+        out.push(c);
+        rest = &rest[amp + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+struct Reader<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // Moves past the next occurrence of `end`, returning the text before it.
+    fn skip_past(&mut self, end: &str) -> Result<&'a str, CueError> {
+        let rest = self.rest();
+        let i = rest
+            .find(end)
+            .ok_or_else(|| invalid(&format!("missing {}", end)))?;
+        self.pos += i + end.len();
+        Ok(&rest[..i])
+    }
+
+    fn name(&mut self) -> Result<&'a str, CueError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(invalid("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    // Skips the XML declaration, processing instructions, comments and a DOCTYPE.
+    fn skip_misc(&mut self) -> Result<(), CueError> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<!DOCTYPE") {
+                let rest = self.rest();
+                let subset = rest.find('[');
+                let close = rest.find('>').ok_or_else(|| invalid("missing >"))?;
+                if subset.is_some_and(|subset| subset < close) {
+                    self.skip_past("]")?;
+                }
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn element(&mut self) -> Result<Element, CueError> {
+        if !self.rest().starts_with('<') {
+            return Err(invalid("expected an element"));
+        }
+        self.pos += 1;
+        let mut element = Element {
+            name: self.name()?.to_string(),
+            ..Element::default()
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?.to_string();
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(invalid(&format!("attribute {} has no value", key)));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q @ '"') | Some(q @ '\'') => q,
+                _ => return Err(invalid(&format!("attribute {} is not quoted", key))),
+            };
+            self.pos += 1;
+            let value = self.skip_past(&quote.to_string())?;
+            element.attributes.push((key, unescape(value)?));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(invalid(&format!("</{}> closes <{}>", name, element.name)));
+                }
+                self.skip_past(">")?;
+                return Ok(element);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                let cdata = self.skip_past("]]>")?;
+                element.text.push_str(cdata);
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else if rest.is_empty() {
+                return Err(invalid(&format!("<{}> is not closed", element.name)));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                element.text.push_str(&unescape(&rest[..len])?);
+                self.pos += len;
+            }
+        }
+    }
+}
+
+/// Parses an XML document into its root element.
+///
+/// Only what chapter and DAT files need is supported: elements, attributes, text, CDATA and the
+/// predefined entities. Comments, processing instructions and the DOCTYPE are skipped.
+pub fn parse(s: &str) -> Result<Element, CueError> {
+    let mut reader = Reader {
+        s: s.trim_start_matches('\u{feff}'),
+        pos: 0,
+    };
+    reader.skip_misc()?;
+    let root = reader.element()?;
+    reader.skip_misc()?;
+    if !reader.rest().is_empty() {
+        return Err(invalid("content after the root element"));
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE a [ <!ENTITY x "y"> ]>
+<!-- comment -->
+<a k='v &amp; w'>
+  <b>one &lt;1&gt; &#65;&#x42;</b>
+  <c/><b><![CDATA[<two>]]></b>
+</a>
+"#;
+        let root = parse(xml).unwrap();
+        assert_eq!(root.name, "a");
+        assert_eq!(
+            root.attributes,
+            vec![("k".to_string(), "v & w".to_string())]
+        );
+        assert_eq!(root.child_text("b"), Some("one <1> AB"));
+        assert_eq!(root.children_named("b").count(), 2);
+        assert_eq!(root.children[2].text, "<two>");
+        assert!(root.child("c").is_some());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("<a><b></a>").is_err());
+        assert!(parse("<a>").is_err());
+        assert!(parse("<a>&bogus;</a>").is_err());
+        assert!(parse("<a/><b/>").is_err());
+    }
+}
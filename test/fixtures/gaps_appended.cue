@@ -0,0 +1,17 @@
+REM GENRE Alternative
+REM DATE 1991
+REM DISCID 860B640B
+REM COMMENT "ExactAudioCopy v0.95b4"
+PERFORMER "My Bloody Valentine"
+TITLE "Loveless"
+FILE "01 - Only Shallow.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Only Shallow"
+    PERFORMER "My Bloody Valentine"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Loomer"
+    PERFORMER "My Bloody Valentine"
+    INDEX 00 04:15:52
+FILE "02 - Loomer.wav" WAVE
+    INDEX 01 00:00:00
@@ -0,0 +1,20 @@
+REM ORIGINAL MEDIA-TYPE: CD
+PERFORMER "Björk"
+TITLE "Post"
+REM SESSION 01
+FILE "Post (Track 01).bin" BINARY
+  TRACK 01 AUDIO
+    TITLE "Army of Me"
+    INDEX 01 00:00:00
+FILE "Post (Track 02).bin" BINARY
+  TRACK 02 AUDIO
+    TITLE "Hyper-Ballad"
+    INDEX 00 00:00:00
+    INDEX 01 00:02:00
+REM LEAD-OUT 01:30:00
+REM SESSION 02
+REM LEAD-IN 01:00:00
+REM PREGAP 00:02:00
+FILE "Post (Track 03).bin" BINARY
+  TRACK 03 MODE2/2352
+    INDEX 01 00:00:00